@@ -11,32 +11,37 @@ use flume::{Receiver, Sender, TryRecvError};
 use songbird::{
     constants::*,
     driver::{
-        bench_internals::{mixer::Mixer, task_message::*, CryptoState},
+        bench_internals::{mixer::Mixer, task_message::*, Cipher, CryptoState},
+        transport::TokioUdpSocket,
         Bitrate,
+        CryptoMode,
     },
     input::{cached::Compressed, Input},
     tracks,
 };
-use tokio::runtime::{Handle, Runtime};
-use xsalsa20poly1305::{aead::NewAead, XSalsa20Poly1305 as Cipher, KEY_SIZE};
+use std::{net::UdpSocket, sync::Arc};
+use tokio::{
+    net::UdpSocket as AsyncUdpSocket,
+    runtime::{Handle, Runtime},
+};
+
+type Listeners = (
+    Receiver<CoreMessage>,
+    Receiver<EventMessage>,
+    Receiver<UdpRxMessage>,
+    Receiver<UdpTxMessage>,
+    UdpSocket,
+);
 
 // create a dummied task + interconnect.
 // measure perf at varying numbers of sources (binary 1--64) without passthrough support.
 
-fn dummied_mixer(
-    handle: Handle,
-) -> (
-    Mixer,
-    (
-        Receiver<CoreMessage>,
-        Receiver<EventMessage>,
-        Receiver<UdpRxMessage>,
-        Receiver<UdpTxMessage>,
-    ),
-) {
+fn dummied_mixer(handle: Handle) -> (Mixer, Listeners) {
     let (mix_tx, mix_rx) = flume::unbounded();
     let (core_tx, core_rx) = flume::unbounded();
     let (event_tx, event_rx) = flume::unbounded();
+    let (rx_mix_tx, _rx_mix_rx) = flume::unbounded();
+    let (recorder_tx, _recorder_rx) = flume::unbounded();
 
     let (udp_sender_tx, udp_sender_rx) = flume::unbounded();
     let (udp_receiver_tx, udp_receiver_rx) = flume::unbounded();
@@ -45,13 +50,29 @@ fn dummied_mixer(
         core: core_tx,
         events: event_tx,
         mixer: mix_tx,
+        rx_mixer: rx_mix_tx,
+        recorder: recorder_tx,
+        recording: Default::default(),
+        stats: Default::default(),
+    };
+
+    // Packets are sent to a local socket which is never read from.
+    let sink = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(sink.local_addr().unwrap()).unwrap();
+    socket.set_nonblocking(true).unwrap();
+
+    let socket = {
+        let _guard = handle.enter();
+        TokioUdpSocket::new(AsyncUdpSocket::from_std(socket).unwrap()).unwrap()
     };
 
     let mut out = Mixer::new(mix_rx, handle, ic, Default::default());
 
     let fake_conn = MixerConnection {
-        cipher: Cipher::new_from_slice(&vec![0u8; KEY_SIZE]).unwrap(),
+        cipher: Cipher::new(CryptoMode::Normal, &[0u8; 32]).unwrap(),
         crypto_state: CryptoState::Normal,
+        socket: Arc::new(socket),
         udp_rx: udp_receiver_tx,
         udp_tx: udp_sender_tx,
     };
@@ -60,21 +81,13 @@ fn dummied_mixer(
 
     out.skip_sleep = true;
 
-    (out, (core_rx, event_rx, udp_receiver_rx, udp_sender_rx))
+    (
+        out,
+        (core_rx, event_rx, udp_receiver_rx, udp_sender_rx, sink),
+    )
 }
 
-fn mixer_float(
-    num_tracks: usize,
-    handle: Handle,
-) -> (
-    Mixer,
-    (
-        Receiver<CoreMessage>,
-        Receiver<EventMessage>,
-        Receiver<UdpRxMessage>,
-        Receiver<UdpTxMessage>,
-    ),
-) {
+fn mixer_float(num_tracks: usize, handle: Handle) -> (Mixer, Listeners) {
     let mut out = dummied_mixer(handle);
 
     let floats = utils::make_sine(10 * STEREO_FRAME_SIZE, true);
@@ -90,18 +103,7 @@ fn mixer_float(
     out
 }
 
-fn mixer_float_drop(
-    num_tracks: usize,
-    handle: Handle,
-) -> (
-    Mixer,
-    (
-        Receiver<CoreMessage>,
-        Receiver<EventMessage>,
-        Receiver<UdpRxMessage>,
-        Receiver<UdpTxMessage>,
-    ),
-) {
+fn mixer_float_drop(num_tracks: usize, handle: Handle) -> (Mixer, Listeners) {
     let mut out = dummied_mixer(handle);
 
     let mut tracks = vec![];
@@ -116,17 +118,7 @@ fn mixer_float_drop(
     out
 }
 
-fn mixer_opus(
-    handle: Handle,
-) -> (
-    Mixer,
-    (
-        Receiver<CoreMessage>,
-        Receiver<EventMessage>,
-        Receiver<UdpRxMessage>,
-        Receiver<UdpTxMessage>,
-    ),
-) {
+fn mixer_opus(handle: Handle) -> (Mixer, Listeners) {
     // should add a single opus-based track.
     // make this fully loaded to prevent any perf cost there.
     let mut out = dummied_mixer(handle);
@@ -1,22 +1,35 @@
 #[cfg(feature = "driver-core")]
-use super::driver::{retry::Retry, CryptoMode, DecodeMode};
+use super::driver::{
+    retry::Retry,
+    transport::{TokioTransport, Transport},
+    CatchUpPolicy,
+    CryptoMode,
+    DecodeMode,
+    EncoderConfig,
+    SchedulerMode,
+};
 
 use std::time::Duration;
+#[cfg(feature = "driver-core")]
+use std::{num::NonZeroUsize, sync::Arc};
 
 /// Configuration for drivers and calls.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 pub struct Config {
     #[cfg(feature = "driver-core")]
-    /// Selected tagging mode for voice packet encryption.
+    /// Preferred tagging mode for voice packet encryption.
+    ///
+    /// If the voice server does not offer this mode, the best available mode is
+    /// negotiated instead: AEAD modes are chosen over the legacy XSalsa20Poly1305 modes.
     ///
-    /// Defaults to [`CryptoMode::Normal`].
+    /// Defaults to [`CryptoMode::Aes256Gcm`].
     ///
     /// Changes to this field will not immediately apply if the
     /// driver is actively connected, but will apply to subsequent
     /// sessions.
     ///
-    /// [`CryptoMode::Normal`]: CryptoMode::Normal
+    /// [`CryptoMode::Aes256Gcm`]: CryptoMode::Aes256Gcm
     pub crypto_mode: CryptoMode,
     #[cfg(feature = "driver-core")]
     /// Configures whether decoding and decryption occur for all received packets.
@@ -74,13 +87,78 @@ pub struct Config {
     ///
     /// Defaults to 10 seconds. If set to `None`, connections will never time out.
     pub driver_timeout: Option<Duration>,
+    #[cfg(feature = "driver-core")]
+    /// Number of received audio frames to buffer for each user before playout begins.
+    ///
+    /// Received packets are reordered within this window, and lost packets are
+    /// concealed once their slot is reached. Each 20ms, one frame from every user
+    /// is released as a [`VoiceTick`] event. Larger values tolerate more network jitter,
+    /// at the cost of added latency (20ms per frame).
+    ///
+    /// Defaults to 5 (100ms).
+    ///
+    /// Changes to this field will only affect users who begin speaking after the change.
+    ///
+    /// [`VoiceTick`]: crate::events::CoreEvent::VoiceTick
+    pub playout_buffer_length: NonZeroUsize,
+    #[cfg(feature = "driver-core")]
+    /// Number of additional frames each user's playout buffer may hold on top of
+    /// [`playout_buffer_length`], to absorb bursts of delayed packets.
+    ///
+    /// If a packet arrives too far ahead of the playout point, the oldest buffered
+    /// audio is skipped.
+    ///
+    /// Defaults to 3.
+    ///
+    /// [`playout_buffer_length`]: Config::playout_buffer_length
+    pub playout_spike_length: usize,
+    #[cfg(feature = "driver-core")]
+    /// Behaviour of the mixer when it falls behind its packet deadline.
+    ///
+    /// Defaults to [`CatchUpPolicy::Burst`].
+    ///
+    /// [`CatchUpPolicy::Burst`]: CatchUpPolicy::Burst
+    pub catch_up: CatchUpPolicy,
+    #[cfg(feature = "driver-core")]
+    /// Settings for the Opus encoder used to send mixed audio, other than its bitrate.
+    ///
+    /// Changes to this field are applied to a running driver immediately.
+    /// Defaults to [`EncoderConfig::default`].
+    ///
+    /// [`EncoderConfig::default`]: EncoderConfig::default
+    pub encoder: EncoderConfig,
+    #[cfg(feature = "driver-core")]
+    /// Selects whether each driver mixes audio on its own thread, or shares
+    /// a pool of threads with other drivers.
+    ///
+    /// Bots connected to many calls at once should use [`SchedulerMode::Pooled`],
+    /// sharing one [`Scheduler`] between all of their drivers (e.g., by setting
+    /// this on the config given to [`Songbird`]).
+    ///
+    /// Defaults to [`SchedulerMode::Dedicated`]. Changes to this field only
+    /// affect drivers created afterwards.
+    ///
+    /// [`SchedulerMode::Pooled`]: SchedulerMode::Pooled
+    /// [`SchedulerMode::Dedicated`]: SchedulerMode::Dedicated
+    /// [`Scheduler`]: crate::driver::Scheduler
+    /// [`Songbird`]: crate::Songbird
+    pub scheduler: SchedulerMode,
+    #[cfg(feature = "driver-core")]
+    /// Network transport used to open each voice connection's websocket and UDP socket.
+    ///
+    /// Defaults to [`TokioTransport`], which connects directly to Discord.
+    /// Other transports can route voice traffic through a proxy, or connect
+    /// to a local test server using a [`MemoryTransport`].
+    ///
+    /// [`MemoryTransport`]: crate::driver::transport::MemoryTransport
+    pub transport: Arc<dyn Transport>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             #[cfg(feature = "driver-core")]
-            crypto_mode: CryptoMode::Normal,
+            crypto_mode: CryptoMode::Aes256Gcm,
             #[cfg(feature = "driver-core")]
             decode_mode: DecodeMode::Decrypt,
             #[cfg(feature = "gateway-core")]
@@ -91,13 +169,25 @@ impl Default for Config {
             driver_retry: Default::default(),
             #[cfg(feature = "driver-core")]
             driver_timeout: Some(Duration::from_secs(10)),
+            #[cfg(feature = "driver-core")]
+            playout_buffer_length: NonZeroUsize::new(5).expect("Playout buffer length is nonzero."),
+            #[cfg(feature = "driver-core")]
+            playout_spike_length: 3,
+            #[cfg(feature = "driver-core")]
+            catch_up: CatchUpPolicy::Burst,
+            #[cfg(feature = "driver-core")]
+            encoder: EncoderConfig::default(),
+            #[cfg(feature = "driver-core")]
+            scheduler: SchedulerMode::Dedicated,
+            #[cfg(feature = "driver-core")]
+            transport: Arc::new(TokioTransport),
         }
     }
 }
 
 #[cfg(feature = "driver-core")]
 impl Config {
-    /// Sets this `Config`'s chosen cryptographic tagging scheme.
+    /// Sets this `Config`'s preferred cryptographic tagging scheme.
     pub fn crypto_mode(mut self, crypto_mode: CryptoMode) -> Self {
         self.crypto_mode = crypto_mode;
         self
@@ -127,6 +217,42 @@ impl Config {
         self
     }
 
+    /// Sets this `Config`'s number of frames to buffer per user before playing out received audio.
+    pub fn playout_buffer_length(mut self, playout_buffer_length: NonZeroUsize) -> Self {
+        self.playout_buffer_length = playout_buffer_length;
+        self
+    }
+
+    /// Sets this `Config`'s number of extra frames which received audio buffers may hold.
+    pub fn playout_spike_length(mut self, playout_spike_length: usize) -> Self {
+        self.playout_spike_length = playout_spike_length;
+        self
+    }
+
+    /// Sets this `Config`'s behaviour when the mixer falls behind its packet deadline.
+    pub fn catch_up(mut self, catch_up: CatchUpPolicy) -> Self {
+        self.catch_up = catch_up;
+        self
+    }
+
+    /// Sets this `Config`'s Opus encoder settings.
+    pub fn encoder(mut self, encoder: EncoderConfig) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Sets this `Config`'s choice of dedicated or pooled mixer threads.
+    pub fn scheduler(mut self, scheduler: SchedulerMode) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Sets this `Config`'s network transport for voice connections.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
     /// This is used to prevent changes which would invalidate the current session.
     pub(crate) fn make_safe(&mut self, previous: &Config, connected: bool) {
         if connected {
@@ -8,7 +8,10 @@ use std::time::Duration;
 
 #[cfg(feature = "driver-core")]
 /// The voice gateway version used by the library.
-pub const VOICE_GATEWAY_VERSION: u8 = crate::model::constants::GATEWAY_VERSION;
+///
+/// This is newer than the version described by [`serenity_voice_model`]: sequence
+/// numbers and acknowledgements added since are handled by the driver itself.
+pub const VOICE_GATEWAY_VERSION: u8 = 8;
 
 #[cfg(feature = "driver-core")]
 /// Sample rate of audio to be sent to Discord.
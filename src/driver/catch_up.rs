@@ -0,0 +1,29 @@
+/// Behaviour of the mixer when it falls behind its 20ms packet deadline,
+/// for instance if its host is overloaded.
+///
+/// Late frames are reported via [`CoreEvent::MixerOverload`] under either policy.
+///
+/// [`CoreEvent::MixerOverload`]: crate::events::CoreEvent::MixerOverload
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CatchUpPolicy {
+    /// Frames which missed their deadline are sent as quickly as possible until
+    /// the mixer is back on schedule.
+    ///
+    /// No audio is lost, but listeners may hear a burst of sped-up or stuttering
+    /// audio while Discord's jitter buffer absorbs the backlog.
+    Burst,
+    /// Frames whose entire 20ms slot has passed are skipped, and the RTP timestamp
+    /// is advanced to match the wall clock.
+    ///
+    /// Playback resumes on schedule immediately, at the cost of a short gap in
+    /// audio (and tracks advancing more slowly than real time).
+    Drop,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for CatchUpPolicy {
+    fn default() -> Self {
+        CatchUpPolicy::Burst
+    }
+}
@@ -23,7 +23,7 @@ pub enum Error {
     InvalidLength(InvalidLength),
     /// Server did not return the expected crypto mode during negotiation.
     CryptoModeInvalid,
-    /// Selected crypto mode was not offered by server.
+    /// Server offered no supported crypto modes.
     CryptoModeUnavailable,
     /// An indicator that an endpoint URL was invalid.
     EndpointUrl,
@@ -106,7 +106,7 @@ impl fmt::Display for Error {
             Crypto(e) => e.fmt(f),
             InvalidLength(e) => e.fmt(f),
             CryptoModeInvalid => write!(f, "server changed negotiated encryption mode"),
-            CryptoModeUnavailable => write!(f, "server did not offer chosen encryption mode"),
+            CryptoModeUnavailable => write!(f, "server offered no supported encryption mode"),
             EndpointUrl => write!(f, "endpoint URL received from gateway was invalid"),
             IllegalDiscoveryResponse => write!(f, "IP discovery/NAT punching response was invalid"),
             IllegalIp => write!(f, "IP discovery/NAT punching response had bad IP value"),
@@ -2,6 +2,8 @@ pub mod error;
 
 use super::{
     tasks::{message::*, udp_rx, udp_tx, ws as ws_task},
+    transport::UdpTransport,
+    Cipher,
     Config,
     CryptoMode,
 };
@@ -15,25 +17,28 @@ use crate::{
     ws::{self, ReceiverExt, SenderExt, WsStream},
     ConnectionInfo,
 };
-use crypto_secretbox::{KeyInit, XSalsa20Poly1305 as Cipher};
 use discortp::discord::{IpDiscoveryPacket, IpDiscoveryType, MutableIpDiscoveryPacket};
 use error::{Error, Result};
 use flume::Sender;
-use std::{net::IpAddr, str::FromStr, sync::Arc};
-use tokio::{net::UdpSocket, spawn, time::timeout};
-use tracing::{debug, info, instrument};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::{spawn, time::timeout};
+use tracing::{debug, info, instrument, trace};
 use url::Url;
 
-#[cfg(all(feature = "rustls-marker", not(feature = "native-marker")))]
-use ws::create_rustls_client;
-
-#[cfg(feature = "native-marker")]
-use ws::create_native_tls_client;
-
-pub(crate) struct Connection {
+pub struct Connection {
     pub(crate) info: ConnectionInfo,
     pub(crate) ssrc: u32,
     pub(crate) ws: Sender<WsMessage>,
+    /// Sequence number of the last message received from the voice gateway.
+    seq_ack: Arc<Mutex<Option<u64>>>,
+    /// UDP state yet to be handed to the mixer by [`Connection::attach`].
+    mix_conn: Option<MixerConnection>,
 }
 
 impl Connection {
@@ -42,6 +47,20 @@ impl Connection {
         interconnect: &Interconnect,
         config: &Config,
         idx: usize,
+    ) -> Result<Connection> {
+        let mut connection = Connection::handshake(info, interconnect, config, idx).await?;
+        connection.attach(interconnect)?;
+
+        Ok(connection)
+    }
+
+    /// Connects to the voice server without handing the connection to the mixer,
+    /// which keeps using any older connection until [`Connection::attach`] is called.
+    pub(crate) async fn handshake(
+        info: ConnectionInfo,
+        interconnect: &Interconnect,
+        config: &Config,
+        idx: usize,
     ) -> Result<Connection> {
         if let Some(t) = config.driver_timeout {
             timeout(t, Connection::new_inner(info, interconnect, config, idx)).await?
@@ -58,14 +77,11 @@ impl Connection {
     ) -> Result<Connection> {
         let url = generate_url(&mut info.endpoint)?;
 
-        #[cfg(all(feature = "rustls-marker", not(feature = "native-marker")))]
-        let mut client = create_rustls_client(url).await?;
-
-        #[cfg(feature = "native-marker")]
-        let mut client = create_native_tls_client(url).await?;
+        let mut client = config.transport.connect_ws(url).await?;
 
         let mut hello = None;
         let mut ready = None;
+        let mut seq_ack = None;
 
         client
             .send_json(&GatewayEvent::from(Identify {
@@ -77,7 +93,14 @@ impl Connection {
             .await?;
 
         loop {
-            let value = match client.recv_json().await? {
+            let (value, seq) = match client.recv_json().await? {
+                Some(msg) => msg,
+                None => continue,
+            };
+
+            seq_ack = seq.or(seq_ack);
+
+            let value = match value {
                 Some(value) => value,
                 None => continue,
             };
@@ -106,12 +129,21 @@ impl Connection {
         let ready =
             ready.expect("Ready packet expected in connection initialisation, but not found.");
 
-        if !has_valid_mode(&ready.modes, config.crypto_mode) {
-            return Err(Error::CryptoModeUnavailable);
+        let mode = CryptoMode::negotiate(config.crypto_mode, &ready.modes)
+            .ok_or(Error::CryptoModeUnavailable)?;
+
+        if mode != config.crypto_mode {
+            debug!(
+                "Preferred crypto mode {:?} unavailable, using {:?}.",
+                config.crypto_mode, mode
+            );
         }
 
-        let udp = UdpSocket::bind("0.0.0.0:0").await?;
-        udp.connect((ready.ip, ready.port)).await?;
+        let udp: Arc<dyn UdpTransport> = config
+            .transport
+            .connect_udp(SocketAddr::new(ready.ip, ready.port))
+            .await?
+            .into();
 
         // Follow Discord's IP Discovery procedures, in case NAT tunnelling is needed.
         let mut bytes = [0; IpDiscoveryPacket::const_packet_size()];
@@ -127,7 +159,7 @@ impl Connection {
 
         udp.send(&bytes).await?;
 
-        let (len, _addr) = udp.recv_from(&mut bytes).await?;
+        let len = udp.recv(&mut bytes).await?;
         {
             let view =
                 IpDiscoveryPacket::new(&bytes[..len]).ok_or(Error::IllegalDiscoveryResponse)?;
@@ -158,14 +190,14 @@ impl Connection {
                     protocol: "udp".into(),
                     data: ProtocolData {
                         address,
-                        mode: config.crypto_mode.to_request_str().into(),
+                        mode: mode.to_request_str().into(),
                         port: view.get_port(),
                     },
                 }))
                 .await?;
         }
 
-        let cipher = init_cipher(&mut client, config.crypto_mode).await?;
+        let cipher = init_cipher(&mut client, mode, &mut seq_ack).await?;
 
         info!("Connected to: {}", info.endpoint);
 
@@ -175,35 +207,28 @@ impl Connection {
         let (udp_sender_msg_tx, udp_sender_msg_rx) = flume::unbounded();
         let (udp_receiver_msg_tx, udp_receiver_msg_rx) = flume::unbounded();
 
-        let (udp_rx, udp_tx) = {
-            let udp_rx = Arc::new(udp);
-            let udp_tx = Arc::clone(&udp_rx);
-            (udp_rx, udp_tx)
-        };
+        let (udp_rx, udp_tx) = (udp.clone(), udp.clone());
 
         let ssrc = ready.ssrc;
 
+        // Messages sent during the handshake must also be acknowledged.
+        let seq_ack = Arc::new(Mutex::new(seq_ack));
+
         let mix_conn = MixerConnection {
             cipher: cipher.clone(),
-            crypto_state: config.crypto_mode.into(),
+            crypto_state: mode.into(),
+            socket: udp,
             udp_rx: udp_receiver_msg_tx,
             udp_tx: udp_sender_msg_tx,
         };
 
-        interconnect
-            .mixer
-            .send(MixerMessage::Ws(Some(ws_msg_tx.clone())))?;
-
-        interconnect
-            .mixer
-            .send(MixerMessage::SetConn(mix_conn, ready.ssrc))?;
-
         spawn(ws_task::runner(
             interconnect.clone(),
             ws_msg_rx,
             client,
             ssrc,
             hello.heartbeat_interval,
+            seq_ack.clone(),
             idx,
             info.clone(),
         ));
@@ -212,6 +237,7 @@ impl Connection {
             interconnect.clone(),
             udp_receiver_msg_rx,
             cipher,
+            mode,
             config.clone(),
             udp_rx,
         ));
@@ -221,44 +247,70 @@ impl Connection {
             info,
             ssrc,
             ws: ws_msg_tx,
+            seq_ack,
+            mix_conn: Some(mix_conn),
         })
     }
 
+    /// Hands this connection's websocket and UDP state to the mixer.
+    pub(crate) fn attach(&mut self, interconnect: &Interconnect) -> Result<()> {
+        if let Some(mix_conn) = self.mix_conn.take() {
+            interconnect.stats.reset(self.ssrc);
+
+            interconnect
+                .mixer
+                .send(MixerMessage::Ws(Some(self.ws.clone())))?;
+
+            interconnect
+                .mixer
+                .send(MixerMessage::SetConn(mix_conn, self.ssrc))?;
+        }
+
+        Ok(())
+    }
+
     #[instrument(skip(self))]
     pub async fn reconnect(&mut self, config: &Config) -> Result<()> {
         if let Some(t) = config.driver_timeout {
-            timeout(t, self.reconnect_inner()).await?
+            timeout(t, self.reconnect_inner(config)).await?
         } else {
-            self.reconnect_inner().await
+            self.reconnect_inner(config).await
         }
     }
 
     #[instrument(skip(self))]
-    pub async fn reconnect_inner(&mut self) -> Result<()> {
+    pub async fn reconnect_inner(&mut self, config: &Config) -> Result<()> {
         let url = generate_url(&mut self.info.endpoint)?;
 
         // Thread may have died, we want to send to prompt a clean exit
         // (if at all possible) and then proceed as normal.
+        let mut client = config.transport.connect_ws(url).await?;
 
-        #[cfg(all(feature = "rustls-marker", not(feature = "native-marker")))]
-        let mut client = create_rustls_client(url).await?;
-
-        #[cfg(feature = "native-marker")]
-        let mut client = create_native_tls_client(url).await?;
+        // The voice server replays any messages sent after `seq_ack`.
+        let resume = GatewayEvent::from(Resume {
+            server_id: self.info.guild_id.into(),
+            session_id: self.info.session_id.clone(),
+            token: self.info.token.clone(),
+        });
+        let mut seq_ack = *self.seq_ack.lock();
 
         client
-            .send_json(&GatewayEvent::from(Resume {
-                server_id: self.info.guild_id.into(),
-                session_id: self.info.session_id.clone(),
-                token: self.info.token.clone(),
-            }))
+            .send_json(&ws::with_seq_ack(&resume, seq_ack)?)
             .await?;
 
         let mut hello = None;
         let mut resumed = None;
+        let mut replayed = vec![];
 
         loop {
-            let value = match client.recv_json().await? {
+            let (value, seq) = match client.recv_json().await? {
+                Some(msg) => msg,
+                None => continue,
+            };
+
+            seq_ack = seq.or(seq_ack);
+
+            let value = match value {
                 Some(value) => value,
                 None => continue,
             };
@@ -277,7 +329,9 @@ impl Connection {
                     }
                 },
                 other => {
-                    debug!("Expected resumed/hello; got: {:?}", other);
+                    // Messages missed while disconnected are replayed before `Resumed`.
+                    trace!("Replayed message during resume: {:?}", other);
+                    replayed.push(other);
                 },
             }
         }
@@ -287,6 +341,13 @@ impl Connection {
 
         self.ws
             .send(WsMessage::SetKeepalive(hello.heartbeat_interval))?;
+
+        // Replayed messages are only acknowledged once handed on, in case this attempt fails.
+        for event in replayed {
+            self.ws.send(WsMessage::Replayed(Box::new(event)))?;
+        }
+        *self.seq_ack.lock() = seq_ack;
+
         self.ws.send(WsMessage::Ws(Box::new(client)))?;
 
         info!("Reconnected to: {}", &self.info.endpoint);
@@ -294,6 +355,15 @@ impl Connection {
     }
 }
 
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("info", &self.info)
+            .field("ssrc", &self.ssrc)
+            .finish()
+    }
+}
+
 impl Drop for Connection {
     fn drop(&mut self) {
         info!("Disconnected");
@@ -312,9 +382,20 @@ fn generate_url(endpoint: &mut String) -> Result<Url> {
 }
 
 #[inline]
-async fn init_cipher(client: &mut WsStream, mode: CryptoMode) -> Result<Cipher> {
+async fn init_cipher(
+    client: &mut WsStream,
+    mode: CryptoMode,
+    seq_ack: &mut Option<u64>,
+) -> Result<Cipher> {
     loop {
-        let value = match client.recv_json().await? {
+        let (value, seq) = match client.recv_json().await? {
+            Some(msg) => msg,
+            None => continue,
+        };
+
+        *seq_ack = seq.or(*seq_ack);
+
+        let value = match value {
             Some(value) => value,
             None => continue,
         };
@@ -325,7 +406,7 @@ async fn init_cipher(client: &mut WsStream, mode: CryptoMode) -> Result<Cipher>
                     return Err(Error::CryptoModeInvalid);
                 }
 
-                return Ok(Cipher::new_from_slice(&desc.secret_key)?);
+                return Ok(Cipher::new(mode, &desc.secret_key)?);
             },
             other => {
                 debug!(
@@ -337,12 +418,3 @@ async fn init_cipher(client: &mut WsStream, mode: CryptoMode) -> Result<Cipher>
         }
     }
 }
-
-#[inline]
-fn has_valid_mode<T, It>(modes: It, mode: CryptoMode) -> bool
-where
-    T: for<'a> PartialEq<&'a str>,
-    It: IntoIterator<Item = T>,
-{
-    modes.into_iter().any(|s| s == mode.to_request_str())
-}
@@ -0,0 +1,185 @@
+use crate::constants::*;
+use audiopus::{
+    coder::Encoder as OpusEncoder,
+    Application,
+    Bitrate,
+    Channels,
+    Error as OpusError,
+    Signal,
+};
+use std::time::Duration;
+
+/// Settings for the Opus encoder used to send mixed audio.
+///
+/// Speech-focussed bots (e.g., TTS) may prefer [`Application::Voip`] with
+/// in-band FEC at a low bitrate, while music bots are best served by the defaults.
+/// The bitrate itself is set separately, via [`Driver::set_bitrate`].
+///
+/// [`Driver::set_bitrate`]: crate::driver::Driver::set_bitrate
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct EncoderConfig {
+    /// The kind of audio the encoder is tuned for.
+    ///
+    /// Defaults to [`Application::Audio`].
+    pub application: Application,
+    /// Whether to include in-band forward error correction data, allowing
+    /// listeners to recover from packet loss at the cost of some bitrate.
+    ///
+    /// This is only used by the encoder when [`packet_loss`] is nonzero.
+    /// Defaults to `false`.
+    ///
+    /// [`packet_loss`]: EncoderConfig::packet_loss
+    pub fec: bool,
+    /// The expected packet loss, as a percentage between 0 and 100.
+    ///
+    /// Defaults to `0`.
+    pub packet_loss: u8,
+    /// Whether to enable discontinuous transmission, reducing the bitrate
+    /// used on silent or background noise frames.
+    ///
+    /// Defaults to `false`.
+    pub dtx: bool,
+    /// Computational complexity of the encoder, between 0 and 10.
+    ///
+    /// Higher complexities give better quality audio at the same bitrate.
+    /// Defaults to `10`.
+    pub complexity: u8,
+    /// Hints the kind of signal being encoded.
+    ///
+    /// Defaults to [`Signal::Auto`].
+    pub signal: Signal,
+    /// Whether to send mono or stereo audio.
+    ///
+    /// Mono audio is downmixed from the stereo mix. Sources sent via Opus passthrough
+    /// are unchanged. Defaults to [`Channels::Stereo`]: [`Channels::Auto`] is treated
+    /// as stereo.
+    pub channels: Channels,
+    /// Amount of audio sent in each packet.
+    ///
+    /// Longer frames reduce packet overhead, at the cost of latency and robustness
+    /// to packet loss. Opus passthrough is only possible with 20ms frames.
+    /// Defaults to [`FrameLength::Ms20`].
+    pub frame_length: FrameLength,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self {
+            application: Application::Audio,
+            fec: false,
+            packet_loss: 0,
+            dtx: false,
+            complexity: 10,
+            signal: Signal::Auto,
+            channels: Channels::Stereo,
+            frame_length: FrameLength::Ms20,
+        }
+    }
+}
+
+impl EncoderConfig {
+    /// Sets the kind of audio the encoder is tuned for.
+    pub fn application(mut self, application: Application) -> Self {
+        self.application = application;
+        self
+    }
+
+    /// Sets whether to include in-band FEC data, and the packet loss percentage it should expect.
+    pub fn fec(mut self, fec: bool, packet_loss: u8) -> Self {
+        self.fec = fec;
+        self.packet_loss = packet_loss;
+        self
+    }
+
+    /// Sets whether to use discontinuous transmission.
+    pub fn dtx(mut self, dtx: bool) -> Self {
+        self.dtx = dtx;
+        self
+    }
+
+    /// Sets the computational complexity of the encoder.
+    pub fn complexity(mut self, complexity: u8) -> Self {
+        self.complexity = complexity;
+        self
+    }
+
+    /// Sets the kind of signal being encoded.
+    pub fn signal(mut self, signal: Signal) -> Self {
+        self.signal = signal;
+        self
+    }
+
+    /// Sets whether to send mono or stereo audio.
+    pub fn channels(mut self, channels: Channels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Sets the amount of audio sent in each packet.
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    pub(crate) fn is_stereo(&self) -> bool {
+        self.channels != Channels::Mono
+    }
+
+    /// Creates an encoder using these settings.
+    pub(crate) fn build(&self, bitrate: Bitrate) -> Result<OpusEncoder, OpusError> {
+        let channels = if self.is_stereo() {
+            Channels::Stereo
+        } else {
+            Channels::Mono
+        };
+
+        let mut encoder = OpusEncoder::new(SAMPLE_RATE, channels, self.application)?;
+        encoder.set_bitrate(bitrate)?;
+        encoder.set_inband_fec(self.fec)?;
+        encoder.set_packet_loss_perc(self.packet_loss)?;
+        encoder.set_dtx(self.dtx)?;
+        encoder.set_complexity(self.complexity)?;
+        encoder.set_signal(self.signal)?;
+
+        Ok(encoder)
+    }
+}
+
+/// Amount of audio held in each packet sent by the driver.
+///
+/// Audio is always mixed in 20ms blocks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FrameLength {
+    /// 10ms of audio per packet. Each mixed block is sent as two packets.
+    Ms10,
+    /// 20ms of audio per packet.
+    Ms20,
+    /// 40ms of audio per packet.
+    Ms40,
+    /// 60ms of audio per packet.
+    Ms60,
+}
+
+impl FrameLength {
+    /// Returns the duration of audio in each packet.
+    pub fn duration(self) -> Duration {
+        Duration::from_millis(match self {
+            FrameLength::Ms10 => 10,
+            FrameLength::Ms20 => 20,
+            FrameLength::Ms40 => 40,
+            FrameLength::Ms60 => 60,
+        })
+    }
+
+    /// Number of samples per channel in each packet.
+    pub(crate) fn samples(self) -> usize {
+        match self {
+            FrameLength::Ms10 => MONO_FRAME_SIZE / 2,
+            FrameLength::Ms20 => MONO_FRAME_SIZE,
+            FrameLength::Ms40 => 2 * MONO_FRAME_SIZE,
+            FrameLength::Ms60 => 3 * MONO_FRAME_SIZE,
+        }
+    }
+}
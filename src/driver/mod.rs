@@ -11,16 +11,32 @@
 #[cfg(feature = "internals")]
 pub mod bench_internals;
 
+mod catch_up;
 pub(crate) mod connection;
 mod crypto;
 mod decode_mode;
+mod encoder;
+mod recording;
 pub mod retry;
+mod scheduler;
+mod stats;
 pub(crate) mod tasks;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod transport;
+mod voice_mix_stream;
 
+pub use catch_up::CatchUpPolicy;
 use connection::error::{Error, Result};
 pub use crypto::CryptoMode;
-pub(crate) use crypto::CryptoState;
+pub(crate) use crypto::{Cipher, CryptoState};
 pub use decode_mode::DecodeMode;
+pub use encoder::{EncoderConfig, FrameLength};
+pub use recording::{Recording, RecordingError};
+pub use scheduler::{Scheduler, SchedulerMode};
+pub use stats::ConnectionStats;
+pub(crate) use stats::StatsRecorder;
+pub use voice_mix_stream::VoiceMixStream;
 
 #[cfg(feature = "builtin-queue")]
 use crate::tracks::{self, TrackQueue};
@@ -41,6 +57,7 @@ use core::{
     task::{Context, Poll},
 };
 use flume::{r#async::RecvFut, SendError, Sender};
+use std::sync::Arc;
 use tasks::message::CoreMessage;
 use tracing::instrument;
 
@@ -54,6 +71,7 @@ pub struct Driver {
     config: Config,
     self_mute: bool,
     sender: Sender<CoreMessage>,
+    stats: Arc<StatsRecorder>,
     #[cfg(feature = "builtin-queue")]
     queue: TrackQueue,
 }
@@ -64,27 +82,29 @@ impl Driver {
     /// This will create the core voice tasks in the background.
     #[inline]
     pub fn new(config: Config) -> Self {
-        let sender = Self::start_inner(config.clone());
+        let stats: Arc<StatsRecorder> = Default::default();
+        let sender = Self::start_inner(config.clone(), stats.clone());
 
         Driver {
             config,
             self_mute: false,
             sender,
+            stats,
             #[cfg(feature = "builtin-queue")]
             queue: Default::default(),
         }
     }
 
-    fn start_inner(config: Config) -> Sender<CoreMessage> {
+    fn start_inner(config: Config, stats: Arc<StatsRecorder>) -> Sender<CoreMessage> {
         let (tx, rx) = flume::unbounded();
 
-        tasks::start(config, rx, tx.clone());
+        tasks::start(config, rx, tx.clone(), stats);
 
         tx
     }
 
     fn restart_inner(&mut self) {
-        self.sender = Self::start_inner(self.config.clone());
+        self.sender = Self::start_inner(self.config.clone(), self.stats.clone());
 
         self.mute(self.self_mute);
     }
@@ -251,6 +271,57 @@ impl Driver {
         self.send(CoreMessage::RemoveGlobalEvents);
     }
 
+    /// Sets the volume applied to a user's audio when mixing together
+    /// received audio, for [`VoiceMix`] events and [`VoiceMixStream`]s.
+    ///
+    /// Users are identified by their SSRC, which can be found using
+    /// [`SpeakingStateUpdate`] events. Every user's volume defaults to `1.0`.
+    ///
+    /// [`VoiceMix`]: crate::events::CoreEvent::VoiceMix
+    /// [`SpeakingStateUpdate`]: crate::events::CoreEvent::SpeakingStateUpdate
+    #[instrument(skip(self))]
+    pub fn set_receive_volume(&mut self, ssrc: u32, volume: f32) {
+        self.send(CoreMessage::SetReceiveVolume(ssrc, volume));
+    }
+
+    /// Creates a new [`VoiceMixStream`], producing a single stereo mixdown of all
+    /// audio received from this point onwards.
+    ///
+    /// This requires that the driver is configured with [`DecodeMode::Decode`].
+    #[instrument(skip(self))]
+    pub fn voice_mix_stream(&mut self) -> VoiceMixStream {
+        let (tx, rx) = flume::bounded(VoiceMixStream::BUFFERED_FRAMES);
+        self.send(CoreMessage::AddVoiceMixStream(tx));
+
+        VoiceMixStream::new(rx)
+    }
+
+    /// Starts recording audio received from other users to disk, stopping
+    /// any recording already in progress.
+    ///
+    /// Any errors encountered while recording (such as the driver not being
+    /// configured with a suitable [`DecodeMode`]) end the recording, and are
+    /// reported via [`RecordingError`] events.
+    ///
+    /// [`RecordingError`]: crate::events::CoreEvent::RecordingError
+    #[instrument(skip(self))]
+    pub fn start_recording(&mut self, recording: Recording) {
+        self.send(CoreMessage::StartRecording(recording));
+    }
+
+    /// Stops the current recording, if any, and finalises its files.
+    #[instrument(skip(self))]
+    pub fn stop_recording(&mut self) {
+        self.send(CoreMessage::StopRecording);
+    }
+
+    /// Returns network and performance statistics for the current voice connection.
+    ///
+    /// Statistics are reset whenever the driver connects to a new voice session.
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.stats.snapshot()
+    }
+
     /// Sends a message to the inner tasks, restarting it if necessary.
     fn send(&mut self, status: CoreMessage) {
         // Restart thread if it errored.
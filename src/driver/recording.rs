@@ -0,0 +1,99 @@
+//! Recording of audio received from other users to disk.
+
+use std::{error::Error as StdError, fmt, io::Error as IoError, path::PathBuf};
+
+/// File layout and format for a call recording, started using [`Driver::start_recording`].
+///
+/// Ogg Opus recordings store received packets as-is, without decoding or re-encoding
+/// audio, and require that the driver is configured with at least [`DecodeMode::Decrypt`].
+/// Granule positions are derived from each packet's RTP timestamp, and any gaps
+/// (i.e., lost packets or periods of silence) are filled using silent Opus frames.
+///
+/// WAV recordings contain 16-bit stereo PCM audio at 48kHz, and require
+/// [`DecodeMode::Decode`].
+///
+/// All files begin at the moment the recording was started, so that users' tracks
+/// remain time-aligned with one another.
+///
+/// [`Driver::start_recording`]: super::Driver::start_recording
+/// [`DecodeMode::Decrypt`]: super::DecodeMode::Decrypt
+/// [`DecodeMode::Decode`]: super::DecodeMode::Decode
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Recording {
+    /// Writes one Ogg Opus file per user into the given directory, named `<ssrc>.opus`.
+    ///
+    /// The directory is created if it does not exist.
+    OggPerUser(PathBuf),
+    /// Writes a single Ogg file to the given path, containing one Opus stream per user.
+    ///
+    /// Each stream is tagged with the SSRC of its user (as an `SSRC` comment). When a
+    /// new user is first heard, the file begins a new link of a chained Ogg stream, in
+    /// which every user is given a new stream. Streams within a link play back together,
+    /// while links play back one after another.
+    OggMultitrack(PathBuf),
+    /// Writes one WAV file per user into the given directory, named `<ssrc>.wav`.
+    ///
+    /// The directory is created if it does not exist.
+    WavPerUser(PathBuf),
+    /// Writes a single WAV file to the given path, containing the mixdown of all
+    /// users' audio.
+    ///
+    /// This respects the volume set for each user via [`Driver::set_receive_volume`].
+    ///
+    /// [`Driver::set_receive_volume`]: super::Driver::set_receive_volume
+    WavMixdown(PathBuf),
+}
+
+impl Recording {
+    /// Returns whether this recording requires received audio to be decoded.
+    pub fn requires_decode(&self) -> bool {
+        matches!(self, Recording::WavPerUser(_) | Recording::WavMixdown(_))
+    }
+}
+
+/// Errors encountered while recording a call.
+///
+/// Any such error ends the current recording, and is reported via
+/// a [`RecordingError`] event.
+///
+/// [`RecordingError`]: crate::events::CoreEvent::RecordingError
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RecordingError {
+    /// The driver is not configured to decrypt received packets.
+    DecryptRequired,
+    /// The driver is not configured to decode received packets, as
+    /// required by WAV recordings.
+    DecodeRequired,
+    /// Failed to create or write to a recording's files.
+    Io(IoError),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to record call: ")?;
+        match self {
+            RecordingError::DecryptRequired =>
+                write!(f, "received packets must be decrypted to record a call"),
+            RecordingError::DecodeRequired =>
+                write!(f, "received packets must be decoded to record WAV audio"),
+            RecordingError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for RecordingError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            RecordingError::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<IoError> for RecordingError {
+    fn from(e: IoError) -> Self {
+        RecordingError::Io(e)
+    }
+}
@@ -0,0 +1,297 @@
+use super::tasks::{
+    disposal,
+    message::{DisposalMessage, Interconnect, MixerMessage},
+    mixer::{self, Mixer},
+};
+use crate::{constants::*, Config};
+use flume::{Receiver, Sender, TryRecvError};
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+use tokio::runtime::Handle;
+use tracing::{debug, trace};
+
+/// Selects which threads run each driver's audio mixing and encoding.
+///
+/// Changes to this setting only affect drivers created (or restarted) afterwards.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum SchedulerMode {
+    /// Each driver spawns its own mixer thread, along with a thread to dispose of
+    /// finished tracks.
+    ///
+    /// This offers the most predictable timing, but scales poorly when a bot is
+    /// connected to many calls at once.
+    Dedicated,
+    /// Drivers share the worker threads of a [`Scheduler`].
+    Pooled(Scheduler),
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for SchedulerMode {
+    fn default() -> Self {
+        SchedulerMode::Dedicated
+    }
+}
+
+/// A fixed pool of threads which mixes audio for many drivers on a shared 20ms tick.
+///
+/// On each tick, every driver using this scheduler is placed in a shared queue,
+/// from which any idle worker takes the next waiting call. A worker held up by one
+/// expensive call therefore does not delay the calls behind it, so long as the pool
+/// as a whole has spare capacity.
+///
+/// Threads are spawned when a scheduler is created, and exit once every handle
+/// to the scheduler and every driver using it has been dropped. Clones of a
+/// `Scheduler` share the same pool.
+///
+/// # Example
+/// ```rust,no_run
+/// use songbird::{
+///     driver::{Scheduler, SchedulerMode},
+///     Config,
+/// };
+///
+/// # async {
+/// let scheduler = Scheduler::new(4);
+/// let config = Config::default().scheduler(SchedulerMode::Pooled(scheduler));
+/// # };
+/// ```
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<SchedulerInner>,
+}
+
+struct SchedulerInner {
+    workers: usize,
+    mixers: Sender<Box<Mixer>>,
+    disposer: Sender<DisposalMessage>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("workers", &self.inner.workers)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Creates a new scheduler, backed by `workers` mixing threads.
+    ///
+    /// At least one worker is always spawned.
+    pub fn new(workers: usize) -> Self {
+        Self::spawn(workers).0
+    }
+
+    /// Creates a new scheduler, returning the handles of every thread it spawned.
+    fn spawn(workers: usize) -> (Self, Vec<JoinHandle<()>>) {
+        let workers = workers.max(1);
+        let mut threads = Vec::with_capacity(workers + 2);
+
+        let (mixer_tx, mixer_rx) = flume::unbounded();
+        let (work_tx, work_rx) = flume::unbounded();
+        let (done_tx, done_rx) = flume::unbounded();
+
+        let (disposer, disposal_rx) = flume::unbounded();
+        threads.push(thread::spawn(move || disposal::runner(disposal_rx)));
+
+        for i in 0..workers {
+            let work_rx = work_rx.clone();
+            let done_tx = done_tx.clone();
+
+            threads.push(thread::spawn(move || {
+                trace!("Scheduler worker {} started.", i);
+                worker(work_rx, done_tx);
+                trace!("Scheduler worker {} finished.", i);
+            }));
+        }
+
+        let final_disposer = disposer.clone();
+        threads.push(thread::spawn(move || {
+            trace!("Scheduler started.");
+            coordinator(mixer_rx, work_tx, done_rx);
+            let _ = final_disposer.send(DisposalMessage::Poison);
+            trace!("Scheduler finished.");
+        }));
+
+        let scheduler = Self {
+            inner: Arc::new(SchedulerInner {
+                workers,
+                mixers: mixer_tx,
+                disposer,
+            }),
+        };
+
+        (scheduler, threads)
+    }
+
+    /// Returns the number of worker threads in this pool.
+    pub fn workers(&self) -> usize {
+        self.inner.workers
+    }
+
+    pub(crate) fn add_mixer(
+        &self,
+        interconnect: Interconnect,
+        mix_rx: Receiver<MixerMessage>,
+        async_handle: Handle,
+        config: Config,
+    ) {
+        let mixer = mixer::pooled(
+            interconnect,
+            mix_rx,
+            async_handle,
+            config,
+            self.inner.disposer.clone(),
+        );
+
+        let _ = self.inner.mixers.send(Box::new(mixer));
+    }
+}
+
+impl Default for Scheduler {
+    /// Creates a scheduler with one worker per available CPU core.
+    fn default() -> Self {
+        Self::new(
+            thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1),
+        )
+    }
+}
+
+/// Hands every live mixer to the worker pool once per tick.
+fn coordinator(
+    mixer_rx: Receiver<Box<Mixer>>,
+    work_tx: Sender<(Box<Mixer>, Instant)>,
+    done_rx: Receiver<Option<Box<Mixer>>>,
+) {
+    let mut ready = vec![];
+    let mut in_flight = 0usize;
+    let mut handles_dropped = false;
+    let mut tick = Instant::now();
+
+    loop {
+        loop {
+            match mixer_rx.try_recv() {
+                Ok(mixer) => ready.push(mixer),
+                Err(TryRecvError::Disconnected) => {
+                    handles_dropped = true;
+                    break;
+                },
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        // Mixers still running from the last tick sit this one out:
+        // they catch up on any missed frames when next scheduled.
+        for done in done_rx.try_iter() {
+            in_flight -= 1;
+            ready.extend(done);
+        }
+
+        if handles_dropped && ready.is_empty() && in_flight == 0 {
+            break;
+        }
+
+        for mixer in ready.drain(..) {
+            in_flight += 1;
+            if work_tx.send((mixer, tick)).is_err() {
+                return;
+            }
+        }
+
+        tick += TIMESTEP_LENGTH;
+
+        let now = Instant::now();
+        if now > tick + TIMESTEP_LENGTH {
+            debug!("Scheduler fell behind by {:?}.", now - tick);
+            tick = now;
+        }
+
+        thread::sleep(tick.saturating_duration_since(now));
+    }
+}
+
+fn worker(work_rx: Receiver<(Box<Mixer>, Instant)>, done_tx: Sender<Option<Box<Mixer>>>) {
+    while let Ok((mut mixer, tick)) = work_rx.recv() {
+        let live = mixer.run_pooled(tick);
+
+        if done_tx.send(if live { Some(mixer) } else { None }).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        driver::{test_support::MockServer, Driver},
+        input::Input,
+    };
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn square_wave() -> Input {
+        let samples: Vec<u8> = (0..STEREO_FRAME_SIZE * 25)
+            .flat_map(|i| if (i / 96) % 2 == 0 { 0.5f32 } else { -0.5 }.to_le_bytes())
+            .collect();
+
+        Input::float_pcm(true, samples.into())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pooled_mixers_send_audio_and_exit_once_dropped() {
+        let (scheduler, threads) = Scheduler::spawn(2);
+        assert_eq!(threads.len(), 4);
+
+        let server = MockServer::new();
+        let config = server
+            .config()
+            .scheduler(SchedulerMode::Pooled(scheduler.clone()));
+
+        let mut drivers = vec![];
+        let mut sessions = vec![];
+        for guild in 1..=3u64 {
+            let mut driver = Driver::new(config.clone());
+            driver
+                .connect(MockServer::connection_info(guild, 2))
+                .await
+                .unwrap();
+            sessions.push(timeout(WAIT, server.next_session()).await.unwrap().unwrap());
+
+            driver.play_source(square_wave());
+            drivers.push(driver);
+        }
+
+        for session in &sessions {
+            let audio = timeout(WAIT, session.recv_audio()).await.unwrap().unwrap();
+            assert_eq!(audio.len(), STEREO_FRAME_SIZE);
+            assert!(audio.iter().any(|s| s.abs() > 0.1));
+        }
+
+        // Every handle to the pool must go before its threads can finish.
+        let (done_tx, done_rx) = flume::bounded(1);
+        thread::spawn(move || {
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            let _ = done_tx.send(());
+        });
+
+        drop(drivers);
+        drop(config);
+        assert!(done_rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        drop(scheduler);
+        timeout(WAIT, done_rx.recv_async()).await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+use crate::constants::SAMPLE_RATE_RAW;
+use discortp::{
+    rtcp::{
+        report::{ReportBlockPacket, SenderInfoPacket},
+        MutableRtcpPacket,
+    },
+    Packet,
+};
+use parking_lot::Mutex;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Seconds between the NTP epoch (1900) and the UNIX epoch (1970).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Network and performance statistics for a driver's current voice connection.
+///
+/// These are available at any time via [`Driver::connection_stats`], and are
+/// sent periodically to [`CoreEvent::ConnectionStats`] handlers.
+///
+/// Comparing network statistics (heartbeat and RTCP measurements) against
+/// [`deadline_misses`] distinguishes a poor route to the voice server from an
+/// overloaded host.
+///
+/// [`Driver::connection_stats`]: super::Driver::connection_stats
+/// [`CoreEvent::ConnectionStats`]: crate::events::CoreEvent::ConnectionStats
+/// [`deadline_misses`]: ConnectionStats::deadline_misses
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    /// Round-trip time of the most recently acknowledged voice websocket heartbeat.
+    pub heartbeat_rtt: Option<Duration>,
+    /// Fraction of this driver's packets lost between the last two RTCP reports
+    /// from the voice server, from `0.0` to `1.0`.
+    pub packet_loss: Option<f32>,
+    /// Total number of this driver's packets which the voice server reports as lost.
+    pub packets_lost: Option<u32>,
+    /// Interarrival jitter of this driver's packets, as reported by the voice server.
+    pub jitter: Option<Duration>,
+    /// Round-trip time computed from RTCP reports.
+    ///
+    /// This is only known if the voice server includes sender report timing
+    /// information in its reports.
+    pub rtcp_rtt: Option<Duration>,
+    /// Number of voice packets sent since connecting.
+    pub packets_sent: u64,
+    /// Number of bytes of voice packets sent since connecting.
+    pub bytes_sent: u64,
+    /// Number of UDP packets received since connecting.
+    pub packets_received: u64,
+    /// Number of bytes of UDP packets received since connecting.
+    pub bytes_received: u64,
+    /// Number of audio frames which the mixer failed to produce by their deadline
+    /// since connecting.
+    pub deadline_misses: u64,
+}
+
+/// Shared, thread-safe store of the statistics gathered by each driver task.
+#[derive(Debug, Default)]
+pub struct StatsRecorder {
+    packets_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_received: AtomicU64,
+    deadline_misses: AtomicU64,
+    reports: Mutex<ReportState>,
+}
+
+#[derive(Debug, Default)]
+struct ReportState {
+    ssrc: u32,
+    stats: ConnectionStats,
+}
+
+impl StatsRecorder {
+    /// Clears all statistics on connection to a new voice session as `ssrc`.
+    pub(crate) fn reset(&self, ssrc: u32) {
+        for counter in [
+            &self.packets_sent,
+            &self.bytes_sent,
+            &self.packets_received,
+            &self.bytes_received,
+            &self.deadline_misses,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+
+        *self.reports.lock() = ReportState {
+            ssrc,
+            stats: Default::default(),
+        };
+    }
+
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received(&self, bytes: usize) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_deadline_miss(&self) {
+        self.deadline_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_heartbeat_rtt(&self, rtt: Duration) {
+        self.reports.lock().stats.heartbeat_rtt = Some(rtt);
+    }
+
+    /// Extracts loss, jitter and timing from any report blocks concerning this
+    /// driver's stream in a decrypted RTCP packet.
+    ///
+    /// `start` and `tail` are the number of bytes to skip at either end of the
+    /// packet payload, as returned by decryption.
+    pub(crate) fn record_rtcp(&self, rtcp: &MutableRtcpPacket<'_>, start: usize, tail: usize) {
+        let (count, header_len, payload) = match rtcp {
+            MutableRtcpPacket::SenderReport(p) => (
+                p.get_rx_report_count(),
+                SenderInfoPacket::minimum_packet_size(),
+                p.payload(),
+            ),
+            MutableRtcpPacket::ReceiverReport(p) => (p.get_rx_report_count(), 0, p.payload()),
+            _ => return,
+        };
+
+        let body = match payload.get(start..payload.len().saturating_sub(tail)) {
+            Some(body) => body.get(header_len..).unwrap_or_default(),
+            None => return,
+        };
+
+        let mut reports = self.reports.lock();
+        let ssrc = reports.ssrc;
+
+        let blocks = body
+            .chunks_exact(ReportBlockPacket::minimum_packet_size())
+            .take(count as usize)
+            .filter_map(ReportBlockPacket::new)
+            .filter(|block| block.get_ssrc() == ssrc);
+
+        for block in blocks {
+            let stats = &mut reports.stats;
+
+            stats.packet_loss = Some(f32::from(block.get_fraction_lost()) / 256.0);
+
+            // Cumulative loss is a signed 24-bit field: duplicates can push it below zero.
+            let lost = block.get_cumulative_pkts_lost();
+            stats.packets_lost = Some(if lost & 0x80_0000 != 0 { 0 } else { lost });
+
+            stats.jitter = Some(Duration::from_secs_f64(
+                f64::from(block.get_interarrival_jitter()) / f64::from(SAMPLE_RATE_RAW as u32),
+            ));
+
+            let last_sr = block.get_last_sr_timestamp();
+            if last_sr != 0 {
+                let rtt = ntp_middle_bits()
+                    .wrapping_sub(last_sr)
+                    .wrapping_sub(block.get_last_sr_delay());
+                stats.rtcp_rtt = Some(Duration::from_secs_f64(f64::from(rtt) / 65536.0));
+            }
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStats {
+        ConnectionStats {
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            deadline_misses: self.deadline_misses.load(Ordering::Relaxed),
+            ..self.reports.lock().stats.clone()
+        }
+    }
+}
+
+/// Returns the middle 32 bits of the current NTP timestamp, as used by RTCP reports.
+fn ntp_middle_bits() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() + NTP_UNIX_OFFSET;
+    let frac = (u64::from(now.subsec_nanos()) << 32) / 1_000_000_000;
+
+    (((secs & 0xFFFF) << 16) | (frac >> 16)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receiver_report_for_own_ssrc() {
+        let recorder = StatsRecorder::default();
+        recorder.reset(0xDEAD_BEEF);
+
+        let mut packet = vec![0u8; 8 + 2 * ReportBlockPacket::minimum_packet_size()];
+        packet[..8].copy_from_slice(&[0x82, 201, 0, 13, 0, 0, 0, 1]);
+
+        // One report about another stream, followed by one about ours.
+        let ours = &mut packet[8 + ReportBlockPacket::minimum_packet_size()..];
+        ours[..4].copy_from_slice(&0xDEAD_BEEFu32.to_be_bytes());
+        ours[4] = 64;
+        ours[5..8].copy_from_slice(&[0, 0, 12]);
+        ours[12..16].copy_from_slice(&4800u32.to_be_bytes());
+
+        let rtcp = MutableRtcpPacket::new(&mut packet[..]).unwrap();
+        recorder.record_rtcp(&rtcp, 0, 0);
+        recorder.record_sent(100);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.packet_loss, Some(0.25));
+        assert_eq!(stats.packets_lost, Some(12));
+        assert_eq!(stats.jitter, Some(Duration::from_millis(100)));
+        assert_eq!(stats.rtcp_rtt, None);
+        assert_eq!((stats.packets_sent, stats.bytes_sent), (1, 100));
+    }
+}
@@ -65,6 +65,9 @@ pub(crate) async fn runner(_interconnect: Interconnect, evt_rx: Receiver<EventMe
                 let state = states
                     .get_mut(i)
                     .expect("Event thread was given an illegal state index for ChangeState.");
+                let handle = handles
+                    .get(i)
+                    .expect("Event thread was given an illegal handle index for ChangeState.");
 
                 match change {
                     Mode(mode) => {
@@ -77,6 +80,14 @@ pub(crate) async fn runner(_interconnect: Interconnect, evt_rx: Receiver<EventMe
                     Volume(vol) => {
                         state.volume = vol;
                     },
+                    FadeComplete(vol) => {
+                        state.volume = vol;
+                        global.fire_track_event(TrackEvent::FadeComplete, i);
+                    },
+                    Metadata(metadata) => {
+                        handle.set_current_metadata(*metadata);
+                        global.fire_track_event(TrackEvent::MetadataUpdate, i);
+                    },
                     Position(pos) => {
                         // Currently, only Tick should fire time events.
                         state.position = pos;
@@ -1,7 +1,12 @@
 #![allow(missing_docs)]
 
 use crate::{
-    driver::{connection::error::Error, Bitrate, Config},
+    driver::{
+        connection::{error::Error, Connection},
+        Bitrate,
+        Config,
+        Recording,
+    },
     events::{context_data::DisconnectReason, EventData},
     tracks::Track,
     ConnectionInfo,
@@ -13,6 +18,7 @@ use flume::Sender;
 pub enum CoreMessage {
     ConnectWithResult(ConnectionInfo, Sender<Result<(), Error>>),
     RetryConnect(usize),
+    MigrationResult(usize, Result<Connection, Error>),
     SignalWsClosure(usize, ConnectionInfo, Option<DisconnectReason>),
     Disconnect,
     SetTrack(Option<Track>),
@@ -20,9 +26,13 @@ pub enum CoreMessage {
     SetBitrate(Bitrate),
     AddEvent(EventData),
     RemoveGlobalEvents,
+    SetReceiveVolume(u32, f32),
+    AddVoiceMixStream(Sender<Vec<f32>>),
+    StartRecording(Recording),
+    StopRecording,
     SetConfig(Config),
     Mute(bool),
-    Reconnect,
+    Reconnect(usize),
     FullReconnect,
     RebuildInterconnect,
     Poison,
@@ -1,9 +1,10 @@
 #![allow(missing_docs)]
 
-use crate::tracks::Track;
+use crate::tracks::{effects::AudioEffect, Track};
 
 pub enum DisposalMessage {
     Track(Track),
+    Effects(Vec<Box<dyn AudioEffect>>),
 
     Poison,
 }
@@ -2,6 +2,7 @@
 
 use crate::{
     events::{CoreContext, EventData, EventStore},
+    input::Metadata,
     tracks::{LoopState, PlayMode, TrackHandle, TrackState},
 };
 use std::time::Duration;
@@ -27,6 +28,8 @@ pub enum EventMessage {
 pub enum TrackStateChange {
     Mode(PlayMode),
     Volume(f32),
+    FadeComplete(f32),
+    Metadata(Box<Metadata>),
     Position(Duration),
     // Bool indicates user-set.
     Loops(LoopState, bool),
@@ -3,15 +3,18 @@
 use super::{Interconnect, UdpRxMessage, UdpTxMessage, WsMessage};
 
 use crate::{
-    driver::{Bitrate, Config, CryptoState},
+    driver::{transport::UdpTransport, Bitrate, Cipher, Config, CryptoState},
     tracks::Track,
 };
-use crypto_secretbox::XSalsa20Poly1305 as Cipher;
 use flume::Sender;
+use std::sync::Arc;
 
 pub struct MixerConnection {
     pub cipher: Cipher,
     pub crypto_state: CryptoState,
+    /// Handle to the voice UDP socket, used to send packets directly from the mixer
+    /// without blocking.
+    pub socket: Arc<dyn UdpTransport>,
     pub udp_rx: Sender<UdpRxMessage>,
     pub udp_tx: Sender<UdpTxMessage>,
 }
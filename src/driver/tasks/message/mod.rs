@@ -4,13 +4,27 @@ mod core;
 mod disposal;
 mod events;
 mod mixer;
+mod recorder;
+mod rx_mixer;
 mod udp_rx;
 mod udp_tx;
 mod ws;
 
-pub use self::{core::*, disposal::*, events::*, mixer::*, udp_rx::*, udp_tx::*, ws::*};
+pub use self::{
+    core::*,
+    disposal::*,
+    events::*,
+    mixer::*,
+    recorder::*,
+    rx_mixer::*,
+    udp_rx::*,
+    udp_tx::*,
+    ws::*,
+};
 
+use crate::driver::StatsRecorder;
 use flume::Sender;
+use std::sync::{atomic::AtomicBool, Arc};
 use tokio::spawn;
 use tracing::trace;
 
@@ -19,6 +33,12 @@ pub struct Interconnect {
     pub core: Sender<CoreMessage>,
     pub events: Sender<EventMessage>,
     pub mixer: Sender<MixerMessage>,
+    pub rx_mixer: Sender<RxMixerMessage>,
+    pub recorder: Sender<RecorderMessage>,
+    /// Set while a recording is in progress, so that received audio
+    /// is only sent to the recorder when needed.
+    pub recording: Arc<AtomicBool>,
+    pub stats: Arc<StatsRecorder>,
 }
 
 impl Interconnect {
@@ -28,6 +48,8 @@ impl Interconnect {
 
     pub fn poison_all(&self) {
         let _ = self.mixer.send(MixerMessage::Poison);
+        let _ = self.rx_mixer.send(RxMixerMessage::Poison);
+        let _ = self.recorder.send(RecorderMessage::Poison);
         self.poison();
     }
 
@@ -49,5 +71,11 @@ impl Interconnect {
         let _ = self
             .mixer
             .send(MixerMessage::ReplaceInterconnect(self.clone()));
+        let _ = self
+            .rx_mixer
+            .send(RxMixerMessage::ReplaceInterconnect(self.clone()));
+        let _ = self
+            .recorder
+            .send(RecorderMessage::ReplaceInterconnect(self.clone()));
     }
 }
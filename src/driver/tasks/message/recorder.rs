@@ -0,0 +1,13 @@
+#![allow(missing_docs)]
+
+use super::Interconnect;
+use crate::{driver::Recording, events::context_data::VoiceTick};
+
+pub enum RecorderMessage {
+    Start(Recording),
+    Stop,
+    Tick(VoiceTick),
+    ReplaceInterconnect(Interconnect),
+
+    Poison,
+}
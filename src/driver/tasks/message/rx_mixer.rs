@@ -0,0 +1,14 @@
+#![allow(missing_docs)]
+
+use super::Interconnect;
+use crate::events::context_data::VoiceTick;
+use flume::Sender;
+
+pub enum RxMixerMessage {
+    Tick(VoiceTick),
+    SetVolume(u32, f32),
+    AddStream(Sender<Vec<f32>>),
+    ReplaceInterconnect(Interconnect),
+
+    Poison,
+}
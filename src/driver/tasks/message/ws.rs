@@ -1,11 +1,13 @@
 #![allow(missing_docs)]
 
 use super::Interconnect;
-use crate::ws::WsStream;
+use crate::{model::Event as GatewayEvent, ws::WsStream};
 
 #[allow(dead_code)]
 pub enum WsMessage {
     Ws(Box<WsStream>),
+    /// An event replayed by the voice server while resuming a session.
+    Replayed(Box<GatewayEvent>),
     ReplaceInterconnect(Interconnect),
     SetKeepalive(f64),
     Speaking(bool),
@@ -1,14 +1,19 @@
-use super::{disposal, error::Result, message::*};
-use crate::driver::crypto::TAG_SIZE;
+use super::{
+    disposal,
+    error::{Error, Recipient, Result},
+    message::*,
+    overload::OverloadMonitor,
+};
 use crate::{
     constants::*,
-    tracks::{PlayMode, Track},
+    driver::{CatchUpPolicy, EncoderConfig, FrameLength},
+    events::CoreContext,
+    tracks::{Declick, Fade, LoopState, PlayMode, Track},
     Config,
 };
 use audiopus::{
-    coder::Encoder as OpusEncoder,
+    coder::{Encoder as OpusEncoder, GenericCtl},
     softclip::SoftClip,
-    Application as CodingMode,
     Bitrate,
     Channels,
 };
@@ -18,7 +23,7 @@ use discortp::{
 };
 use flume::{Receiver, Sender, TryRecvError};
 use rand::random;
-use std::{convert::TryInto, time::Instant};
+use std::{convert::TryInto, io::ErrorKind, time::Instant};
 use tokio::runtime::Handle;
 use tracing::{debug, error, instrument};
 
@@ -40,13 +45,35 @@ pub struct Mixer {
     pub soft_clip: SoftClip,
     pub tracks: Vec<Track>,
     pub ws: Option<Sender<WsMessage>>,
+    // Settings `encoder` was actually built with.
+    encoder_config: EncoderConfig,
+    // Set when the last packet sent was not produced by `encoder` (i.e., on passthrough),
+    // whose state must then be reset before it is used again.
+    encoder_stale: bool,
+    // Mixed audio (at the encoder's channel count) not yet sent, when packets
+    // hold more than one 20ms block.
+    pending: Vec<f32>,
+    overload: OverloadMonitor,
+    pooled: bool,
 }
 
-fn new_encoder(bitrate: Bitrate) -> Result<OpusEncoder> {
-    let mut encoder = OpusEncoder::new(SAMPLE_RATE, Channels::Stereo, CodingMode::Audio)?;
-    encoder.set_bitrate(bitrate)?;
+/// Builds an encoder from the given settings, falling back to known-good values on failure.
+fn new_encoder(bitrate: Bitrate, config: EncoderConfig) -> (Bitrate, EncoderConfig, OpusEncoder) {
+    match config.build(bitrate) {
+        Ok(encoder) => (bitrate, config, encoder),
+        Err(e) => {
+            error!(
+                "Failed to build encoder. Resetting bitrate and encoder settings. {:?}",
+                e
+            );
+            let config = EncoderConfig::default();
+            let encoder = config
+                .build(DEFAULT_BITRATE)
+                .expect("Failed fallback rebuild of OpusEncoder with safe inputs.");
 
-    Ok(encoder)
+            (DEFAULT_BITRATE, config, encoder)
+        },
+    }
 }
 
 impl Mixer {
@@ -56,9 +83,21 @@ impl Mixer {
         interconnect: Interconnect,
         config: Config,
     ) -> Self {
-        let bitrate = DEFAULT_BITRATE;
-        let encoder = new_encoder(bitrate)
-            .expect("Failed to create encoder in mixing thread with known-good values.");
+        // Create an object disposal thread here.
+        let (disposer, disposal_rx) = flume::unbounded();
+        std::thread::spawn(move || disposal::runner(disposal_rx));
+
+        Self::with_disposer(mix_rx, async_handle, interconnect, config, disposer)
+    }
+
+    pub(crate) fn with_disposer(
+        mix_rx: Receiver<MixerMessage>,
+        async_handle: Handle,
+        interconnect: Interconnect,
+        config: Config,
+        disposer: Sender<DisposalMessage>,
+    ) -> Self {
+        let (bitrate, encoder_config, encoder) = new_encoder(DEFAULT_BITRATE, config.encoder);
         let soft_clip = SoftClip::new(Channels::Stereo);
 
         let mut packet = [0u8; VOICE_PACKET_MAX];
@@ -74,10 +113,6 @@ impl Mixer {
 
         let tracks = Vec::with_capacity(1.max(config.preallocated_tracks));
 
-        // Create an object disposal thread here.
-        let (disposer, disposal_rx) = flume::unbounded();
-        std::thread::spawn(move || disposal::runner(disposal_rx));
-
         Self {
             async_handle,
             bitrate,
@@ -96,93 +131,132 @@ impl Mixer {
             soft_clip,
             tracks,
             ws: None,
+            encoder_config,
+            encoder_stale: false,
+            pending: Vec::with_capacity(3 * STEREO_FRAME_SIZE),
+            overload: OverloadMonitor::new(),
+            pooled: false,
         }
     }
 
     fn run(&mut self) {
+        loop {
+            let (events_failure, conn_failure, should_exit) = if self.conn_active.is_some() {
+                self.drain_messages_and_cycle()
+            } else {
+                match self.mix_rx.recv() {
+                    Ok(m) => self.handle_message(m),
+                    Err(_) => (false, false, true),
+                }
+            };
+
+            if should_exit || !self.recover(events_failure, conn_failure) {
+                break;
+            }
+        }
+    }
+
+    /// Runs the mixer for one tick of a shared [`Scheduler`], returning whether
+    /// it should be scheduled again.
+    ///
+    /// Unlike a dedicated mixer, this never blocks: any frames due before the
+    /// following tick are mixed and sent immediately.
+    ///
+    /// [`Scheduler`]: crate::driver::Scheduler
+    pub(crate) fn run_pooled(&mut self, tick: Instant) -> bool {
+        let horizon = tick + TIMESTEP_LENGTH;
+
+        loop {
+            let last_deadline = self.deadline;
+            let (events_failure, conn_failure, should_exit) = self.drain_messages_and_cycle();
+
+            if should_exit || !self.recover(events_failure, conn_failure) {
+                return false;
+            }
+
+            // Each successful cycle advances the deadline, so this catches up on any
+            // frames missed while the pool was too busy to run this mixer.
+            if self.conn_active.is_none()
+                || self.deadline == last_deadline
+                || self.deadline > horizon
+            {
+                return true;
+            }
+        }
+    }
+
+    /// Handles all waiting messages, then mixes and sends one frame if connected.
+    fn drain_messages_and_cycle(&mut self) -> (bool, bool, bool) {
         let mut events_failure = false;
         let mut conn_failure = false;
 
-        'runner: loop {
-            if self.conn_active.is_some() {
-                loop {
-                    match self.mix_rx.try_recv() {
-                        Ok(m) => {
-                            let (events, conn, should_exit) = self.handle_message(m);
-                            events_failure |= events;
-                            conn_failure |= conn;
-
-                            if should_exit {
-                                break 'runner;
-                            }
-                        },
-
-                        Err(TryRecvError::Disconnected) => {
-                            break 'runner;
-                        },
-
-                        Err(TryRecvError::Empty) => {
-                            break;
-                        },
-                    };
-                }
-
-                // The above action may have invalidated the connection; need to re-check!
-                if self.conn_active.is_some() {
-                    if let Err(e) = self.cycle().and_then(|_| self.audio_commands_events()) {
-                        events_failure |= e.should_trigger_interconnect_rebuild();
-                        conn_failure |= e.should_trigger_connect();
+        loop {
+            match self.mix_rx.try_recv() {
+                Ok(m) => {
+                    let (events, conn, should_exit) = self.handle_message(m);
+                    events_failure |= events;
+                    conn_failure |= conn;
 
-                        debug!("Mixer thread cycle: {:?}", e);
+                    if should_exit {
+                        return (events_failure, conn_failure, true);
                     }
-                }
-            } else {
-                match self.mix_rx.recv() {
-                    Ok(m) => {
-                        let (events, conn, should_exit) = self.handle_message(m);
-                        events_failure |= events;
-                        conn_failure |= conn;
-
-                        if should_exit {
-                            break 'runner;
-                        }
-                    },
-                    Err(_) => {
-                        break 'runner;
-                    },
-                }
-            }
+                },
 
-            // event failure? rebuild interconnect.
-            // ws or udp failure? full connect
-            // (soft reconnect is covered by the ws task.)
-            //
-            // in both cases, send failure is fatal,
-            // but will only occur on disconnect.
-            // expecting this is fairly noisy, so exit silently.
-            if events_failure {
-                self.prevent_events = true;
-                let sent = self
-                    .interconnect
-                    .core
-                    .send(CoreMessage::RebuildInterconnect);
-                events_failure = false;
-
-                if sent.is_err() {
+                Err(TryRecvError::Disconnected) => {
+                    return (events_failure, conn_failure, true);
+                },
+
+                Err(TryRecvError::Empty) => {
                     break;
-                }
+                },
+            };
+        }
+
+        // The above action may have invalidated the connection; need to re-check!
+        if self.conn_active.is_some() {
+            if let Err(e) = self.cycle().and_then(|_| self.audio_commands_events()) {
+                events_failure |= e.should_trigger_interconnect_rebuild();
+                conn_failure |= e.should_trigger_connect();
+
+                debug!("Mixer thread cycle: {:?}", e);
             }
+        }
 
-            if conn_failure {
-                self.conn_active = None;
-                let sent = self.interconnect.core.send(CoreMessage::FullReconnect);
-                conn_failure = false;
+        (events_failure, conn_failure, false)
+    }
 
-                if sent.is_err() {
-                    break;
-                }
+    /// Asks the driver to repair any failed tasks, returning `false` if the
+    /// driver has gone away.
+    fn recover(&mut self, events_failure: bool, conn_failure: bool) -> bool {
+        // event failure? rebuild interconnect.
+        // ws or udp failure? full connect
+        // (soft reconnect is covered by the ws task.)
+        //
+        // in both cases, send failure is fatal,
+        // but will only occur on disconnect.
+        // expecting this is fairly noisy, so exit silently.
+        if events_failure {
+            self.prevent_events = true;
+            let sent = self
+                .interconnect
+                .core
+                .send(CoreMessage::RebuildInterconnect);
+
+            if sent.is_err() {
+                return false;
             }
         }
+
+        if conn_failure {
+            self.conn_active = None;
+            let sent = self.interconnect.core.send(CoreMessage::FullReconnect);
+
+            if sent.is_err() {
+                return false;
+            }
+        }
+
+        true
     }
 
     #[inline]
@@ -227,15 +301,19 @@ impl Mixer {
                 Ok(())
             },
             SetConn(conn, ssrc) => {
-                self.conn_active = Some(conn);
+                // Swapping out a live connection (i.e., moving to another voice server)
+                // keeps the RTP clock and frame deadline running, so playback is seamless.
+                let migrating = self.conn_active.replace(conn).is_some();
                 let mut rtp = MutableRtpPacket::new(&mut self.packet[..]).expect(
                     "Too few bytes in self.packet for RTP header.\
                         (Blame: VOICE_PACKET_MAX?)",
                 );
                 rtp.set_ssrc(ssrc);
-                rtp.set_sequence(random::<u16>().into());
-                rtp.set_timestamp(random::<u32>().into());
-                self.deadline = Instant::now();
+                if !migrating {
+                    rtp.set_sequence(random::<u16>().into());
+                    rtp.set_timestamp(random::<u32>().into());
+                    self.deadline = Instant::now();
+                }
                 Ok(())
             },
             DropConn => {
@@ -274,18 +352,19 @@ impl Mixer {
 
                 Ok(())
             },
-            RebuildEncoder => match new_encoder(self.bitrate) {
-                Ok(encoder) => {
-                    self.encoder = encoder;
-                    Ok(())
-                },
-                Err(e) => {
-                    error!("Failed to rebuild encoder. Resetting bitrate. {:?}", e);
-                    self.bitrate = DEFAULT_BITRATE;
-                    self.encoder = new_encoder(self.bitrate)
-                        .expect("Failed fallback rebuild of OpusEncoder with safe inputs.");
-                    Ok(())
-                },
+            RebuildEncoder => {
+                let (bitrate, encoder_config, encoder) =
+                    new_encoder(self.bitrate, self.config.encoder);
+
+                self.bitrate = bitrate;
+                self.encoder_config = encoder_config;
+                self.encoder = encoder;
+                self.encoder_stale = false;
+
+                // Buffered audio may not match the new channel count or frame length.
+                self.pending.clear();
+
+                Ok(())
             },
             Ws(new_ws_handle) => {
                 self.ws = new_ws_handle;
@@ -356,7 +435,7 @@ impl Mixer {
             // but if the event thread has died then we'll certainly
             // detect that on the tick later.
             // Changes to play state etc. MUST all be handled.
-            track.process_commands(i, &self.interconnect);
+            track.process_commands(i, &self.interconnect, &self.disposer);
         }
 
         // TODO: do without vec?
@@ -368,7 +447,8 @@ impl Mixer {
                 .get_mut(i)
                 .expect("Tried to remove an illegal track index.");
 
-            if track.playing.is_done() {
+            // Stopped tracks are kept for one more frame while they ramp out.
+            if track.playing.is_done() && track.declick != Some(Declick::Out) {
                 let p_state = track.playing();
                 let to_drop = self.tracks.swap_remove(i);
                 to_remove.push(i);
@@ -399,13 +479,70 @@ impl Mixer {
             return;
         }
 
-        std::thread::sleep(self.deadline.saturating_duration_since(Instant::now()));
+        let now = Instant::now();
+        self.overload.pause_work(now);
+
+        if now > self.deadline {
+            let lateness = now - self.deadline;
+
+            let dropped = match self.config.catch_up {
+                CatchUpPolicy::Burst => 0,
+                CatchUpPolicy::Drop => {
+                    // Skip every frame whose slot has fully elapsed, so that both
+                    // the deadline and RTP timestamp match the wall clock.
+                    let missed = (lateness.as_nanos() / TIMESTEP_LENGTH.as_nanos()) as u32;
+
+                    self.deadline += TIMESTEP_LENGTH * missed;
+
+                    let mut rtp = MutableRtpPacket::new(&mut self.packet[..]).expect(
+                        "FATAL: Too few bytes in self.packet for RTP header.\
+                            (Blame: VOICE_PACKET_MAX?)",
+                    );
+                    rtp.set_timestamp(
+                        rtp.get_timestamp() + missed.wrapping_mul(MONO_FRAME_SIZE as u32),
+                    );
+
+                    missed
+                },
+            };
+
+            self.interconnect.stats.record_deadline_miss();
+            self.overload.record_late(lateness, dropped);
+        }
+
+        // Pooled mixers are woken by their scheduler, and send each frame as soon as it is ready.
+        if !self.pooled {
+            std::thread::sleep(self.deadline.saturating_duration_since(now));
+        }
         self.deadline += TIMESTEP_LENGTH;
+
+        self.overload.start_work(Instant::now());
     }
 
     pub fn cycle(&mut self) -> Result<()> {
+        self.overload.start_work(Instant::now());
+
+        let out = self.mix_and_send();
+
+        if let Some(report) = self.overload.end_cycle(Instant::now()) {
+            self.fire_event(EventMessage::FireCoreEvent(CoreContext::MixerOverload(
+                report,
+            )))?;
+        }
+
+        out
+    }
+
+    fn mix_and_send(&mut self) -> Result<()> {
         let mut mix_buffer = [0f32; STEREO_FRAME_SIZE];
 
+        let crypto_mode = self
+            .conn_active
+            .as_ref()
+            .map(|conn| conn.crypto_state.kind())
+            .unwrap_or(self.config.crypto_mode);
+        let payload_start = crypto_mode.payload_prefix_len();
+
         // Walk over all the audio files, combining into one audio frame according
         // to volume, play state, etc.
         let mut mix_len = {
@@ -415,14 +552,15 @@ impl Mixer {
             );
 
             let payload = rtp.payload_mut();
+            let payload_end = payload.len() - crypto_mode.payload_suffix_len();
 
-            // self.mix_tracks(&mut payload[TAG_SIZE..], &mut mix_buffer)
             mix_tracks(
-                &mut payload[TAG_SIZE..],
+                &mut payload[payload_start..payload_end],
                 &mut mix_buffer,
                 &mut self.tracks,
                 &self.interconnect,
                 self.prevent_events,
+                self.encoder_config.frame_length == FrameLength::Ms20,
             )
         };
 
@@ -432,6 +570,12 @@ impl Mixer {
             mix_len = MixType::MixedPcm(0);
         }
 
+        if mix_len == MixType::MixedPcm(0) && !self.pending.is_empty() {
+            // Complete a partly-filled packet with silence, rather than dropping its audio.
+            mix_buffer = [0f32; STEREO_FRAME_SIZE];
+            mix_len = MixType::MixedPcm(MONO_FRAME_SIZE);
+        }
+
         if mix_len == MixType::MixedPcm(0) {
             if self.silence_frames > 0 {
                 self.silence_frames -= 1;
@@ -444,7 +588,7 @@ impl Mixer {
 
                 let payload = rtp.payload_mut();
 
-                (&mut payload[TAG_SIZE..TAG_SIZE + SILENT_FRAME.len()])
+                (&mut payload[payload_start..payload_start + SILENT_FRAME.len()])
                     .copy_from_slice(&SILENT_FRAME[..]);
 
                 mix_len = MixType::Passthrough(SILENT_FRAME.len());
@@ -482,6 +626,64 @@ impl Mixer {
 
     #[inline]
     fn prep_and_send_packet(&mut self, buffer: [f32; 1920], mix_len: MixType) -> Result<()> {
+        match mix_len {
+            MixType::Passthrough(opus_len) => {
+                self.encoder_stale = true;
+                self.send_packet(opus_len, MONO_FRAME_SIZE)
+            },
+            MixType::MixedPcm(_samples) => {
+                if self.encoder_stale {
+                    // Prevents the encoder from predicting this frame from audio
+                    // it last saw before passthrough began, which would click.
+                    self.encoder.reset_state()?;
+                    self.encoder_stale = false;
+                }
+
+                let stereo = self.encoder_config.is_stereo();
+                if stereo {
+                    self.pending.extend_from_slice(&buffer[..]);
+                } else {
+                    self.pending
+                        .extend(buffer.chunks_exact(2).map(|lr| (lr[0] + lr[1]) / 2.0));
+                }
+
+                let frame_samples = self.encoder_config.frame_length.samples();
+                let frame_len = frame_samples * if stereo { 2 } else { 1 };
+
+                while self.pending.len() >= frame_len {
+                    let payload_len = {
+                        let conn = self.conn_active.as_ref().expect(
+                            "Shouldn't be mixing packets without access to a cipher + UDP dest.",
+                        );
+                        let crypto_mode = conn.crypto_state.kind();
+
+                        let mut rtp = MutableRtpPacket::new(&mut self.packet[..]).expect(
+                            "FATAL: Too few bytes in self.packet for RTP header.\
+                                (Blame: VOICE_PACKET_MAX?)",
+                        );
+                        let payload = rtp.payload_mut();
+                        let payload_start = crypto_mode.payload_prefix_len();
+                        let total_payload_space = payload.len() - crypto_mode.payload_suffix_len();
+
+                        self.encoder.encode_float(
+                            &self.pending[..frame_len],
+                            &mut payload[payload_start..total_payload_space],
+                        )?
+                    };
+
+                    self.pending.drain(..frame_len);
+                    self.send_packet(payload_len, frame_samples)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    /// Encrypts and sends the Opus packet of length `payload_len` held in `self.packet`,
+    /// which holds `samples` samples per channel of audio.
+    #[inline]
+    fn send_packet(&mut self, payload_len: usize, samples: usize) -> Result<()> {
         let conn = self
             .conn_active
             .as_mut()
@@ -493,23 +695,11 @@ impl Mixer {
                     (Blame: VOICE_PACKET_MAX?)",
             );
 
-            let payload = rtp.payload_mut();
-            let crypto_mode = conn.crypto_state.kind();
-
-            let payload_len = match mix_len {
-                MixType::Passthrough(opus_len) => opus_len,
-                MixType::MixedPcm(_samples) => {
-                    let total_payload_space = payload.len() - crypto_mode.payload_suffix_len();
-                    self.encoder.encode_float(
-                        &buffer[..STEREO_FRAME_SIZE],
-                        &mut payload[TAG_SIZE..total_payload_space],
-                    )?
-                },
-            };
+            let payload_start = conn.crypto_state.kind().payload_prefix_len();
 
             let final_payload_size = conn
                 .crypto_state
-                .write_packet_nonce(&mut rtp, TAG_SIZE + payload_len);
+                .write_packet_nonce(&mut rtp, payload_start + payload_len);
 
             conn.crypto_state.kind().encrypt_in_place(
                 &mut rtp,
@@ -520,18 +710,25 @@ impl Mixer {
             RtpPacket::minimum_packet_size() + final_payload_size
         };
 
-        // TODO: This is dog slow, don't do this.
-        // Can we replace this with a shared ring buffer + semaphore?
-        // i.e., do something like double/triple buffering in graphics.
-        conn.udp_tx
-            .send(UdpTxMessage::Packet(self.packet[..index].to_vec()))?;
+        // Sends never block: if the OS send buffer is full, this packet is
+        // dropped as it would be anywhere else on the network.
+        match conn.socket.try_send(&self.packet[..index]) {
+            Ok(_) => self.interconnect.stats.record_sent(index),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                debug!("UDP send buffer full: dropped voice packet.");
+            },
+            Err(e) => {
+                error!("Fatal UDP packet send error: {:?}.", e);
+                return Err(Error::InterconnectFailure(Recipient::UdpTx));
+            },
+        }
 
         let mut rtp = MutableRtpPacket::new(&mut self.packet[..]).expect(
             "FATAL: Too few bytes in self.packet for RTP header.\
                 (Blame: VOICE_PACKET_MAX?)",
         );
         rtp.set_sequence(rtp.get_sequence() + 1);
-        rtp.set_timestamp(rtp.get_timestamp() + MONO_FRAME_SIZE as u32);
+        rtp.set_timestamp(rtp.get_timestamp() + samples as u32);
 
         Ok(())
     }
@@ -550,63 +747,239 @@ fn mix_tracks<'a>(
     tracks: &mut Vec<Track>,
     interconnect: &Interconnect,
     prevent_events: bool,
+    allow_passthrough: bool,
 ) -> MixType {
     let mut len = 0;
 
+    for i in 0..tracks.len() {
+        start_if_unblocked(tracks, i, interconnect, prevent_events, true);
+    }
+
     // Opus frame passthrough.
-    // This requires that we have only one track, who has volume 1.0, and an
-    // Opus codec type.
-    let do_passthrough = tracks.len() == 1 && {
-        let track = &tracks[0];
-        (track.volume - 1.0).abs() < f32::EPSILON && track.source.supports_passthrough()
-    };
+    // Frames can't be altered without decoding them, so this requires 20ms packets,
+    // and a playing track with volume 1.0 and an Opus codec type. Any other playing
+    // tracks are mixed first: if all of these are silent, then its frame is sent as-is.
+    let passthrough = tracks.iter().position(|track| {
+        allow_passthrough
+            && track.playing == PlayMode::Play
+            && (track.volume - 1.0).abs() < f32::EPSILON
+            && track.effects.is_empty()
+            && track.fade.is_none()
+            && track.declick.is_none()
+            && track.source.supports_passthrough()
+    });
 
     for (i, track) in tracks.iter_mut().enumerate() {
-        let vol = track.volume;
-        let stream = &mut track.source;
-
-        if track.playing != PlayMode::Play {
+        if Some(i) == passthrough
+            || (track.playing != PlayMode::Play && track.declick != Some(Declick::Out))
+        {
             continue;
         }
 
-        let (temp_len, opus_len) = if do_passthrough {
-            (0, track.source.read_opus_frame(opus_frame).ok())
+        len = len.max(mix_track(
+            track,
+            i,
+            mix_buffer,
+            interconnect,
+            prevent_events,
+        ));
+    }
+
+    if let Some(i) = passthrough {
+        let track = &mut tracks[i];
+
+        if mix_buffer.iter().all(|sample| *sample == 0.0) {
+            let opus_len = track.source.read_opus_frame(opus_frame).ok();
+            finish_frame(track, i, opus_len.is_some(), interconnect, prevent_events);
+
+            if let Some(opus_len) = opus_len {
+                return MixType::Passthrough(opus_len);
+            }
         } else {
-            (stream.mix(mix_buffer, vol), None)
-        };
+            len = len.max(mix_track(
+                track,
+                i,
+                mix_buffer,
+                interconnect,
+                prevent_events,
+            ));
+        }
+    }
+
+    // Gapless successors may sit before the track they follow: any whose predecessor
+    // ended during this frame are started now, rather than on the next. Crossfades
+    // wait for the next frame, as their predecessor has already been mixed.
+    for i in 0..tracks.len() {
+        if start_if_unblocked(tracks, i, interconnect, prevent_events, false) {
+            len = len.max(mix_track(
+                &mut tracks[i],
+                i,
+                mix_buffer,
+                interconnect,
+                prevent_events,
+            ));
+        }
+    }
+
+    MixType::MixedPcm(len)
+}
+
+/// Mixes one frame of a playing track into `mix_buffer`, returning the number of samples read.
+#[inline]
+fn mix_track(
+    track: &mut Track,
+    i: usize,
+    mix_buffer: &mut [f32; STEREO_FRAME_SIZE],
+    interconnect: &Interconnect,
+    prevent_events: bool,
+) -> usize {
+    let vol = track.volume;
+    let stream = &mut track.source;
+
+    let temp_len = if track.effects.is_empty() && track.fade.is_none() && track.declick.is_none() {
+        stream.mix(mix_buffer, vol)
+    } else {
+        // Effects, fades and ramps must see this track's audio in isolation.
+        let mut track_buffer = [0f32; STEREO_FRAME_SIZE];
+
+        let len = if let Some(fade) = &mut track.fade {
+            let len = stream.mix(&mut track_buffer, 1.0);
+
+            if fade.apply(&mut track_buffer) {
+                let fade = track.fade.take().expect("Fade was checked above.");
+                track.volume = fade.to;
 
-        len = len.max(temp_len);
-        if temp_len > 0 || opus_len.is_some() {
-            track.step_frame();
-        } else if track.do_loop() {
-            if let Ok(time) = track.seek_time(Default::default()) {
-                // have to reproduce self.fire_event here
-                // to circumvent the borrow checker's lack of knowledge.
-                //
-                // In event of error, one of the later event calls will
-                // trigger the event thread rebuild: it is more prudent that
-                // the mixer works as normal right now.
                 if !prevent_events {
                     let _ = interconnect.events.send(EventMessage::ChangeState(
                         i,
-                        TrackStateChange::Position(time),
-                    ));
-                    let _ = interconnect.events.send(EventMessage::ChangeState(
-                        i,
-                        TrackStateChange::Loops(track.loops, false),
+                        TrackStateChange::FadeComplete(track.volume),
                     ));
                 }
+
+                if fade.stop {
+                    track.stop();
+                }
             }
+
+            len
         } else {
-            track.end();
+            stream.mix(&mut track_buffer, vol)
+        };
+
+        for effect in track.effects.iter_mut() {
+            effect.process(&mut track_buffer);
         }
 
-        if let Some(opus_len) = opus_len {
-            return MixType::Passthrough(opus_len);
+        if let Some(declick) = track.declick.take() {
+            declick.apply(&mut track_buffer);
         }
+
+        for (out, sample) in mix_buffer.iter_mut().zip(&track_buffer[..]) {
+            *out += sample;
+        }
+
+        len
+    };
+
+    finish_frame(track, i, temp_len > 0, interconnect, prevent_events);
+
+    temp_len
+}
+
+/// Advances a track after it has been mixed, handling looping and ending.
+#[inline]
+fn finish_frame(
+    track: &mut Track,
+    i: usize,
+    had_audio: bool,
+    interconnect: &Interconnect,
+    prevent_events: bool,
+) {
+    if had_audio {
+        track.step_frame();
+    } else if track.playing != PlayMode::Play {
+        // Tracks ramping out keep whichever state their user chose.
+    } else if track.do_loop() {
+        if let Ok(time) = track.seek_time(Default::default()) {
+            // have to reproduce self.fire_event here
+            // to circumvent the borrow checker's lack of knowledge.
+            //
+            // In event of error, one of the later event calls will
+            // trigger the event thread rebuild: it is more prudent that
+            // the mixer works as normal right now.
+            if !prevent_events {
+                let _ = interconnect.events.send(EventMessage::ChangeState(
+                    i,
+                    TrackStateChange::Position(time),
+                ));
+                let _ = interconnect.events.send(EventMessage::ChangeState(
+                    i,
+                    TrackStateChange::Loops(track.loops, false),
+                ));
+            }
+        }
+    } else {
+        track.end();
     }
+}
 
-    MixType::MixedPcm(len)
+/// Plays a paused track if the track it is waiting on (for gapless playback)
+/// has finished, or has reached the point where both should crossfade, returning
+/// whether it was started.
+#[inline]
+fn start_if_unblocked(
+    tracks: &mut [Track],
+    i: usize,
+    interconnect: &Interconnect,
+    prevent_events: bool,
+    allow_crossfade: bool,
+) -> bool {
+    let waiting_on = match tracks[i].start_after {
+        Some(uuid) if tracks[i].playing == PlayMode::Pause => uuid,
+        _ => return false,
+    };
+
+    let crossfade = tracks[i].crossfade;
+
+    if let Some(j) = tracks
+        .iter()
+        .position(|t| t.uuid == waiting_on && !t.playing.is_done())
+    {
+        let (duration, curve) = match crossfade {
+            Some(crossfade) if allow_crossfade => crossfade,
+            _ => return false,
+        };
+
+        // The overlap is measured from the track's position rather than its play time,
+        // so that seeks and pauses move the crossfade with them.
+        let prev = &tracks[j];
+        let fading = match prev.source.metadata.duration {
+            Some(length) if length > duration =>
+                prev.playing == PlayMode::Play
+                    && prev.loops == LoopState::Finite(0)
+                    && prev.position + duration >= length,
+            _ => false,
+        };
+
+        if !fading {
+            return false;
+        }
+
+        tracks[j].fade(Fade::fade_out(duration, curve));
+        tracks[i].fade(Fade::fade_in(duration, curve));
+    }
+
+    let track = &mut tracks[i];
+    track.play();
+
+    if !prevent_events {
+        let _ = interconnect.events.send(EventMessage::ChangeState(
+            i,
+            TrackStateChange::Mode(track.playing),
+        ));
+    }
+
+    true
 }
 
 /// The mixing thread is a synchronous context due to its compute-bound nature.
@@ -626,3 +999,211 @@ pub(crate) fn runner(
 
     let _ = mixer.disposer.send(DisposalMessage::Poison);
 }
+
+/// Creates a mixer to be driven by a shared [`Scheduler`], rather than its own thread.
+///
+/// [`Scheduler`]: crate::driver::Scheduler
+pub(crate) fn pooled(
+    interconnect: Interconnect,
+    mix_rx: Receiver<MixerMessage>,
+    async_handle: Handle,
+    config: Config,
+    disposer: Sender<DisposalMessage>,
+) -> Mixer {
+    let mut mixer = Mixer::with_disposer(mix_rx, async_handle, interconnect, config, disposer);
+    mixer.pooled = true;
+
+    mixer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Input,
+        tracks::{self, FadeCurve, QueueTransition, TrackQueue},
+    };
+    use std::time::Duration;
+
+    const CROSSFADE: QueueTransition = QueueTransition::Crossfade {
+        duration: Duration::from_millis(60),
+        curve: FadeCurve::Linear,
+    };
+
+    fn interconnect() -> Interconnect {
+        let (core, _) = flume::unbounded();
+        let (events, _) = flume::unbounded();
+        let (mixer, _) = flume::unbounded();
+        let (rx_mixer, _) = flume::unbounded();
+        let (recorder, _) = flume::unbounded();
+
+        Interconnect {
+            core,
+            events,
+            mixer,
+            rx_mixer,
+            recorder,
+            recording: Default::default(),
+            stats: Default::default(),
+        }
+    }
+
+    /// A track of `frames` 20ms frames, holding a constant level in each channel.
+    fn track(frames: usize, left: f32, right: f32) -> Track {
+        let samples: Vec<u8> = (0..frames * MONO_FRAME_SIZE)
+            .flat_map(|_| [left, right])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+
+        let mut input = Input::float_pcm(true, samples.into());
+        input.metadata.duration = Some(TIMESTEP_LENGTH * frames as u32);
+
+        tracks::create_player(input).0
+    }
+
+    fn queued(transition: QueueTransition, mut tracks: Vec<Track>) -> (TrackQueue, Vec<Track>) {
+        let queue = TrackQueue::new();
+        queue.set_transition(transition);
+
+        for track in tracks.iter_mut() {
+            queue.add_raw(track);
+        }
+
+        (queue, tracks)
+    }
+
+    /// Mixes `frames` frames, returning the loudest sample of each channel in each frame.
+    fn run(tracks: &mut Vec<Track>, frames: usize) -> Vec<(f32, f32)> {
+        let interconnect = interconnect();
+        let (disposer, _disposal_rx) = flume::unbounded();
+        let mut opus_frame = [0u8; VOICE_PACKET_MAX];
+
+        (0..frames)
+            .map(|_| {
+                for (i, track) in tracks.iter_mut().enumerate() {
+                    track.process_commands(i, &interconnect, &disposer);
+                }
+
+                let mut mix_buffer = [0f32; STEREO_FRAME_SIZE];
+                mix_tracks(
+                    &mut opus_frame,
+                    &mut mix_buffer,
+                    tracks,
+                    &interconnect,
+                    false,
+                    false,
+                );
+
+                let loudest = |channel: usize| {
+                    mix_buffer
+                        .iter()
+                        .skip(channel)
+                        .step_by(2)
+                        .fold(0f32, |acc, s| acc.max(s.abs()))
+                };
+
+                (loudest(0), loudest(1))
+            })
+            .collect()
+    }
+
+    /// Index of the first frame in which `channel` holds audio.
+    fn first_audible(frames: &[(f32, f32)], channel: usize) -> Option<usize> {
+        frames
+            .iter()
+            .position(|f| if channel == 0 { f.0 } else { f.1 } > 0.0)
+    }
+
+    #[test]
+    fn gapless_successor_starts_in_frame_predecessor_ends() {
+        for reversed in [false, true] {
+            let (_queue, mut tracks) = queued(
+                QueueTransition::Gapless,
+                vec![track(3, 0.5, 0.0), track(3, 0.0, 0.5)],
+            );
+
+            // Successors may sit before the track they follow.
+            if reversed {
+                tracks.reverse();
+            }
+
+            let frames = run(&mut tracks, 7);
+
+            assert_eq!(
+                frames,
+                vec![
+                    (0.5, 0.0),
+                    (0.5, 0.0),
+                    (0.5, 0.0),
+                    (0.0, 0.5),
+                    (0.0, 0.5),
+                    (0.0, 0.5),
+                    (0.0, 0.0)
+                ],
+                "reversed: {}",
+                reversed
+            );
+        }
+    }
+
+    #[test]
+    fn crossfaded_tracks_overlap_for_fade_duration() {
+        let (_queue, mut tracks) =
+            queued(CROSSFADE, vec![track(10, 0.5, 0.0), track(10, 0.0, 0.5)]);
+
+        let frames = run(&mut tracks, 14);
+        let overlap = frames.iter().filter(|f| f.0 > 0.0 && f.1 > 0.0).count();
+
+        assert_eq!(first_audible(&frames, 1), Some(7));
+        assert_eq!(overlap, 3);
+        assert!(frames[10..].iter().all(|f| f.0 == 0.0 && f.1 == 0.5));
+    }
+
+    #[test]
+    fn crossfade_follows_seeks() {
+        let (_queue, mut tracks) =
+            queued(CROSSFADE, vec![track(10, 0.5, 0.0), track(10, 0.0, 0.5)]);
+
+        let before = run(&mut tracks, 5);
+        tracks[0].seek_time(Duration::ZERO).unwrap();
+        let after = run(&mut tracks, 10);
+
+        assert_eq!(first_audible(&before, 1), None);
+        assert_eq!(first_audible(&after, 1), Some(7));
+
+        let (_queue, mut tracks) =
+            queued(CROSSFADE, vec![track(10, 0.5, 0.0), track(10, 0.0, 0.5)]);
+
+        tracks[0].seek_time(Duration::from_millis(160)).unwrap();
+        let frames = run(&mut tracks, 4);
+
+        assert_eq!(first_audible(&frames, 1), Some(0));
+        assert_eq!(first_audible(&frames[2..], 0), None);
+    }
+
+    #[test]
+    fn modified_queue_is_relinked() {
+        let (queue, mut tracks) = queued(
+            QueueTransition::Gapless,
+            vec![track(2, 0.5, 0.0), track(2, 0.25, 0.0), track(2, 0.75, 0.0)],
+        );
+
+        queue.modify_queue(|q| q.swap(1, 2));
+        let frames = run(&mut tracks, 6);
+
+        let left: Vec<_> = frames.iter().map(|f| f.0).collect();
+        assert_eq!(left, vec![0.5, 0.5, 0.75, 0.75, 0.25, 0.25]);
+
+        let (queue, mut tracks) = queued(
+            QueueTransition::Gapless,
+            vec![track(2, 0.5, 0.0), track(2, 0.25, 0.0)],
+        );
+
+        queue.set_transition(QueueTransition::Sequential);
+        let frames = run(&mut tracks, 4);
+
+        // Sequential successors are only started once the queue handles an end event.
+        let left: Vec<_> = frames.iter().map(|f| f.0).collect();
+        assert_eq!(left, vec![0.5, 0.5, 0.0, 0.0]);
+    }
+}
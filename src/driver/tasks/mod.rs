@@ -5,17 +5,26 @@ pub mod error;
 mod events;
 pub mod message;
 pub mod mixer;
+mod overload;
+mod recorder;
+mod rx_mixer;
 pub(crate) mod udp_rx;
 pub(crate) mod udp_tx;
 pub(crate) mod ws;
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use super::connection::{error::Error as ConnectionError, Connection};
+use super::{
+    connection::{error::Error as ConnectionError, Connection},
+    DecodeMode,
+    RecordingError,
+    SchedulerMode,
+    StatsRecorder,
+};
 use crate::{
     events::{
         context_data::{DisconnectKind, DisconnectReason},
-        internal_data::{InternalConnect, InternalDisconnect},
+        internal_data::{InternalConnect, InternalDisconnect, InternalMigrate},
         CoreContext,
     },
     Config,
@@ -26,22 +35,41 @@ use message::*;
 use tokio::{runtime::Handle, spawn, time::sleep as tsleep};
 use tracing::{debug, instrument, trace};
 
-pub(crate) fn start(config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMessage>) {
+pub(crate) fn start(
+    config: Config,
+    rx: Receiver<CoreMessage>,
+    tx: Sender<CoreMessage>,
+    stats: Arc<StatsRecorder>,
+) {
     spawn(async move {
         trace!("Driver started.");
-        runner(config, rx, tx).await;
+        runner(config, rx, tx, stats).await;
         trace!("Driver finished.");
     });
 }
 
-fn start_internals(core: Sender<CoreMessage>, config: Config) -> Interconnect {
+/// Starts all per-driver tasks, other than the recorder.
+///
+/// The recorder needs its own thread, so is only started once a recording is
+/// requested (using the returned receiver).
+fn start_internals(
+    core: Sender<CoreMessage>,
+    config: Config,
+    stats: Arc<StatsRecorder>,
+) -> (Interconnect, Receiver<RecorderMessage>) {
     let (evt_tx, evt_rx) = flume::unbounded();
     let (mix_tx, mix_rx) = flume::unbounded();
+    let (rx_mix_tx, rx_mix_rx) = flume::unbounded();
+    let (recorder_tx, recorder_rx) = flume::unbounded();
 
     let interconnect = Interconnect {
         core,
         events: evt_tx,
         mixer: mix_tx,
+        rx_mixer: rx_mix_tx,
+        recorder: recorder_tx,
+        recording: Default::default(),
+        stats,
     };
 
     let ic = interconnect.clone();
@@ -51,22 +79,52 @@ fn start_internals(core: Sender<CoreMessage>, config: Config) -> Interconnect {
         trace!("Event processor finished.");
     });
 
+    let ic = interconnect.clone();
+    spawn(async move {
+        trace!("Receive mixer started.");
+        rx_mixer::runner(ic, rx_mix_rx).await;
+        trace!("Receive mixer finished.");
+    });
+
     let ic = interconnect.clone();
     let handle = Handle::current();
+    match &config.scheduler {
+        SchedulerMode::Dedicated => {
+            std::thread::spawn(move || {
+                trace!("Mixer started.");
+                mixer::runner(ic, mix_rx, handle, config);
+                trace!("Mixer finished.");
+            });
+        },
+        SchedulerMode::Pooled(scheduler) => {
+            trace!("Mixer added to scheduler.");
+            scheduler.add_mixer(ic, mix_rx, handle, config.clone());
+        },
+    }
+
+    (interconnect, recorder_rx)
+}
+
+fn start_recorder(interconnect: &Interconnect, rx: Receiver<RecorderMessage>) {
+    let ic = interconnect.clone();
     std::thread::spawn(move || {
-        trace!("Mixer started.");
-        mixer::runner(ic, mix_rx, handle, config);
-        trace!("Mixer finished.");
+        trace!("Recorder started.");
+        recorder::runner(ic, rx);
+        trace!("Recorder finished.");
     });
-
-    interconnect
 }
 
-#[instrument(skip(rx, tx))]
-async fn runner(mut config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMessage>) {
+#[instrument(skip(rx, tx, stats))]
+async fn runner(
+    mut config: Config,
+    rx: Receiver<CoreMessage>,
+    tx: Sender<CoreMessage>,
+    stats: Arc<StatsRecorder>,
+) {
     let mut next_config: Option<Config> = None;
     let mut connection: Option<Connection> = None;
-    let mut interconnect = start_internals(tx, config.clone());
+    let (mut interconnect, recorder_rx) = start_internals(tx, config.clone(), stats);
+    let mut recorder_rx = Some(recorder_rx);
     let mut retrying = None;
     let mut attempt_idx = 0;
 
@@ -82,32 +140,80 @@ async fn runner(mut config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMe
                     config
                 };
 
-                if connection
-                    .as_ref()
-                    .map(|conn| conn.info != info)
-                    .unwrap_or(true)
-                {
-                    // Only *actually* reconnect if the conn info changed, or we don't have an
-                    // active connection.
-                    // This allows the gateway component to keep sending join requests independent
-                    // of driver failures.
-                    connection = ConnectionRetryData::connect(tx, info, &mut attempt_idx)
-                        .attempt(&mut retrying, &interconnect, &config)
-                        .await;
-                } else {
-                    // No reconnection was attempted as there's a valid, identical connection;
-                    // tell the outside listener that the operation was a success.
-                    let _ = tx.send(Ok(()));
+                match &connection {
+                    Some(conn) if conn.info == info => {
+                        // No reconnection was attempted as there's a valid, identical connection;
+                        // tell the outside listener that the operation was a success.
+                        let _ = tx.send(Ok(()));
+                    },
+                    Some(conn) if is_migration(&conn.info, &info) => {
+                        // The old connection keeps sending audio while the new one is set up,
+                        // and is only shut down once replaced or once every retry has failed.
+                        let from = InternalConnect {
+                            info: conn.info.clone(),
+                            ssrc: conn.ssrc,
+                        };
+
+                        ConnectionRetryData::migrate(tx, from, info, &mut attempt_idx)
+                            .spawn_attempt(&mut retrying, &interconnect, &config);
+                    },
+                    _ => {
+                        // Only *actually* reconnect if the conn info changed, or we don't have an
+                        // active connection.
+                        // This allows the gateway component to keep sending join requests independent
+                        // of driver failures.
+                        connection = ConnectionRetryData::connect(tx, info, &mut attempt_idx)
+                            .attempt(&mut retrying, &interconnect, &config)
+                            .await;
+                    },
                 }
             },
             Ok(CoreMessage::RetryConnect(retry_idx)) => {
                 debug!("Retrying idx: {} (vs. {})", retry_idx, attempt_idx);
                 if retry_idx == attempt_idx {
                     if let Some(progress) = retrying.take() {
-                        connection = progress
-                            .attempt(&mut retrying, &interconnect, &config)
-                            .await;
+                        if let ConnectionFlavour::Migrate(..) = progress.flavour {
+                            progress.spawn_attempt(&mut retrying, &interconnect, &config);
+                        } else {
+                            connection = progress
+                                .attempt(&mut retrying, &interconnect, &config)
+                                .await;
+                        }
+                    }
+                }
+            },
+            Ok(CoreMessage::MigrationResult(migrate_idx, result)) => {
+                // Connections from superseded attempts are dropped, closing their tasks.
+                if migrate_idx != attempt_idx {
+                    continue;
+                }
+
+                let progress = match retrying.take() {
+                    // The driver may have left the call while migrating.
+                    Some(progress) if connection.is_some() => progress,
+                    _ => continue,
+                };
+
+                let result = match result {
+                    Ok(mut new_conn) => new_conn.attach(&interconnect).map(|_| new_conn),
+                    Err(e) => Err(e),
+                };
+
+                let last_conn = connection.take();
+                connection = progress.complete(result, &mut retrying, &interconnect, &config);
+
+                if connection.is_none() && retrying.is_some() {
+                    // Keep serving the old connection until a retry resolves the migration.
+                    connection = last_conn;
+                } else if let Some(last_conn) = last_conn {
+                    // A failed migration leaves nothing for the old connection to hand
+                    // over to: it must be torn down as though its websocket had closed.
+                    if connection.is_none() {
+                        let _ = interconnect.mixer.send(MixerMessage::DropConn);
+                        let _ = interconnect.mixer.send(MixerMessage::RebuildEncoder);
                     }
+
+                    let _ = last_conn.ws.send(WsMessage::Poison);
                 }
             },
             Ok(CoreMessage::Disconnect) => {
@@ -156,11 +262,17 @@ async fn runner(mut config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMe
                 let _ = interconnect.mixer.send(MixerMessage::SetBitrate(b));
             },
             Ok(CoreMessage::SetConfig(mut new_config)) => {
+                let encoder_changed =
+                    next_config.as_ref().unwrap_or(&config).encoder != new_config.encoder;
                 next_config = Some(new_config.clone());
 
                 new_config.make_safe(&config, connection.is_some());
 
                 let _ = interconnect.mixer.send(MixerMessage::SetConfig(new_config));
+
+                if encoder_changed {
+                    let _ = interconnect.mixer.send(MixerMessage::RebuildEncoder);
+                }
             },
             Ok(CoreMessage::AddEvent(evt)) => {
                 let _ = interconnect.events.send(EventMessage::AddGlobalEvent(evt));
@@ -168,45 +280,66 @@ async fn runner(mut config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMe
             Ok(CoreMessage::RemoveGlobalEvents) => {
                 let _ = interconnect.events.send(EventMessage::RemoveGlobalEvents);
             },
+            Ok(CoreMessage::SetReceiveVolume(ssrc, volume)) => {
+                let _ = interconnect
+                    .rx_mixer
+                    .send(RxMixerMessage::SetVolume(ssrc, volume));
+            },
+            Ok(CoreMessage::AddVoiceMixStream(tx)) => {
+                let _ = interconnect.rx_mixer.send(RxMixerMessage::AddStream(tx));
+            },
+            Ok(CoreMessage::StartRecording(recording)) => {
+                let err = if !config.decode_mode.should_decrypt() {
+                    Some(RecordingError::DecryptRequired)
+                } else if recording.requires_decode() && config.decode_mode != DecodeMode::Decode {
+                    Some(RecordingError::DecodeRequired)
+                } else {
+                    None
+                };
+
+                if let Some(err) = err {
+                    let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                        CoreContext::RecordingError(err),
+                    ));
+                } else {
+                    if let Some(rx) = recorder_rx.take() {
+                        start_recorder(&interconnect, rx);
+                    }
+
+                    let _ = interconnect
+                        .recorder
+                        .send(RecorderMessage::Start(recording));
+                }
+            },
+            Ok(CoreMessage::StopRecording) => {
+                let _ = interconnect.recorder.send(RecorderMessage::Stop);
+            },
             Ok(CoreMessage::Mute(m)) => {
                 let _ = interconnect.mixer.send(MixerMessage::SetMute(m));
             },
-            Ok(CoreMessage::Reconnect) => {
+            Ok(CoreMessage::Reconnect(ws_idx)) => {
+                if ws_idx != attempt_idx {
+                    // Stale request from the websocket of a replaced connection.
+                    continue;
+                }
+
                 if let Some(mut conn) = connection.take() {
-                    // try once: if interconnect, try again.
-                    // if still issue, full connect.
+                    // UDP and the mixer are left running while we attempt to resume
+                    // the websocket session: only if this fails do we fully reconnect.
                     let info = conn.info.clone();
 
-                    let full_connect = match conn.reconnect(&config).await {
-                        Ok(()) => {
-                            connection = Some(conn);
-                            false
-                        },
-                        Err(ConnectionError::InterconnectFailure(_)) => {
-                            interconnect.restart_volatile_internals();
-
-                            match conn.reconnect(&config).await {
-                                Ok(()) => {
-                                    connection = Some(conn);
-                                    false
-                                },
-                                _ => true,
-                            }
-                        },
-                        _ => true,
-                    };
-
-                    if full_connect {
-                        connection = ConnectionRetryData::reconnect(info, &mut attempt_idx)
-                            .attempt(&mut retrying, &interconnect, &config)
-                            .await;
-                    } else if let Some(ref connection) = &connection {
+                    if resume(&mut conn, &mut interconnect, &config).await {
                         let _ = interconnect.events.send(EventMessage::FireCoreEvent(
-                            CoreContext::DriverReconnect(InternalConnect {
-                                info: connection.info.clone(),
-                                ssrc: connection.ssrc,
+                            CoreContext::DriverResume(InternalConnect {
+                                info: conn.info.clone(),
+                                ssrc: conn.ssrc,
                             }),
                         ));
+                        connection = Some(conn);
+                    } else {
+                        connection = ConnectionRetryData::reconnect(info, &mut attempt_idx)
+                            .attempt(&mut retrying, &interconnect, &config)
+                            .await;
                     }
                 }
             },
@@ -231,6 +364,51 @@ async fn runner(mut config: Config, rx: Receiver<CoreMessage>, tx: Sender<CoreMe
     interconnect.poison_all();
 }
 
+/// Returns whether `new` only moves the session described by `old` onto another
+/// voice server, as happens when Discord sends a fresh voice server update.
+fn is_migration(old: &ConnectionInfo, new: &ConnectionInfo) -> bool {
+    old.guild_id == new.guild_id
+        && old.channel_id == new.channel_id
+        && old.session_id == new.session_id
+        && old.user_id == new.user_id
+        && (old.endpoint != new.endpoint || old.token != new.token)
+}
+
+/// Number of times to try resuming a voice websocket session before falling back
+/// to a full reconnect.
+const RESUME_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry of a failed resume, doubled on each subsequent retry.
+const RESUME_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Attempts to resume the websocket session of `conn`, returning whether this succeeded.
+async fn resume(conn: &mut Connection, interconnect: &mut Interconnect, config: &Config) -> bool {
+    let mut backoff = RESUME_BACKOFF;
+
+    for attempt in 1..=RESUME_ATTEMPTS {
+        match conn.reconnect(config).await {
+            Ok(()) => return true,
+            Err(ConnectionError::InterconnectFailure(_)) => {
+                interconnect.restart_volatile_internals();
+            },
+            Err(ConnectionError::Ws(e)) if !ws::ws_error_is_not_final(&e) => {
+                debug!("Voice session cannot be resumed: {:?}", e);
+                return false;
+            },
+            Err(e) => {
+                debug!("Resume attempt {} failed: {}", attempt, e);
+            },
+        }
+
+        if attempt < RESUME_ATTEMPTS {
+            tsleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    false
+}
+
 struct ConnectionRetryData {
     flavour: ConnectionFlavour,
     attempts: usize,
@@ -252,6 +430,15 @@ impl ConnectionRetryData {
         Self::base(ConnectionFlavour::Reconnect, info, idx_src)
     }
 
+    fn migrate(
+        tx: Sender<Result<(), ConnectionError>>,
+        from: InternalConnect,
+        info: ConnectionInfo,
+        idx_src: &mut usize,
+    ) -> Self {
+        Self::base(ConnectionFlavour::Migrate(tx, from), info, idx_src)
+    }
+
     fn base(flavour: ConnectionFlavour, info: ConnectionInfo, idx_src: &mut usize) -> Self {
         *idx_src = idx_src.wrapping_add(1);
 
@@ -265,12 +452,50 @@ impl ConnectionRetryData {
     }
 
     async fn attempt(
+        self,
+        attempt_slot: &mut Option<Self>,
+        interconnect: &Interconnect,
+        config: &Config,
+    ) -> Option<Connection> {
+        let result = Connection::new(self.info.clone(), interconnect, config, self.idx).await;
+
+        self.complete(result, attempt_slot, interconnect, config)
+    }
+
+    /// Runs the connection handshake on another task, so that the runner can keep
+    /// serving the current connection.
+    ///
+    /// The result is returned as a [`CoreMessage::MigrationResult`], to be passed to
+    /// [`Self::complete`] once this has been taken back out of `attempt_slot`.
+    fn spawn_attempt(
+        self,
+        attempt_slot: &mut Option<Self>,
+        interconnect: &Interconnect,
+        config: &Config,
+    ) {
+        let info = self.info.clone();
+        let idx = self.idx;
+        let remote_ic = interconnect.clone();
+        let config = config.clone();
+
+        spawn(async move {
+            let result = Connection::handshake(info, &remote_ic, &config, idx).await;
+            let _ = remote_ic
+                .core
+                .send(CoreMessage::MigrationResult(idx, result));
+        });
+
+        *attempt_slot = Some(self);
+    }
+
+    fn complete(
         mut self,
+        result: Result<Connection, ConnectionError>,
         attempt_slot: &mut Option<Self>,
         interconnect: &Interconnect,
         config: &Config,
     ) -> Option<Connection> {
-        match Connection::new(self.info.clone(), interconnect, config, self.idx).await {
+        match result {
             Ok(connection) => {
                 match self.flavour {
                     ConnectionFlavour::Connect(tx) => {
@@ -292,6 +517,19 @@ impl ConnectionRetryData {
                             }),
                         ));
                     },
+                    ConnectionFlavour::Migrate(tx, from) => {
+                        let _ = tx.send(Ok(()));
+
+                        let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                            CoreContext::DriverMigrate(InternalMigrate {
+                                from,
+                                to: InternalConnect {
+                                    info: connection.info.clone(),
+                                    ssrc: connection.ssrc,
+                                },
+                            }),
+                        ));
+                    },
                 }
 
                 Some(connection)
@@ -344,6 +582,17 @@ impl ConnectionRetryData {
                                 }),
                             ));
                         },
+                        ConnectionFlavour::Migrate(tx, _) => {
+                            let _ = tx.send(Err(why));
+
+                            let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                                CoreContext::DriverDisconnect(InternalDisconnect {
+                                    kind: DisconnectKind::Migrate,
+                                    reason,
+                                    info: self.info,
+                                }),
+                            ));
+                        },
                     }
                 }
 
@@ -356,4 +605,5 @@ impl ConnectionRetryData {
 enum ConnectionFlavour {
     Connect(Sender<Result<(), ConnectionError>>),
     Reconnect,
+    Migrate(Sender<Result<(), ConnectionError>>, InternalConnect),
 }
@@ -0,0 +1,103 @@
+use crate::{constants::*, events::context_data::MixerOverload};
+use std::time::{Duration, Instant};
+
+/// Number of mixer cycles covered by each overload report (one second).
+const REPORT_WINDOW: u32 = AUDIO_FRAME_RATE as u32;
+
+/// Measures the work done by each mixer cycle, batching any deadline misses
+/// into periodic reports.
+#[derive(Debug)]
+pub(crate) struct OverloadMonitor {
+    work_start: Instant,
+    cycle_work: Duration,
+    report: MixerOverload,
+    total_work: Duration,
+}
+
+impl OverloadMonitor {
+    pub(crate) fn new() -> Self {
+        Self {
+            work_start: Instant::now(),
+            cycle_work: Duration::default(),
+            report: Default::default(),
+            total_work: Duration::default(),
+        }
+    }
+
+    /// Marks the start of mixing/encoding work.
+    pub(crate) fn start_work(&mut self, now: Instant) {
+        self.work_start = now;
+    }
+
+    /// Marks that the mixer has finished its work, and is about to wait for a deadline.
+    pub(crate) fn pause_work(&mut self, now: Instant) {
+        self.cycle_work += now.saturating_duration_since(self.work_start);
+        self.work_start = now;
+    }
+
+    pub(crate) fn record_late(&mut self, lateness: Duration, dropped_frames: u32) {
+        self.report.late_frames += 1;
+        self.report.dropped_frames += dropped_frames;
+        self.report.max_lateness = self.report.max_lateness.max(lateness);
+    }
+
+    /// Completes the current cycle, returning a report at the end of each window
+    /// in which any frame was late.
+    pub(crate) fn end_cycle(&mut self, now: Instant) -> Option<MixerOverload> {
+        self.pause_work(now);
+
+        let work = std::mem::take(&mut self.cycle_work);
+        self.total_work += work;
+        self.report.max_cycle_time = self.report.max_cycle_time.max(work);
+        self.report.frames += 1;
+
+        if self.report.frames < REPORT_WINDOW {
+            return None;
+        }
+
+        let mut report = std::mem::take(&mut self.report);
+        report.mean_cycle_time = std::mem::take(&mut self.total_work) / report.frames;
+
+        if report.late_frames > 0 {
+            Some(report)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_only_late_windows() {
+        let mut monitor = OverloadMonitor::new();
+        let start = Instant::now();
+
+        for i in 0..REPORT_WINDOW {
+            let cycle = start + TIMESTEP_LENGTH * i;
+            monitor.start_work(cycle);
+            assert!(monitor
+                .end_cycle(cycle + Duration::from_millis(2))
+                .is_none());
+        }
+
+        for i in 0..REPORT_WINDOW {
+            let cycle = start + TIMESTEP_LENGTH * (REPORT_WINDOW + i);
+            monitor.start_work(cycle);
+            if i == 3 {
+                monitor.record_late(Duration::from_millis(5), 0);
+            }
+            let report = monitor.end_cycle(cycle + Duration::from_millis(4));
+            assert_eq!(report.is_some(), i == REPORT_WINDOW - 1);
+
+            if let Some(report) = report {
+                assert_eq!(report.frames, REPORT_WINDOW);
+                assert_eq!(report.late_frames, 1);
+                assert_eq!(report.max_lateness, Duration::from_millis(5));
+                assert_eq!(report.mean_cycle_time, Duration::from_millis(4));
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+mod ogg;
+mod wav;
+
+use self::ogg::{OggChain, OggStream, OggWriter};
+use super::{message::*, udp_rx::rtp_opus_data};
+use crate::{
+    driver::{Recording, RecordingError},
+    events::{
+        context_data::{VoiceFrame, VoiceTick},
+        CoreContext,
+    },
+};
+use flume::Receiver;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    io::{BufWriter, Result as IoResult, Write},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+use tracing::{debug, instrument, trace, warn};
+use wav::WavWriter;
+
+/// A recording in progress, and the writers for each of its files.
+enum Session {
+    OggPerUser(PathBuf, HashMap<u32, (OggWriter, OggStream)>),
+    OggMultitrack(OggChain),
+    WavPerUser(PathBuf, HashMap<u32, WavWriter>),
+    WavMixdown(WavWriter, Receiver<Vec<f32>>),
+}
+
+impl Session {
+    fn new(recording: Recording, interconnect: &Interconnect) -> IoResult<Self> {
+        Ok(match recording {
+            Recording::OggPerUser(dir) => {
+                fs::create_dir_all(&dir)?;
+                Session::OggPerUser(dir, HashMap::new())
+            },
+            Recording::OggMultitrack(path) =>
+                Session::OggMultitrack(OggChain::new(create_ogg(&path)?)),
+            Recording::WavPerUser(dir) => {
+                fs::create_dir_all(&dir)?;
+                Session::WavPerUser(dir, HashMap::new())
+            },
+            Recording::WavMixdown(path) => {
+                let writer = WavWriter::create(&path)?;
+                let (tx, rx) = flume::unbounded();
+                let _ = interconnect.rx_mixer.send(RxMixerMessage::AddStream(tx));
+
+                Session::WavMixdown(writer, rx)
+            },
+        })
+    }
+
+    /// Writes one 20ms tick of received audio, which occurred `ticks`
+    /// frames after the recording began.
+    fn write_tick(&mut self, tick: VoiceTick, ticks: u64) -> Result<(), RecordingError> {
+        match self {
+            Session::OggPerUser(dir, streams) =>
+                for (ssrc, frame) in tick.speaking {
+                    if let Some((ts, opus)) = frame_opus(&frame) {
+                        let (writer, stream) = match streams.entry(ssrc) {
+                            Entry::Occupied(e) => e.into_mut(),
+                            Entry::Vacant(e) => {
+                                let path = dir.join(format!("{}.opus", ssrc));
+                                let mut writer = create_ogg(&path)?;
+                                let stream = OggStream::new(&mut writer, ssrc, ssrc, ts, ticks)?;
+
+                                e.insert((writer, stream))
+                            },
+                        };
+
+                        stream.write_packet(writer, ts, opus)?;
+                    }
+                },
+            Session::OggMultitrack(chain) => {
+                let packets: Vec<_> = tick
+                    .speaking
+                    .iter()
+                    .filter_map(|(ssrc, frame)| {
+                        frame_opus(frame).map(|(ts, opus)| (*ssrc, ts, opus))
+                    })
+                    .collect();
+
+                chain.write_tick(&packets, ticks)?;
+            },
+            Session::WavPerUser(dir, writers) => {
+                for (ssrc, frame) in &tick.speaking {
+                    let voice = frame
+                        .decoded_voice
+                        .as_ref()
+                        .ok_or(RecordingError::DecodeRequired)?;
+
+                    let writer = match writers.entry(*ssrc) {
+                        Entry::Occupied(e) => e.into_mut(),
+                        Entry::Vacant(e) => {
+                            let path = dir.join(format!("{}.wav", ssrc));
+                            let mut writer = WavWriter::create(&path)?;
+                            writer.write_silence(ticks)?;
+
+                            e.insert(writer)
+                        },
+                    };
+
+                    writer.write_samples(voice)?;
+                }
+
+                // Keep all other users' files aligned.
+                for (_, writer) in writers
+                    .iter_mut()
+                    .filter(|(ssrc, _)| !tick.speaking.contains_key(ssrc))
+                {
+                    writer.write_silence(1)?;
+                }
+            },
+            Session::WavMixdown(writer, rx) => {
+                if tick
+                    .speaking
+                    .values()
+                    .any(|frame| frame.decoded_voice.is_none())
+                {
+                    return Err(RecordingError::DecodeRequired);
+                }
+
+                for mix in rx.try_iter() {
+                    writer.write_float_samples(&mix)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> IoResult<()> {
+        match self {
+            Session::OggPerUser(_, streams) =>
+                for (_, (mut writer, stream)) in streams {
+                    stream.finish(&mut writer)?;
+                    writer.inner_mut().flush()?;
+                },
+            Session::OggMultitrack(chain) => chain.finish()?,
+            Session::WavPerUser(_, writers) =>
+                for (_, writer) in writers {
+                    writer.finish()?;
+                },
+            Session::WavMixdown(mut writer, rx) => {
+                for mix in rx.try_iter() {
+                    writer.write_float_samples(&mix)?;
+                }
+                writer.finish()?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+fn create_ogg(path: &Path) -> IoResult<OggWriter> {
+    Ok(OggWriter::new(BufWriter::new(File::create(path)?)))
+}
+
+/// Returns the RTP timestamp and Opus payload held in a frame, if a packet was received.
+fn frame_opus(frame: &VoiceFrame) -> Option<(u32, &[u8])> {
+    let packet = frame.packet.as_ref()?;
+
+    match rtp_opus_data(packet, frame.payload_offset, frame.payload_end_pad) {
+        Ok(opus) => Some((packet.timestamp.into(), opus)),
+        Err(e) => {
+            warn!("Could not record packet from SSRC {}: {:?}", packet.ssrc, e);
+            None
+        },
+    }
+}
+
+struct Recorder {
+    interconnect: Interconnect,
+    session: Option<Session>,
+    ticks: u64,
+}
+
+impl Recorder {
+    fn start(&mut self, recording: Recording) {
+        self.stop();
+
+        debug!("Starting recording: {:?}.", recording);
+
+        match Session::new(recording, &self.interconnect) {
+            Ok(session) => {
+                self.session = Some(session);
+                self.ticks = 0;
+                self.interconnect.recording.store(true, Ordering::Relaxed);
+            },
+            Err(e) => self.fire_error(e.into()),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(session) = self.session.take() {
+            debug!("Stopping recording.");
+            self.interconnect.recording.store(false, Ordering::Relaxed);
+
+            if let Err(e) = session.finish() {
+                self.fire_error(e.into());
+            }
+        }
+    }
+
+    fn tick(&mut self, tick: VoiceTick) {
+        if let Some(session) = self.session.as_mut() {
+            if let Err(e) = session.write_tick(tick, self.ticks) {
+                self.stop();
+                self.fire_error(e);
+            } else {
+                self.ticks += 1;
+            }
+        }
+    }
+
+    fn fire_error(&self, e: RecordingError) {
+        warn!("Recording failed: {}.", e);
+
+        let _ = self
+            .interconnect
+            .events
+            .send(EventMessage::FireCoreEvent(CoreContext::RecordingError(e)));
+    }
+}
+
+/// The recorder performs blocking file I/O, and so runs on its own thread.
+#[instrument(skip(interconnect, rx))]
+pub(crate) fn runner(interconnect: Interconnect, rx: Receiver<RecorderMessage>) {
+    let mut recorder = Recorder {
+        interconnect,
+        session: None,
+        ticks: 0,
+    };
+
+    loop {
+        use RecorderMessage::*;
+        match rx.recv() {
+            Ok(Start(recording)) => recorder.start(recording),
+            Ok(Stop) => recorder.stop(),
+            Ok(Tick(tick)) => recorder.tick(tick),
+            Ok(ReplaceInterconnect(i)) => recorder.interconnect = i,
+            Ok(Poison) | Err(_) => break,
+        }
+    }
+
+    recorder.stop();
+    trace!("Recorder exited.");
+}
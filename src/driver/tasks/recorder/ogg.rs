@@ -0,0 +1,338 @@
+use crate::constants::*;
+use audiopus::packet::{self as opus_packet, Packet as OpusPacket};
+use byteorder::{LittleEndian, WriteBytesExt};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs::File,
+    io::{BufWriter, Result as IoResult, Write},
+};
+
+pub type OggWriter = PacketWriter<'static, BufWriter<File>>;
+
+/// Number of audio packets written before a page is forcibly ended.
+///
+/// This bounds how far apart each stream's pages lie when several
+/// streams are interleaved into one file.
+const PACKETS_PER_PAGE: usize = AUDIO_FRAME_RATE;
+
+/// State of one user's logical Opus stream within an Ogg file.
+#[derive(Debug)]
+pub struct OggStream {
+    serial: u32,
+    /// RTP timestamp of the next expected sample.
+    next_ts: u32,
+    /// Number of samples written to this stream.
+    granule: u64,
+    packets_in_page: usize,
+}
+
+impl OggStream {
+    /// Writes the headers for a new stream of `ssrc`'s audio, whose first packet has
+    /// RTP timestamp `ts` and arrived `ticks` 20ms frames after the stream began.
+    pub fn new(
+        writer: &mut OggWriter,
+        serial: u32,
+        ssrc: u32,
+        ts: u32,
+        ticks: u64,
+    ) -> IoResult<Self> {
+        writer.write_packet(opus_head(), serial, PacketWriteEndInfo::EndPage, 0)?;
+        writer.write_packet(opus_tags(ssrc), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        let lead_in = (ticks as u32).wrapping_mul(MONO_FRAME_SIZE as u32);
+
+        Ok(Self {
+            serial,
+            next_ts: ts.wrapping_sub(lead_in),
+            granule: 0,
+            packets_in_page: 0,
+        })
+    }
+
+    /// Appends a packet with RTP timestamp `ts` to this stream, filling any
+    /// gap since the previous packet with silence.
+    pub fn write_packet(&mut self, writer: &mut OggWriter, ts: u32, opus: &[u8]) -> IoResult<()> {
+        let gap = ts.wrapping_sub(self.next_ts) as i32;
+
+        if gap < 0 {
+            // Duplicate, or already covered by silence.
+            return Ok(());
+        }
+
+        for _ in 0..(gap as usize / MONO_FRAME_SIZE) {
+            self.push(writer, &SILENT_FRAME[..], MONO_FRAME_SIZE)?;
+        }
+
+        let samples = OpusPacket::try_from(opus)
+            .and_then(|pkt| opus_packet::nb_samples(pkt, SAMPLE_RATE))
+            .unwrap_or(MONO_FRAME_SIZE);
+
+        self.next_ts = ts;
+        self.push(writer, opus, samples)
+    }
+
+    /// Ends this stream's final page.
+    pub fn finish(self, writer: &mut OggWriter) -> IoResult<()> {
+        let end = self.granule + MONO_FRAME_SIZE as u64;
+        self.finish_at(writer, end)
+    }
+
+    /// Ends this stream, padding it with silence so that it holds `end` samples.
+    ///
+    /// The final frame of silence is trimmed as needed (RFC 7845, section 4.5).
+    pub fn finish_at(mut self, writer: &mut OggWriter, end: u64) -> IoResult<()> {
+        while self.granule + (MONO_FRAME_SIZE as u64) < end {
+            self.push(writer, &SILENT_FRAME[..], MONO_FRAME_SIZE)?;
+        }
+
+        writer.write_packet(
+            SILENT_FRAME.to_vec(),
+            self.serial,
+            PacketWriteEndInfo::EndStream,
+            end.max(self.granule),
+        )
+    }
+
+    /// Returns the RTP timestamp of the sample at position `granule` in this stream.
+    fn ts_at(&self, granule: u64) -> u32 {
+        self.next_ts
+            .wrapping_sub(self.granule as u32)
+            .wrapping_add(granule as u32)
+    }
+
+    fn push(&mut self, writer: &mut OggWriter, data: &[u8], samples: usize) -> IoResult<()> {
+        self.next_ts = self.next_ts.wrapping_add(samples as u32);
+        self.granule += samples as u64;
+        self.packets_in_page += 1;
+
+        let end_info = if self.packets_in_page >= PACKETS_PER_PAGE {
+            self.packets_in_page = 0;
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+
+        writer.write_packet(data.to_vec(), self.serial, end_info, self.granule)
+    }
+}
+
+/// A single Ogg file holding one Opus stream per user.
+///
+/// Every stream in an Ogg file must begin before any audio is written (RFC 3533, section 4).
+/// Whenever a new user is heard, each open stream is ended and a new link of the chain begins,
+/// holding a new stream for every user. All streams within a link share the same start and
+/// end time, keeping users aligned with one another.
+pub struct OggChain {
+    writer: OggWriter,
+    streams: HashMap<u32, OggStream>,
+    next_serial: u32,
+    /// Tick at which the current link began.
+    link_start: u64,
+    /// Number of ticks written to this file.
+    ticks: u64,
+}
+
+impl OggChain {
+    pub fn new(writer: OggWriter) -> Self {
+        Self {
+            writer,
+            streams: HashMap::new(),
+            next_serial: 0,
+            link_start: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Writes the packets received from each user (as SSRC, RTP timestamp, and Opus data)
+    /// during one 20ms tick, which occurred `ticks` frames after the recording began.
+    pub fn write_tick(&mut self, packets: &[(u32, u32, &[u8])], ticks: u64) -> IoResult<()> {
+        if packets
+            .iter()
+            .any(|(ssrc, _, _)| !self.streams.contains_key(ssrc))
+        {
+            self.start_link(packets, ticks)?;
+        }
+
+        for (ssrc, ts, opus) in packets {
+            if let Some(stream) = self.streams.get_mut(ssrc) {
+                stream.write_packet(&mut self.writer, *ts, opus)?;
+            }
+        }
+
+        self.ticks = ticks + 1;
+
+        Ok(())
+    }
+
+    /// Ends the current link (if any), and begins a new link with streams for
+    /// all current users and any new users in `packets`.
+    fn start_link(&mut self, packets: &[(u32, u32, &[u8])], ticks: u64) -> IoResult<()> {
+        // Existing users continue from the RTP timestamp at which their last link ended.
+        let mut starts = vec![];
+
+        if !self.streams.is_empty() {
+            let end = (ticks - self.link_start) * MONO_FRAME_SIZE as u64;
+
+            for (ssrc, stream) in self.streams.drain() {
+                starts.push((ssrc, stream.ts_at(end), 0));
+                stream.finish_at(&mut self.writer, end)?;
+            }
+
+            self.link_start = ticks;
+        }
+
+        for (ssrc, ts, _) in packets {
+            if !starts.iter().any(|(s, _, _)| s == ssrc) {
+                starts.push((*ssrc, *ts, ticks - self.link_start));
+            }
+        }
+
+        starts.sort_unstable();
+
+        for (ssrc, ts, lead_in) in starts {
+            let serial = self.next_serial;
+            self.next_serial = self.next_serial.wrapping_add(1);
+
+            let stream = OggStream::new(&mut self.writer, serial, ssrc, ts, lead_in)?;
+            self.streams.insert(ssrc, stream);
+        }
+
+        Ok(())
+    }
+
+    /// Ends all streams in the current link, and flushes the file.
+    pub fn finish(mut self) -> IoResult<()> {
+        let end = (self.ticks - self.link_start) * MONO_FRAME_SIZE as u64;
+
+        for (_, stream) in self.streams.drain() {
+            stream.finish_at(&mut self.writer, end)?;
+        }
+
+        self.writer.inner_mut().flush()
+    }
+}
+
+/// Identification header for a stereo, 48kHz Opus stream (RFC 7845, section 5.1).
+fn opus_head() -> Vec<u8> {
+    let mut out = b"OpusHead".to_vec();
+
+    // Writes to a Vec cannot fail.
+    let _ = out.write_u8(1); // version
+    let _ = out.write_u8(2); // channel count
+    let _ = out.write_u16::<LittleEndian>(0); // pre-skip
+    let _ = out.write_u32::<LittleEndian>(SAMPLE_RATE_RAW as u32);
+    let _ = out.write_i16::<LittleEndian>(0); // output gain
+    let _ = out.write_u8(0); // channel mapping family
+
+    out
+}
+
+/// Comment header for an Opus stream (RFC 7845, section 5.2), tagged with the SSRC of its user.
+fn opus_tags(ssrc: u32) -> Vec<u8> {
+    let vendor = concat!("songbird ", env!("CARGO_PKG_VERSION"));
+    let comment = format!("SSRC={}", ssrc);
+    let mut out = b"OpusTags".to_vec();
+
+    let _ = out.write_u32::<LittleEndian>(vendor.len() as u32);
+    out.extend_from_slice(vendor.as_bytes());
+    let _ = out.write_u32::<LittleEndian>(1); // user comment count
+    let _ = out.write_u32::<LittleEndian>(comment.len() as u32);
+    out.extend_from_slice(comment.as_bytes());
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ogg::PacketReader;
+    use std::collections::HashSet;
+
+    // 20ms stereo CELT frame (TOC only).
+    const FRAME: [u8; 1] = [0xfc];
+
+    #[test]
+    fn gaps_are_filled_with_silence() {
+        let path = std::env::temp_dir().join(format!("songbird-ogg-{}.opus", std::process::id()));
+        let mut writer = OggWriter::new(BufWriter::new(File::create(&path).unwrap()));
+
+        let frame = FRAME;
+        let ts = u32::MAX - 100;
+
+        let mut stream = OggStream::new(&mut writer, 7, 7, ts, 2).unwrap();
+        stream.write_packet(&mut writer, ts, &frame).unwrap();
+        stream
+            .write_packet(&mut writer, ts.wrapping_add(3 * 960), &frame)
+            .unwrap();
+        stream.finish(&mut writer).unwrap();
+        writer.inner_mut().flush().unwrap();
+        drop(writer);
+
+        let mut reader = PacketReader::new(File::open(&path).unwrap());
+        let mut packets = vec![];
+        while let Some(pkt) = reader.read_packet().unwrap() {
+            assert_eq!(pkt.stream_serial(), 7);
+            packets.push(pkt);
+        }
+        let _ = std::fs::remove_file(&path);
+
+        // Headers, 2 frames of lead-in, packet, 2 frames of gap, packet, end.
+        assert_eq!(packets.len(), 2 + 2 + 1 + 2 + 1 + 1);
+        assert_eq!(&packets[4].data[..], &frame[..]);
+        assert_eq!(&packets[7].data[..], &frame[..]);
+
+        let last = packets.last().unwrap();
+        assert!(last.last_in_stream());
+        assert_eq!(last.absgp_page(), 7 * 960);
+    }
+
+    #[test]
+    fn new_users_begin_new_links() {
+        let path =
+            std::env::temp_dir().join(format!("songbird-ogg-chain-{}.opus", std::process::id()));
+        let writer = OggWriter::new(BufWriter::new(File::create(&path).unwrap()));
+        let mut chain = OggChain::new(writer);
+
+        // User 1 speaks for ticks 1-3, and user 2 joins at tick 3 until tick 4.
+        chain.write_tick(&[], 0).unwrap();
+        chain.write_tick(&[(1, 1000, &FRAME)], 1).unwrap();
+        chain.write_tick(&[(1, 1960, &FRAME)], 2).unwrap();
+        chain
+            .write_tick(&[(1, 2920, &FRAME), (2, 50, &FRAME)], 3)
+            .unwrap();
+        chain.write_tick(&[(2, 1010, &FRAME)], 4).unwrap();
+        chain.finish().unwrap();
+
+        let mut reader = PacketReader::new(File::open(&path).unwrap());
+        let mut open = HashSet::new();
+        let mut audio_in_link = false;
+        let mut links = vec![vec![]];
+
+        while let Some(pkt) = reader.read_packet().unwrap() {
+            if pkt.first_in_stream() {
+                // All streams of a link must begin before its audio.
+                assert!(!audio_in_link);
+                open.insert(pkt.stream_serial());
+            } else if !pkt.data.starts_with(b"Opus") {
+                audio_in_link = true;
+            }
+
+            if pkt.last_in_stream() {
+                open.remove(&pkt.stream_serial());
+                links.last_mut().unwrap().push(pkt.absgp_page());
+
+                if open.is_empty() {
+                    audio_in_link = false;
+                    links.push(vec![]);
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+
+        // Each link's streams end together, after 3 and 2 ticks respectively.
+        links.pop();
+        assert_eq!(links, vec![vec![3 * 960], vec![2 * 960, 2 * 960]]);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::constants::*;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::{
+    fs::File,
+    io::{BufWriter, Result as IoResult, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const HEADER_LEN: u32 = 44;
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Writer for 16-bit stereo PCM audio in a WAV container.
+///
+/// The RIFF and data chunk lengths are only correct after [`finish`] is called.
+///
+/// [`finish`]: WavWriter::finish
+#[derive(Debug)]
+pub struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path) -> IoResult<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let block_align = CHANNELS * (BITS_PER_SAMPLE / 8);
+
+        file.write_all(b"RIFF")?;
+        file.write_u32::<LittleEndian>(HEADER_LEN - 8)?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_u32::<LittleEndian>(16)?;
+        file.write_u16::<LittleEndian>(1)?; // PCM
+        file.write_u16::<LittleEndian>(CHANNELS)?;
+        file.write_u32::<LittleEndian>(SAMPLE_RATE_RAW as u32)?;
+        file.write_u32::<LittleEndian>(SAMPLE_RATE_RAW as u32 * u32::from(block_align))?;
+        file.write_u16::<LittleEndian>(block_align)?;
+        file.write_u16::<LittleEndian>(BITS_PER_SAMPLE)?;
+        file.write_all(b"data")?;
+        file.write_u32::<LittleEndian>(0)?;
+
+        Ok(Self { file, data_len: 0 })
+    }
+
+    /// Writes interleaved stereo samples.
+    pub fn write_samples(&mut self, samples: &[i16]) -> IoResult<()> {
+        for sample in samples {
+            self.file.write_i16::<LittleEndian>(*sample)?;
+        }
+
+        self.data_len = self
+            .data_len
+            .saturating_add(std::mem::size_of_val(samples) as u32);
+
+        Ok(())
+    }
+
+    /// Writes interleaved stereo floating-point samples, clamping them to `[-1.0, 1.0]`.
+    pub fn write_float_samples(&mut self, samples: &[f32]) -> IoResult<()> {
+        let samples: Vec<i16> = samples
+            .iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+            .collect();
+
+        self.write_samples(&samples)
+    }
+
+    /// Writes `frames` 20ms frames of silence.
+    pub fn write_silence(&mut self, frames: u64) -> IoResult<()> {
+        let silence = [0i16; STEREO_FRAME_SIZE];
+
+        for _ in 0..frames {
+            self.write_samples(&silence[..])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the final chunk lengths into the file's header.
+    pub fn finish(mut self) -> IoResult<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_u32::<LittleEndian>(self.data_len.saturating_add(HEADER_LEN - 8))?;
+        self.file.seek(SeekFrom::Start(u64::from(HEADER_LEN - 4)))?;
+        self.file.write_u32::<LittleEndian>(self.data_len)?;
+        self.file.flush()
+    }
+}
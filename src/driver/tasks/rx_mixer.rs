@@ -0,0 +1,151 @@
+use super::message::*;
+use crate::{
+    constants::*,
+    events::{
+        context_data::{VoiceMix, VoiceTick},
+        CoreContext,
+    },
+};
+use flume::{Receiver, Sender, TrySendError};
+use std::collections::HashMap;
+use tracing::{instrument, trace};
+
+struct RxMixer {
+    interconnect: Interconnect,
+    volumes: HashMap<u32, f32>,
+    streams: Vec<Sender<Vec<f32>>>,
+}
+
+impl RxMixer {
+    fn new(interconnect: Interconnect) -> Self {
+        Self {
+            interconnect,
+            volumes: HashMap::new(),
+            streams: vec![],
+        }
+    }
+
+    fn mix_tick(&mut self, tick: VoiceTick) {
+        let mut mix = VoiceMix {
+            audio: vec![0.0; STEREO_FRAME_SIZE],
+            ..Default::default()
+        };
+
+        for (ssrc, frame) in tick.speaking {
+            if let Some(voice) = frame.decoded_voice {
+                let volume = self.volumes.get(&ssrc).copied().unwrap_or(1.0);
+
+                for (out, sample) in mix.audio.iter_mut().zip(voice) {
+                    *out += volume * f32::from(sample) / 32768.0;
+                }
+
+                mix.speaking.insert(ssrc);
+            }
+        }
+
+        // Readers which have been dropped are removed here. Streams which are
+        // not being read from drop new audio, rather than buffering forever.
+        self.streams.retain(|stream| {
+            !matches!(
+                stream.try_send(mix.audio.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+
+        let _ = self
+            .interconnect
+            .events
+            .send(EventMessage::FireCoreEvent(CoreContext::VoiceMix(mix)));
+    }
+}
+
+/// Combines the per-user audio of each [`VoiceTick`] into a single stereo stream.
+///
+/// This lives for as long as its parent driver, so that attached streams
+/// survive reconnections.
+#[instrument(skip(interconnect, rx))]
+pub(crate) async fn runner(interconnect: Interconnect, rx: Receiver<RxMixerMessage>) {
+    let mut state = RxMixer::new(interconnect);
+
+    loop {
+        use RxMixerMessage::*;
+        match rx.recv_async().await {
+            Ok(Tick(tick)) => state.mix_tick(tick),
+            Ok(SetVolume(ssrc, volume)) => {
+                state.volumes.insert(ssrc, volume);
+            },
+            Ok(AddStream(tx)) => state.streams.push(tx),
+            Ok(ReplaceInterconnect(i)) => state.interconnect = i,
+            Ok(Poison) | Err(_) => break,
+        }
+    }
+
+    trace!("Receive mixer exited.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::context_data::VoiceFrame;
+
+    fn frame(sample: i16) -> VoiceFrame {
+        VoiceFrame {
+            packet: None,
+            payload_offset: 0,
+            payload_end_pad: 0,
+            decoded_voice: Some(vec![sample; STEREO_FRAME_SIZE]),
+        }
+    }
+
+    #[test]
+    fn mixes_aligned_frames_with_user_volumes() {
+        let (core, _) = flume::unbounded();
+        let (events, events_rx) = flume::unbounded();
+        let (mixer, _) = flume::unbounded();
+        let (rx_mixer, _) = flume::unbounded();
+        let (recorder, _) = flume::unbounded();
+
+        let mut state = RxMixer::new(Interconnect {
+            core,
+            events,
+            mixer,
+            rx_mixer,
+            recorder,
+            recording: Default::default(),
+            stats: Default::default(),
+        });
+
+        let (stream_tx, stream_rx) = flume::bounded(1);
+        state.streams.push(stream_tx);
+        state.volumes.insert(1, 0.5);
+
+        let mut tick = VoiceTick::default();
+        tick.speaking.insert(1, frame(16384));
+        tick.speaking.insert(2, frame(8192));
+        tick.silent.insert(3);
+        state.mix_tick(tick);
+
+        // Silent ticks still produce a full frame, keeping the mix aligned.
+        state.mix_tick(VoiceTick::default());
+
+        let mix = match events_rx.try_recv().unwrap() {
+            EventMessage::FireCoreEvent(CoreContext::VoiceMix(mix)) => mix,
+            _ => panic!("Expected a VoiceMix event."),
+        };
+        assert_eq!(mix.audio.len(), STEREO_FRAME_SIZE);
+        assert!(mix.audio.iter().all(|s| (s - 0.5).abs() < 1e-6));
+        assert_eq!(mix.speaking, vec![1, 2].into_iter().collect());
+
+        let silent = match events_rx.try_recv().unwrap() {
+            EventMessage::FireCoreEvent(CoreContext::VoiceMix(mix)) => mix,
+            _ => panic!("Expected a VoiceMix event."),
+        };
+        assert_eq!(silent.audio, vec![0.0; STEREO_FRAME_SIZE]);
+        assert!(silent.speaking.is_empty());
+
+        // The full stream keeps its first frame, and is not dropped.
+        assert_eq!(stream_rx.try_recv().unwrap(), mix.audio);
+        assert!(stream_rx.try_recv().is_err());
+        assert_eq!(state.streams.len(), 1);
+    }
+}
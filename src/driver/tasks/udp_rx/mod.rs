@@ -0,0 +1,659 @@
+mod playout_buffer;
+
+use super::{
+    error::{Error, Result},
+    message::*,
+    Config,
+};
+use crate::{
+    constants::*,
+    driver::{transport::UdpTransport, Cipher, CryptoMode, DecodeMode},
+    events::{
+        context_data::{VoiceFrame, VoiceTick},
+        internal_data::*,
+        CoreContext,
+    },
+};
+use audiopus::{
+    coder::Decoder as OpusDecoder,
+    error::{Error as OpusError, ErrorCode},
+    packet::Packet as OpusPacket,
+    Channels,
+};
+use discortp::{
+    demux::{self, DemuxedMut},
+    rtp::{Rtp, RtpExtensionPacket, RtpPacket},
+    FromPacket,
+    Packet,
+    PacketSize,
+};
+use flume::Receiver;
+use playout_buffer::{PacketLookup, PlayoutBuffer, StoredPacket};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::{
+    select,
+    time::{sleep_until, Instant},
+};
+use tracing::{error, instrument, trace, warn};
+
+/// Length of time after which a silent source's state is discarded.
+const SSRC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct SsrcState {
+    playout_buffer: PlayoutBuffer,
+    silent_frame_count: u16,
+    decoder: OpusDecoder,
+    last_seq: u16,
+    decode_size: PacketDecodeSize,
+    /// Decoded audio from packets longer than 20ms, awaiting playout.
+    leftover_audio: Vec<i16>,
+    last_packet: Instant,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PacketDecodeSize {
+    /// Minimum frame size on Discord.
+    TwentyMillis,
+    /// Hybrid packet, sent by Firefox web client.
+    ///
+    /// Likely 20ms frame + 10ms frame.
+    ThirtyMillis,
+    /// Next largest frame size.
+    FortyMillis,
+    /// Maximum Opus frame size.
+    SixtyMillis,
+    /// Maximum Opus packet size: 120ms.
+    Max,
+}
+
+impl PacketDecodeSize {
+    fn bump_up(self) -> Self {
+        use PacketDecodeSize::*;
+        match self {
+            TwentyMillis => ThirtyMillis,
+            ThirtyMillis => FortyMillis,
+            FortyMillis => SixtyMillis,
+            SixtyMillis | Max => Max,
+        }
+    }
+
+    fn can_bump_up(self) -> bool {
+        self != PacketDecodeSize::Max
+    }
+
+    fn len(self) -> usize {
+        use PacketDecodeSize::*;
+        match self {
+            TwentyMillis => STEREO_FRAME_SIZE,
+            ThirtyMillis => (STEREO_FRAME_SIZE / 2) * 3,
+            FortyMillis => 2 * STEREO_FRAME_SIZE,
+            SixtyMillis => 3 * STEREO_FRAME_SIZE,
+            Max => 6 * STEREO_FRAME_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SpeakingDelta {
+    Same,
+    Start,
+    Stop,
+}
+
+impl SsrcState {
+    fn new(pkt: RtpPacket<'_>, config: &Config) -> Self {
+        let seq = pkt.get_sequence().into();
+
+        Self {
+            playout_buffer: PlayoutBuffer::new(config, seq),
+            silent_frame_count: 5, // We do this to make the first speech packet fire an event.
+            decoder: OpusDecoder::new(SAMPLE_RATE, Channels::Stereo)
+                .expect("Failed to create new Opus decoder for source."),
+            last_seq: seq,
+            decode_size: PacketDecodeSize::TwentyMillis,
+            leftover_audio: Vec::new(),
+            last_packet: Instant::now(),
+        }
+    }
+
+    fn process(
+        &mut self,
+        pkt: RtpPacket<'_>,
+        data_offset: usize,
+        data_trailer: usize,
+        decrypted: bool,
+    ) -> Result<SpeakingDelta> {
+        let new_seq: u16 = pkt.get_sequence().into();
+        let payload_len = pkt.payload().len();
+
+        self.last_packet = Instant::now();
+
+        let extensions = pkt.get_extension() != 0;
+        let seq_delta = new_seq.wrapping_sub(self.last_seq);
+        Ok(if seq_delta >= (1 << 15) {
+            // Overflow, reordered (previously missing) packet.
+            // Playout handles this: it has no bearing on speaking state.
+            SpeakingDelta::Same
+        } else {
+            self.last_seq = new_seq;
+            let missed_packets = seq_delta.saturating_sub(1);
+
+            // Note: we still need to handle this for non-decoded.
+            // This is mainly because packet events and speaking events can be handed to the
+            // user.
+            let pkt_size = if decrypted {
+                opus_data(
+                    &pkt.payload()[data_offset..payload_len - data_trailer],
+                    extensions,
+                )?
+                .len()
+            } else {
+                // The latter part is an upper bound, as we cannot determine
+                // how long packet extensions are.
+                // WIthout decryption, speaking detection is thus broken.
+                payload_len - data_offset - data_trailer
+            };
+
+            if pkt_size == SILENT_FRAME.len() {
+                // Frame is silent.
+                let old = self.silent_frame_count;
+                self.silent_frame_count =
+                    self.silent_frame_count.saturating_add(1 + missed_packets);
+
+                if self.silent_frame_count >= 5 && old < 5 {
+                    SpeakingDelta::Stop
+                } else {
+                    SpeakingDelta::Same
+                }
+            } else {
+                // Frame has meaningful audio.
+                let out = if self.silent_frame_count >= 5 {
+                    SpeakingDelta::Start
+                } else {
+                    SpeakingDelta::Same
+                };
+                self.silent_frame_count = 0;
+                out
+            }
+        })
+    }
+
+    /// Plays out this source's next 20ms frame from its jitter buffer.
+    ///
+    /// Returns `None` if the source is silent (or still filling its buffer).
+    /// If `decode` is set, decoded packets are additionally returned as a
+    /// [`InternalVoicePacket`] containing all of their audio.
+    fn get_voice_tick(
+        &mut self,
+        decode: bool,
+    ) -> Result<Option<(VoiceFrame, Option<InternalVoicePacket>)>> {
+        if self.leftover_audio.len() >= STEREO_FRAME_SIZE {
+            // Still playing out a packet longer than 20ms.
+            let decoded_voice = self.leftover_audio.drain(..STEREO_FRAME_SIZE).collect();
+
+            return Ok(Some((
+                VoiceFrame {
+                    packet: None,
+                    payload_offset: 0,
+                    payload_end_pad: 0,
+                    decoded_voice: Some(decoded_voice),
+                },
+                None,
+            )));
+        }
+
+        Ok(match self.playout_buffer.fetch_packet() {
+            PacketLookup::Filling => None,
+            PacketLookup::Packet(pkt) =>
+                if decode && pkt.decrypted {
+                    let audio = self.decode_packet(&pkt)?;
+
+                    let mut decoded_voice = vec![0; STEREO_FRAME_SIZE];
+                    let frame_len = audio.len().min(STEREO_FRAME_SIZE);
+                    decoded_voice[..frame_len].copy_from_slice(&audio[..frame_len]);
+                    self.leftover_audio.clear();
+                    self.leftover_audio.extend_from_slice(&audio[frame_len..]);
+
+                    let packet_evt = InternalVoicePacket {
+                        audio: Some(audio),
+                        packet: pkt.packet.clone(),
+                        payload_offset: pkt.payload_offset,
+                        payload_end_pad: pkt.payload_end_pad,
+                    };
+
+                    Some((
+                        VoiceFrame {
+                            packet: Some(pkt.packet),
+                            payload_offset: pkt.payload_offset,
+                            payload_end_pad: pkt.payload_end_pad,
+                            decoded_voice: Some(decoded_voice),
+                        },
+                        Some(packet_evt),
+                    ))
+                } else if !pkt.decrypted {
+                    // Packet could not be decrypted: treat it as though it were lost.
+                    Some((self.missed_frame(decode), None))
+                } else {
+                    Some((
+                        VoiceFrame {
+                            packet: Some(pkt.packet),
+                            payload_offset: pkt.payload_offset,
+                            payload_end_pad: pkt.payload_end_pad,
+                            decoded_voice: None,
+                        },
+                        None,
+                    ))
+                },
+            PacketLookup::MissedPacket => Some((self.missed_frame(decode), None)),
+        })
+    }
+
+    fn missed_frame(&mut self, decode: bool) -> VoiceFrame {
+        if decode {
+            self.conceal_missed_frame()
+        } else {
+            VoiceFrame {
+                packet: None,
+                payload_offset: 0,
+                payload_end_pad: 0,
+                decoded_voice: None,
+            }
+        }
+    }
+
+    /// Regenerates a lost frame using the in-band FEC data of the following packet
+    /// (if it has already arrived), or Opus's packet loss concealment otherwise.
+    fn conceal_missed_frame(&mut self) -> VoiceFrame {
+        let mut out = vec![0; STEREO_FRAME_SIZE];
+
+        let fec_data = self
+            .playout_buffer
+            .peek_next()
+            .filter(|next| next.decrypted)
+            .and_then(|next| stored_opus_data(next).ok());
+
+        let dest_samples = (&mut out[..])
+            .try_into()
+            .expect("Decode logic will cap decode buffer size at i32::MAX.");
+
+        let res = match fec_data.map(TryInto::try_into) {
+            Some(Ok(next_packet)) => self.decoder.decode(Some(next_packet), dest_samples, true),
+            _ => {
+                let missing_frame: Option<OpusPacket> = None;
+                self.decoder.decode(missing_frame, dest_samples, false)
+            },
+        };
+
+        if let Err(e) = res {
+            warn!("Issue while decoding for missed packet: {:?}.", e);
+        }
+
+        VoiceFrame {
+            packet: None,
+            payload_offset: 0,
+            payload_end_pad: 0,
+            decoded_voice: Some(out),
+        }
+    }
+
+    fn decode_packet(&mut self, pkt: &StoredPacket) -> Result<Vec<i16>> {
+        let data = stored_opus_data(pkt)?;
+        let mut out = vec![0; self.decode_size.len()];
+
+        // In general, we should expect 20 ms frames.
+        // However, Discord occasionally like to surprise us with something bigger.
+        // This is *sender-dependent behaviour*.
+        //
+        // This should scan up to find the "correct" size that a source is using,
+        // and then remember that.
+        loop {
+            let tried_audio_len =
+                self.decoder
+                    .decode(Some(data.try_into()?), (&mut out[..]).try_into()?, false);
+
+            match tried_audio_len {
+                Ok(audio_len) => {
+                    // Decoding to stereo: audio_len refers to sample count irrespective of channel count.
+                    // => multiply by number of channels.
+                    out.truncate(2 * audio_len);
+
+                    break;
+                },
+                Err(OpusError::Opus(ErrorCode::BufferTooSmall)) => {
+                    if self.decode_size.can_bump_up() {
+                        self.decode_size = self.decode_size.bump_up();
+                        out = vec![0; self.decode_size.len()];
+                    } else {
+                        error!("Received packet larger than Opus standard maximum,");
+                        return Err(Error::IllegalVoicePacket);
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to decode received packet: {:?}.", e);
+                    return Err(e.into());
+                },
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Returns whether this source has no audio left to play, and has not
+    /// transmitted for a long time.
+    fn is_stale(&self, now: Instant) -> bool {
+        self.playout_buffer.is_empty()
+            && self.leftover_audio.is_empty()
+            && now.saturating_duration_since(self.last_packet) >= SSRC_TIMEOUT
+    }
+}
+
+/// Returns the Opus payload of a decrypted RTP body, skipping any extensions.
+fn opus_data(data: &[u8], extension: bool) -> Result<&[u8]> {
+    let start = if extension {
+        RtpExtensionPacket::new(data)
+            .map(|pkt| pkt.packet_size())
+            .ok_or_else(|| {
+                error!("Extension packet indicated, but insufficient space.");
+                Error::IllegalVoicePacket
+            })
+    } else {
+        Ok(0)
+    }?;
+
+    Ok(&data[start..])
+}
+
+/// Returns the Opus payload of a decrypted RTP packet, given the bounds of its body.
+pub(crate) fn rtp_opus_data(
+    packet: &Rtp,
+    payload_offset: usize,
+    payload_end_pad: usize,
+) -> Result<&[u8]> {
+    let payload = &packet.payload[..];
+    let end = payload
+        .len()
+        .checked_sub(payload_end_pad)
+        .filter(|end| *end >= payload_offset)
+        .ok_or(Error::IllegalVoicePacket)?;
+
+    opus_data(&payload[payload_offset..end], packet.extension != 0)
+}
+
+fn stored_opus_data(pkt: &StoredPacket) -> Result<&[u8]> {
+    rtp_opus_data(&pkt.packet, pkt.payload_offset, pkt.payload_end_pad)
+}
+
+struct UdpRx {
+    cipher: Cipher,
+    crypto_mode: CryptoMode,
+    decoder_map: HashMap<u32, SsrcState>,
+    config: Config,
+    packet_buffer: [u8; VOICE_PACKET_MAX],
+    rx: Receiver<UdpRxMessage>,
+
+    udp_socket: Arc<dyn UdpTransport>,
+}
+
+impl UdpRx {
+    #[instrument(skip(self))]
+    async fn run(&mut self, interconnect: &mut Interconnect) {
+        let mut playout_time = Instant::now() + TIMESTEP_LENGTH;
+
+        loop {
+            select! {
+                Ok(len) = self.udp_socket.recv(&mut self.packet_buffer[..]) => {
+                    self.process_udp_message(interconnect, len);
+                }
+                _ = sleep_until(playout_time), if self.config.decode_mode.should_decrypt() => {
+                    self.playout_tick(interconnect);
+
+                    // Don't try to catch up on ticks missed due to scheduling delays:
+                    // these would only arrive as a burst.
+                    playout_time = (playout_time + TIMESTEP_LENGTH).max(Instant::now());
+                }
+                msg = self.rx.recv_async() => {
+                    use UdpRxMessage::*;
+                    match msg {
+                        Ok(ReplaceInterconnect(i)) => {
+                            *interconnect = i;
+                        },
+                        Ok(SetConfig(c)) => {
+                            self.config = c;
+                        },
+                        Ok(Poison) | Err(_) => break,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Plays out one 20ms frame from every known source, firing a [`VoiceTick`].
+    fn playout_tick(&mut self, interconnect: &Interconnect) {
+        let now = Instant::now();
+        self.decoder_map.retain(|_, state| !state.is_stale(now));
+
+        let decode = self.config.decode_mode == DecodeMode::Decode;
+        let mut tick = VoiceTick::default();
+
+        for (ssrc, state) in self.decoder_map.iter_mut() {
+            match state.get_voice_tick(decode) {
+                Ok(Some((frame, packet_evt))) => {
+                    if let Some(packet_evt) = packet_evt {
+                        let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                            CoreContext::VoicePacket(packet_evt),
+                        ));
+                    }
+
+                    tick.speaking.insert(*ssrc, frame);
+                },
+                Ok(None) => {
+                    tick.silent.insert(*ssrc);
+                },
+                Err(e) => {
+                    warn!("RTP playout for SSRC {} failed: {:?}", ssrc, e);
+                    tick.silent.insert(*ssrc);
+                },
+            }
+        }
+
+        // The receive mix and any recordings continue while nobody is speaking.
+        if decode {
+            let _ = interconnect
+                .rx_mixer
+                .send(RxMixerMessage::Tick(tick.clone()));
+        }
+
+        if interconnect.recording.load(Ordering::Relaxed) {
+            let _ = interconnect
+                .recorder
+                .send(RecorderMessage::Tick(tick.clone()));
+        }
+
+        if !self.decoder_map.is_empty() {
+            let _ = interconnect
+                .events
+                .send(EventMessage::FireCoreEvent(CoreContext::VoiceTick(tick)));
+        }
+    }
+
+    fn process_udp_message(&mut self, interconnect: &Interconnect, len: usize) {
+        // NOTE: errors here (and in general for UDP) are not fatal to the connection.
+        // Panics should be avoided due to adversarial nature of rx'd packets,
+        // but correct handling should not prompt a reconnect.
+        //
+        // For simplicity, we nominate the mixing context to rebuild the event
+        // context if it fails (hence, the `let _ =` statements.), as it will try to
+        // make contact every 20ms.
+        let crypto_mode = self.crypto_mode;
+        let packet = &mut self.packet_buffer[..len];
+
+        interconnect.stats.record_received(len);
+
+        match demux::demux_mut(packet) {
+            DemuxedMut::Rtp(mut rtp) => {
+                if !rtp_valid(rtp.to_immutable()) {
+                    error!("Illegal RTP message received.");
+                    return;
+                }
+
+                let packet_data = if self.config.decode_mode.should_decrypt() {
+                    let out = crypto_mode
+                        .decrypt_rtp_in_place(&mut rtp, &self.cipher)
+                        .map(|(s, t)| (s, t, true));
+
+                    if let Err(e) = out {
+                        warn!("RTP decryption failed: {:?}", e);
+                    }
+
+                    out.ok()
+                } else {
+                    None
+                };
+
+                let (rtp_body_start, rtp_body_tail, decrypted) = packet_data.unwrap_or_else(|| {
+                    (
+                        crypto_mode.payload_prefix_len(),
+                        crypto_mode.payload_suffix_len(),
+                        false,
+                    )
+                });
+
+                let config = &self.config;
+                let entry = self
+                    .decoder_map
+                    .entry(rtp.get_ssrc())
+                    .or_insert_with(|| SsrcState::new(rtp.to_immutable(), config));
+
+                if let Ok(delta) =
+                    entry.process(rtp.to_immutable(), rtp_body_start, rtp_body_tail, decrypted)
+                {
+                    match delta {
+                        SpeakingDelta::Start => {
+                            let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                                CoreContext::SpeakingUpdate(InternalSpeakingUpdate {
+                                    ssrc: rtp.get_ssrc(),
+                                    speaking: true,
+                                }),
+                            ));
+                        },
+                        SpeakingDelta::Stop => {
+                            let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                                CoreContext::SpeakingUpdate(InternalSpeakingUpdate {
+                                    ssrc: rtp.get_ssrc(),
+                                    speaking: false,
+                                }),
+                            ));
+                        },
+                        _ => {},
+                    }
+
+                    let packet = rtp.from_packet();
+
+                    if self.config.decode_mode.should_decrypt() {
+                        entry.playout_buffer.store_packet(
+                            StoredPacket {
+                                packet: packet.clone(),
+                                payload_offset: rtp_body_start,
+                                payload_end_pad: rtp_body_tail,
+                                decrypted,
+                            },
+                            config,
+                        );
+                    }
+
+                    // Decoded packets are announced in playout order, once decoded.
+                    if self.config.decode_mode != DecodeMode::Decode {
+                        let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                            CoreContext::VoicePacket(InternalVoicePacket {
+                                audio: None,
+                                packet,
+                                payload_offset: rtp_body_start,
+                                payload_end_pad: rtp_body_tail,
+                            }),
+                        ));
+                    }
+                } else {
+                    warn!("RTP decoding/processing failed.");
+                }
+            },
+            DemuxedMut::Rtcp(mut rtcp) => {
+                let packet_data = if self.config.decode_mode.should_decrypt() {
+                    let out = crypto_mode.decrypt_in_place(&mut rtcp, &self.cipher);
+
+                    if let Err(e) = out {
+                        warn!("RTCP decryption failed: {:?}", e);
+                    }
+
+                    out.ok()
+                } else {
+                    None
+                };
+
+                if let Some((start, tail)) = packet_data {
+                    interconnect.stats.record_rtcp(&rtcp, start, tail);
+                }
+
+                let (start, tail) = packet_data.unwrap_or_else(|| {
+                    (
+                        crypto_mode.payload_prefix_len(),
+                        crypto_mode.payload_suffix_len(),
+                    )
+                });
+
+                let _ =
+                    interconnect
+                        .events
+                        .send(EventMessage::FireCoreEvent(CoreContext::RtcpPacket(
+                            InternalRtcpPacket {
+                                packet: rtcp.from_packet(),
+                                payload_offset: start,
+                                payload_end_pad: tail,
+                            },
+                        )));
+            },
+            DemuxedMut::FailedParse(t) => {
+                warn!("Failed to parse message of type {:?}.", t);
+            },
+            _ => {
+                warn!("Illegal UDP packet from voice server.");
+            },
+        }
+    }
+}
+
+#[instrument(skip(interconnect, rx, cipher))]
+pub(crate) async fn runner(
+    mut interconnect: Interconnect,
+    rx: Receiver<UdpRxMessage>,
+    cipher: Cipher,
+    crypto_mode: CryptoMode,
+    config: Config,
+    udp_socket: Arc<dyn UdpTransport>,
+) {
+    trace!("UDP receive handle started.");
+
+    let mut state = UdpRx {
+        cipher,
+        crypto_mode,
+        decoder_map: Default::default(),
+        config,
+        packet_buffer: [0u8; VOICE_PACKET_MAX],
+        rx,
+        udp_socket,
+    };
+
+    state.run(&mut interconnect).await;
+
+    trace!("UDP receive handle stopped.");
+}
+
+#[inline]
+fn rtp_valid(packet: RtpPacket<'_>) -> bool {
+    packet.get_version() == RTP_VERSION && packet.get_payload_type() == RTP_PROFILE_TYPE
+}
@@ -0,0 +1,228 @@
+use crate::driver::Config;
+use discortp::rtp::Rtp;
+use std::collections::VecDeque;
+use tracing::trace;
+
+/// A received RTP packet, and the location of its (decrypted) payload.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StoredPacket {
+    pub packet: Rtp,
+    pub payload_offset: usize,
+    pub payload_end_pad: usize,
+    pub decrypted: bool,
+}
+
+impl StoredPacket {
+    fn sequence(&self) -> u16 {
+        self.packet.sequence.into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PlayoutMode {
+    /// Packets are being gathered until the target playout delay is reached.
+    Fill,
+    /// One frame is released per tick.
+    Drain,
+}
+
+/// Result of asking a [`PlayoutBuffer`] for the next frame of audio.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PacketLookup {
+    /// The next packet in sequence.
+    Packet(StoredPacket),
+    /// The next packet in sequence was lost, or has not yet arrived.
+    MissedPacket,
+    /// The buffer has not yet reached its target delay: no audio should be played.
+    Filling,
+}
+
+/// Per-SSRC jitter buffer, reordering received packets by RTP sequence number.
+///
+/// Slot `i` of the buffer holds the packet with sequence number `next_seq + i`,
+/// if it has been received.
+#[derive(Debug)]
+pub struct PlayoutBuffer {
+    buffer: VecDeque<Option<StoredPacket>>,
+    playout_mode: PlayoutMode,
+    next_seq: u16,
+}
+
+impl PlayoutBuffer {
+    pub fn new(config: &Config, next_seq: u16) -> Self {
+        Self {
+            buffer: VecDeque::with_capacity(max_len(config)),
+            playout_mode: PlayoutMode::Fill,
+            next_seq,
+        }
+    }
+
+    /// Places a packet into its slot in the buffer.
+    ///
+    /// Packets which arrive after their slot has been played out are discarded.
+    /// Packets arriving too far ahead of the playout point (i.e., during a burst
+    /// exceeding `playout_spike_length`) force the oldest frames to be skipped.
+    pub fn store_packet(&mut self, packet: StoredPacket, config: &Config) {
+        let seq = packet.sequence();
+
+        if self.playout_mode == PlayoutMode::Fill && self.buffer.is_empty() {
+            // Start of a new talk burst: resynchronise on this packet.
+            self.next_seq = seq;
+        }
+
+        let desired_index = seq.wrapping_sub(self.next_seq) as i16;
+
+        if desired_index < 0 {
+            trace!(
+                "Missed playout of packet {} (next is {}).",
+                seq,
+                self.next_seq
+            );
+            return;
+        }
+
+        let mut desired_index = desired_index as usize;
+        let max_len = max_len(config);
+
+        if desired_index >= max_len {
+            let skip = desired_index + 1 - max_len;
+            trace!("Playout buffer overfull: skipping {} frames.", skip);
+
+            for _ in 0..skip {
+                if self.buffer.pop_front().is_none() {
+                    break;
+                }
+            }
+
+            self.next_seq = self.next_seq.wrapping_add(skip as u16);
+            desired_index -= skip;
+        }
+
+        while self.buffer.len() <= desired_index {
+            self.buffer.push_back(None);
+        }
+
+        self.buffer[desired_index] = Some(packet);
+
+        if self.buffer.len() >= config.playout_buffer_length.get() {
+            self.playout_mode = PlayoutMode::Drain;
+        }
+    }
+
+    /// Removes the frame due to be played out on this tick.
+    pub fn fetch_packet(&mut self) -> PacketLookup {
+        if self.playout_mode == PlayoutMode::Fill {
+            return PacketLookup::Filling;
+        }
+
+        let out = match self.buffer.pop_front() {
+            Some(Some(pkt)) => PacketLookup::Packet(pkt),
+            _ => PacketLookup::MissedPacket,
+        };
+
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        if self.buffer.is_empty() {
+            // Either the source has stopped transmitting, or we have
+            // underrun: rebuild the playout delay before continuing.
+            self.playout_mode = PlayoutMode::Fill;
+        }
+
+        out
+    }
+
+    /// Returns the packet which will be played out on the next tick, if it has arrived.
+    ///
+    /// Used to recover lost audio from the in-band FEC data of its successor.
+    pub fn peek_next(&self) -> Option<&StoredPacket> {
+        if self.playout_mode == PlayoutMode::Fill {
+            return None;
+        }
+
+        self.buffer.front().and_then(Option::as_ref)
+    }
+
+    /// Returns whether this buffer holds no audio.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+#[inline]
+fn max_len(config: &Config) -> usize {
+    config.playout_buffer_length.get() + config.playout_spike_length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use discortp::{rtp::RtpType, wrap::*};
+    use std::num::NonZeroUsize;
+
+    fn packet(seq: u16) -> StoredPacket {
+        StoredPacket {
+            packet: Rtp {
+                version: 2,
+                padding: 0,
+                extension: 0,
+                csrc_count: 0,
+                marker: 0,
+                payload_type: RtpType::Dynamic(120),
+                sequence: Wrap16::new(seq),
+                timestamp: Wrap32::new(960 * u32::from(seq)),
+                ssrc: 1,
+                csrc_list: vec![],
+                payload: vec![],
+            },
+            payload_offset: 0,
+            payload_end_pad: 0,
+            decrypted: true,
+        }
+    }
+
+    fn config() -> Config {
+        Config::default()
+            .playout_buffer_length(NonZeroUsize::new(3).unwrap())
+            .playout_spike_length(2)
+    }
+
+    #[test]
+    fn reorders_and_conceals_gaps() {
+        let config = config();
+        let mut buf = PlayoutBuffer::new(&config, 0);
+
+        buf.store_packet(packet(10), &config);
+        assert_eq!(buf.fetch_packet(), PacketLookup::Filling);
+
+        buf.store_packet(packet(12), &config);
+        buf.store_packet(packet(11), &config);
+
+        assert_eq!(buf.fetch_packet(), PacketLookup::Packet(packet(10)));
+        assert_eq!(buf.peek_next(), Some(&packet(11)));
+        assert_eq!(buf.fetch_packet(), PacketLookup::Packet(packet(11)));
+
+        buf.store_packet(packet(14), &config);
+        assert_eq!(buf.fetch_packet(), PacketLookup::Packet(packet(12)));
+        assert_eq!(buf.fetch_packet(), PacketLookup::MissedPacket);
+
+        // Too late: 13 has been concealed already.
+        buf.store_packet(packet(13), &config);
+        assert_eq!(buf.fetch_packet(), PacketLookup::Packet(packet(14)));
+
+        // Drained: must refill before playing out again.
+        assert_eq!(buf.fetch_packet(), PacketLookup::Filling);
+    }
+
+    #[test]
+    fn burst_skips_oldest_frames() {
+        let config = config();
+        let mut buf = PlayoutBuffer::new(&config, u16::MAX - 1);
+
+        for seq in [u16::MAX - 1, u16::MAX, 0, 1, 2, 3, 4] {
+            buf.store_packet(packet(seq), &config);
+        }
+
+        // Capacity is 5: the two oldest frames are dropped.
+        assert_eq!(buf.fetch_packet(), PacketLookup::Packet(packet(0)));
+    }
+}
@@ -1,19 +1,16 @@
 use super::message::*;
-use crate::constants::*;
+use crate::{constants::*, driver::transport::UdpTransport};
 use discortp::discord::MutableKeepalivePacket;
 use flume::Receiver;
 use std::sync::Arc;
-use tokio::{
-    net::UdpSocket,
-    time::{timeout_at, Instant},
-};
+use tokio::time::{timeout_at, Instant};
 use tracing::{error, instrument, trace};
 
 struct UdpTx {
     ssrc: u32,
     rx: Receiver<UdpTxMessage>,
 
-    udp_tx: Arc<UdpSocket>,
+    udp_tx: Arc<dyn UdpTransport>,
 }
 
 impl UdpTx {
@@ -26,7 +23,6 @@ impl UdpTx {
         let mut ka_time = Instant::now() + UDP_KEEPALIVE_GAP;
 
         loop {
-            use UdpTxMessage::*;
             match timeout_at(ka_time, self.rx.recv_async()).await {
                 Err(_) => {
                     trace!("Sending UDP Keepalive.");
@@ -36,16 +32,11 @@ impl UdpTx {
                     }
                     ka_time += UDP_KEEPALIVE_GAP;
                 },
-                Ok(Ok(Packet(p))) =>
-                    if let Err(e) = self.udp_tx.send(&p[..]).await {
-                        error!("Fatal UDP packet send error: {:?}.", e);
-                        break;
-                    },
                 Ok(Err(e)) => {
                     error!("Fatal UDP packet receive error: {:?}.", e);
                     break;
                 },
-                Ok(Ok(Poison)) => {
+                Ok(Ok(UdpTxMessage::Poison)) => {
                     break;
                 },
             }
@@ -54,7 +45,11 @@ impl UdpTx {
 }
 
 #[instrument(skip(udp_msg_rx))]
-pub(crate) async fn runner(udp_msg_rx: Receiver<UdpTxMessage>, ssrc: u32, udp_tx: Arc<UdpSocket>) {
+pub(crate) async fn runner(
+    udp_msg_rx: Receiver<UdpTxMessage>,
+    ssrc: u32,
+    udp_tx: Arc<dyn UdpTransport>,
+) {
     trace!("UDP transmit handle started.");
 
     let mut txer = UdpTx {
@@ -2,19 +2,22 @@ use super::message::*;
 use crate::{
     events::CoreContext,
     model::{
-        payload::{Heartbeat, Speaking},
+        payload::Speaking,
         CloseCode as VoiceCloseCode,
         Event as GatewayEvent,
         FromPrimitive,
         SpeakingState,
     },
-    ws::{Error as WsError, ReceiverExt, SenderExt, WsStream},
+    ws::{self, Error as WsError, ReceiverExt, SenderExt, WsStream},
     ConnectionInfo,
 };
 use async_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use flume::Receiver;
-use rand::random;
-use std::time::Duration;
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::{
     select,
     time::{sleep_until, Instant},
@@ -30,7 +33,8 @@ struct AuxNetwork {
     heartbeat_interval: Duration,
 
     speaking: SpeakingState,
-    last_heartbeat_nonce: Option<u64>,
+    last_heartbeat: Option<(u64, Instant)>,
+    seq_ack: Arc<Mutex<Option<u64>>>,
 
     attempt_idx: usize,
     info: ConnectionInfo,
@@ -42,6 +46,7 @@ impl AuxNetwork {
         ws_client: WsStream,
         ssrc: u32,
         heartbeat_interval: f64,
+        seq_ack: Arc<Mutex<Option<u64>>>,
         attempt_idx: usize,
         info: ConnectionInfo,
     ) -> Self {
@@ -54,7 +59,8 @@ impl AuxNetwork {
             heartbeat_interval: Duration::from_secs_f64(heartbeat_interval / 1000.0),
 
             speaking: SpeakingState::empty(),
-            last_heartbeat_nonce: None,
+            last_heartbeat: None,
+            seq_ack,
 
             attempt_idx,
             info,
@@ -91,8 +97,13 @@ impl AuxNetwork {
                             ws_reason = Some((&e).into());
                             true
                         },
-                        Ok(Some(msg)) => {
-                            self.process_ws(interconnect, msg);
+                        Ok(Some((msg, seq))) => {
+                            if seq.is_some() {
+                                *self.seq_ack.lock() = seq;
+                            }
+                            if let Some(msg) = msg {
+                                self.process_ws(interconnect, msg);
+                            }
                             false
                         },
                         _ => false,
@@ -105,6 +116,9 @@ impl AuxNetwork {
                             next_heartbeat = self.next_heartbeat();
                             self.dont_send = false;
                         },
+                        Ok(WsMessage::Replayed(msg)) => {
+                            self.process_ws(interconnect, *msg);
+                        },
                         Ok(WsMessage::ReplaceInterconnect(i)) => {
                             *interconnect = i;
                         },
@@ -147,7 +161,9 @@ impl AuxNetwork {
                 self.dont_send = true;
 
                 if should_reconnect {
-                    let _ = interconnect.core.send(CoreMessage::Reconnect);
+                    let _ = interconnect
+                        .core
+                        .send(CoreMessage::Reconnect(self.attempt_idx));
                 } else {
                     let _ = interconnect.core.send(CoreMessage::SignalWsClosure(
                         self.attempt_idx,
@@ -165,14 +181,20 @@ impl AuxNetwork {
     }
 
     async fn send_heartbeat(&mut self) -> Result<(), WsError> {
-        let nonce = random::<u64>();
-        self.last_heartbeat_nonce = Some(nonce);
+        // Nonces must survive a round trip through a JS number, so the
+        // current time (as suggested by Discord) is used in place of random bits.
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.last_heartbeat = Some((nonce, Instant::now()));
 
         trace!("Sent heartbeat {:?}", self.speaking);
 
         if !self.dont_send {
+            let seq_ack = *self.seq_ack.lock();
             self.ws_client
-                .send_json(&GatewayEvent::from(Heartbeat { nonce }))
+                .send_json(&ws::heartbeat(nonce, seq_ack))
                 .await?;
         }
 
@@ -195,9 +217,14 @@ impl AuxNetwork {
                 ));
             },
             GatewayEvent::HeartbeatAck(ev) => {
-                if let Some(nonce) = self.last_heartbeat_nonce.take() {
+                if let Some((nonce, sent)) = self.last_heartbeat.take() {
                     if ev.nonce == nonce {
                         trace!("Heartbeat ACK received.");
+
+                        interconnect.stats.record_heartbeat_rtt(sent.elapsed());
+                        let _ = interconnect.events.send(EventMessage::FireCoreEvent(
+                            CoreContext::ConnectionStats(interconnect.stats.snapshot()),
+                        ));
                     } else {
                         warn!(
                             "Heartbeat nonce mismatch! Expected {}, saw {}.",
@@ -213,6 +240,7 @@ impl AuxNetwork {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(skip(interconnect, ws_client))]
 pub(crate) async fn runner(
     mut interconnect: Interconnect,
@@ -220,6 +248,7 @@ pub(crate) async fn runner(
     ws_client: WsStream,
     ssrc: u32,
     heartbeat_interval: f64,
+    seq_ack: Arc<Mutex<Option<u64>>>,
     attempt_idx: usize,
     info: ConnectionInfo,
 ) {
@@ -229,6 +258,7 @@ pub(crate) async fn runner(
         ws_client,
         ssrc,
         heartbeat_interval,
+        seq_ack,
         attempt_idx,
         info,
     );
@@ -237,7 +267,7 @@ pub(crate) async fn runner(
     trace!("WS thread finished.");
 }
 
-fn ws_error_is_not_final(err: &WsError) -> bool {
+pub(crate) fn ws_error_is_not_final(err: &WsError) -> bool {
     match err {
         WsError::WsClosed(Some(frame)) => match frame.code {
             CloseCode::Library(l) =>
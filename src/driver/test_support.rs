@@ -0,0 +1,979 @@
+//! A local stand-in for Discord's voice servers, for testing code built on songbird.
+//!
+//! A [`MockServer`] speaks enough of the voice gateway protocol (Hello, Identify,
+//! Ready, SelectProtocol, SessionDescription, Heartbeat, Speaking and Resume) and
+//! of the UDP protocol (IP discovery, encrypted RTP) for a [`Driver`] to connect
+//! and play audio exactly as it would against Discord. All traffic travels over a
+//! [`MemoryTransport`], so tests need neither network access nor credentials.
+//!
+//! Each connection is exposed as a [`MockSession`], which decrypts the audio sent
+//! by the driver and can inject gateway events and voice packets from fake users.
+//!
+//! Requires the `"test-support"` feature.
+//!
+//! # Example
+//! ```rust,no_run
+//! use songbird::driver::{test_support::MockServer, Driver};
+//!
+//! # async {
+//! let server = MockServer::new();
+//! let mut driver = Driver::new(server.config());
+//!
+//! driver.connect(MockServer::connection_info(1, 2)).await.unwrap();
+//! let session = server.next_session().await.unwrap();
+//!
+//! // ... play a track, then:
+//! let audio = session.recv_audio().await.unwrap();
+//! # };
+//! ```
+//!
+//! [`Driver`]: super::Driver
+
+use super::{
+    transport::{MemoryListener, MemoryTransport, MemoryUdpSocket, WsStream},
+    Cipher,
+    CryptoMode,
+    CryptoState,
+};
+use crate::{
+    constants::*,
+    id::{GuildId, UserId},
+    model::{
+        payload::{Hello, Ready, SessionDescription},
+        Event,
+    },
+    Config,
+    ConnectionInfo,
+};
+use async_tungstenite::tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use audiopus::{coder::Decoder as OpusDecoder, packet::Packet as OpusPacket, Channels};
+use discortp::{
+    demux::{self, DemuxedMut},
+    discord::{IpDiscoveryPacket, IpDiscoveryType, MutableIpDiscoveryPacket},
+    rtp::{MutableRtpPacket, RtpPacket},
+    MutablePacket,
+    Packet,
+};
+use flume::{Receiver, Sender};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    error::Error as StdError,
+    fmt,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    net::{IpAddr, Ipv4Addr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+use tokio::{select, spawn};
+use tracing::{debug, trace};
+
+/// Endpoint given by [`MockServer::connection_info`].
+///
+/// Websockets opened to any other endpoint are closed without a handshake, as
+/// though the voice server were unreachable.
+pub const MOCK_ENDPOINT: &str = "voice.mock";
+
+/// Heartbeat interval (ms) sent by the mock voice gateway.
+const HEARTBEAT_INTERVAL: f64 = 41_250.0;
+
+/// Address advertised as the voice server's RTP endpoint.
+const SERVER_ADDR: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::LOCALHOST), 50_000);
+
+/// Address reported to the driver during IP discovery.
+const CLIENT_ADDR: (&str, u16) = ("127.0.0.1", 50_001);
+
+/// SSRC assigned to the first session; later sessions count upwards.
+const FIRST_SSRC: u32 = 1;
+
+const ALL_MODES: [CryptoMode; 5] = [
+    CryptoMode::Normal,
+    CryptoMode::Suffix,
+    CryptoMode::Lite,
+    CryptoMode::Aes256Gcm,
+    CryptoMode::XChaCha20Poly1305,
+];
+
+/// A local voice server, reachable by drivers using its [`transport`].
+///
+/// Connections are handled on the tokio runtime used to create the server,
+/// until the server is dropped.
+///
+/// [`transport`]: MockServer::transport
+#[derive(Debug)]
+pub struct MockServer {
+    transport: MemoryTransport,
+    sessions: Receiver<MockSession>,
+    _shutdown: Sender<()>,
+}
+
+impl MockServer {
+    /// Starts a new voice server on the current tokio runtime.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn new() -> Self {
+        let (transport, listener) = MemoryTransport::new();
+        let (session_tx, session_rx) = flume::unbounded();
+        let (shutdown_tx, shutdown_rx) = flume::bounded(0);
+
+        let server = Server {
+            listener: Arc::new(listener),
+            sessions: session_tx,
+            resumes: Default::default(),
+            next_ssrc: Arc::new(AtomicU32::new(FIRST_SSRC)),
+            shutdown: shutdown_rx,
+        };
+
+        spawn(server.run());
+
+        Self {
+            transport,
+            sessions: session_rx,
+            _shutdown: shutdown_tx,
+        }
+    }
+
+    /// Returns a transport which connects to this server.
+    pub fn transport(&self) -> MemoryTransport {
+        self.transport.clone()
+    }
+
+    /// Returns a default driver configuration which connects to this server.
+    pub fn config(&self) -> Config {
+        Config::default().transport(self.transport())
+    }
+
+    /// Returns connection details which this server accepts for a given guild and bot user.
+    pub fn connection_info(
+        guild_id: impl Into<GuildId>,
+        user_id: impl Into<UserId>,
+    ) -> ConnectionInfo {
+        ConnectionInfo {
+            channel_id: None,
+            endpoint: MOCK_ENDPOINT.into(),
+            guild_id: guild_id.into(),
+            session_id: "mock-session".into(),
+            token: "mock-token".into(),
+            user_id: user_id.into(),
+        }
+    }
+
+    /// Waits for the next driver to complete its connection handshake.
+    ///
+    /// Resumed sessions are not returned again.
+    pub async fn next_session(&self) -> Option<MockSession> {
+        self.sessions.recv_async().await.ok()
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Server {
+    listener: Arc<MemoryListener>,
+    sessions: Sender<MockSession>,
+    resumes: Arc<Mutex<HashMap<String, Sender<Resumption>>>>,
+    next_ssrc: Arc<AtomicU32>,
+    shutdown: Receiver<()>,
+}
+
+impl Server {
+    async fn run(self) {
+        trace!("Mock voice server started.");
+
+        loop {
+            select! {
+                conn = self.listener.accept_ws() => match conn {
+                    Some((url, ws)) if url.host_str() == Some(MOCK_ENDPOINT) => self.accept(ws),
+                    Some((url, _ws)) => debug!("Mock server refused connection to {}.", url),
+                    None => break,
+                },
+                _ = self.shutdown.recv_async() => break,
+            }
+        }
+
+        trace!("Mock voice server stopped.");
+    }
+
+    fn accept(&self, ws: WsStream) {
+        let listener = self.listener.clone();
+        let sessions = self.sessions.clone();
+        let resumes = self.resumes.clone();
+        let ssrc = self.next_ssrc.fetch_add(1, Ordering::Relaxed);
+        let shutdown = self.shutdown.clone();
+
+        spawn(async move {
+            if let Err(e) = handshake(ws, listener, sessions, resumes, ssrc, shutdown).await {
+                debug!("Mock voice handshake failed: {:?}", e);
+            }
+        });
+    }
+}
+
+/// A websocket resuming a session, and the last sequence number acknowledged by its driver.
+type Resumption = (WsStream, Option<u64>);
+
+async fn handshake(
+    mut ws: WsStream,
+    listener: Arc<MemoryListener>,
+    sessions: Sender<MockSession>,
+    resumes: Arc<Mutex<HashMap<String, Sender<Resumption>>>>,
+    ssrc: u32,
+    shutdown: Receiver<()>,
+) -> IoResult<()> {
+    send_event(
+        &mut ws,
+        &Event::from(Hello {
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+        }),
+    )
+    .await?;
+
+    let session_id = loop {
+        let text = recv_text(&mut ws).await?;
+
+        match serde_json::from_str(&text)? {
+            Event::Identify(identify) => break identify.session_id,
+            Event::Resume(resume) => {
+                let target = resumes.lock().get(&resume.session_id).cloned();
+                let seq_ack = serde_json::from_str::<Value>(&text)?["d"]["seq_ack"].as_u64();
+
+                // Missed messages are replayed by the session, followed by `Resumed`.
+                return match target {
+                    Some(target) => target.send((ws, seq_ack)).map_err(|_| closed()),
+                    None => Err(IoError::new(IoErrorKind::NotFound, "unknown session")),
+                };
+            },
+            other => debug!("Mock server expected identify, got {:?}", other),
+        }
+    };
+
+    let mut seq = 0;
+
+    send_sequenced(
+        &mut ws,
+        &mut seq,
+        &Event::from(Ready {
+            ip: SERVER_ADDR.0,
+            modes: ALL_MODES
+                .iter()
+                .map(|m| m.to_request_str().into())
+                .collect(),
+            port: SERVER_ADDR.1,
+            ssrc,
+        }),
+    )
+    .await?;
+
+    let (_addr, udp) = listener.accept_udp().await.ok_or_else(closed)?;
+    ip_discovery(&udp).await?;
+
+    let mode = loop {
+        match recv_event(&mut ws).await? {
+            Event::SelectProtocol(select) => {
+                break ALL_MODES
+                    .iter()
+                    .copied()
+                    .find(|m| m.to_request_str() == select.data.mode)
+                    .ok_or_else(|| IoError::new(IoErrorKind::InvalidData, "unknown mode"))?;
+            },
+            other => debug!("Mock server expected select protocol, got {:?}", other),
+        }
+    };
+
+    let key: [u8; 32] = rand::random();
+    send_sequenced(
+        &mut ws,
+        &mut seq,
+        &Event::from(SessionDescription {
+            mode: mode.to_request_str().into(),
+            secret_key: key.to_vec(),
+        }),
+    )
+    .await?;
+
+    let cipher = Cipher::new(mode, &key).map_err(other_error)?;
+    let udp = Arc::new(udp);
+    let (cmd_tx, cmd_rx) = flume::unbounded();
+    let (event_tx, event_rx) = flume::unbounded();
+    let (packet_tx, packet_rx) = flume::unbounded();
+    let (resume_tx, resume_rx) = flume::unbounded();
+    let seq_ack = Arc::new(Mutex::new(None));
+
+    resumes.lock().insert(session_id.clone(), resume_tx);
+
+    let session = MockSession {
+        inner: Arc::new(SessionInner {
+            session_id,
+            ssrc,
+            mode,
+            cipher: cipher.clone(),
+            crypto_state: Mutex::new(mode.into()),
+            udp: udp.clone(),
+            commands: cmd_tx,
+            events: event_rx,
+            packets: packet_rx,
+            decoder: Mutex::new(
+                OpusDecoder::new(SAMPLE_RATE, Channels::Stereo).map_err(other_error)?,
+            ),
+            seq_ack: seq_ack.clone(),
+        }),
+    };
+
+    spawn(run_udp(udp, mode, cipher, packet_tx, shutdown.clone()));
+    let _ = sessions.send(session);
+
+    run_ws(WsState {
+        ws,
+        seq,
+        seq_ack,
+        commands: cmd_rx,
+        events: event_tx,
+        resumes: resume_rx,
+        shutdown,
+    })
+    .await;
+
+    Ok(())
+}
+
+/// Answers the driver's IP discovery request on a new UDP socket.
+async fn ip_discovery(udp: &MemoryUdpSocket) -> IoResult<()> {
+    let mut bytes = [0u8; IpDiscoveryPacket::const_packet_size()];
+
+    loop {
+        let len = udp.recv(&mut bytes).await?;
+
+        let ssrc = match IpDiscoveryPacket::new(&bytes[..len]) {
+            Some(view) if view.get_pkt_type() == IpDiscoveryType::Request => view.get_ssrc(),
+            _ => continue,
+        };
+
+        let mut address = [0u8; 64];
+        address[..CLIENT_ADDR.0.len()].copy_from_slice(CLIENT_ADDR.0.as_bytes());
+
+        let mut view = MutableIpDiscoveryPacket::new(&mut bytes[..])
+            .expect("IP discovery buffer is correctly sized.");
+        view.set_pkt_type(IpDiscoveryType::Response);
+        view.set_length(70);
+        view.set_ssrc(ssrc);
+        view.set_address(&address);
+        view.set_port(CLIENT_ADDR.1);
+
+        udp.send(&bytes)?;
+
+        return Ok(());
+    }
+}
+
+enum Command {
+    Send(Value),
+    Close(u16),
+}
+
+struct WsState {
+    ws: WsStream,
+    /// Sequence number of the last message sent to the driver.
+    seq: u64,
+    /// Sequence number last acknowledged by the driver.
+    seq_ack: Arc<Mutex<Option<u64>>>,
+    commands: Receiver<Command>,
+    events: Sender<Event>,
+    resumes: Receiver<Resumption>,
+    shutdown: Receiver<()>,
+}
+
+/// Relays gateway traffic for one session until the server shuts down,
+/// moving onto any websocket which resumes the session.
+///
+/// Messages sent while the driver is disconnected are kept, and replayed on resume.
+async fn run_ws(state: WsState) {
+    let WsState {
+        mut ws,
+        mut seq,
+        seq_ack,
+        commands,
+        events,
+        resumes,
+        shutdown,
+    } = state;
+
+    let mut sent = vec![];
+    let mut live = true;
+    let mut commands_open = true;
+
+    loop {
+        select! {
+            msg = ws.next(), if live => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some((ack, acked)) = heartbeat_ack(&text) {
+                        *seq_ack.lock() = acked;
+                        live = ws.send(Message::Text(ack.to_string())).await.is_ok();
+                    } else {
+                        match serde_json::from_str::<Event>(&text) {
+                            Ok(event) => {
+                                let _ = events.send(event);
+                            },
+                            Err(e) => debug!("Mock server received invalid event: {:?}", e),
+                        }
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(_)) | None => live = false,
+            },
+            cmd = commands.recv_async(), if commands_open => match cmd {
+                Ok(Command::Send(mut value)) => {
+                    seq += 1;
+                    value["seq"] = seq.into();
+                    let text = value.to_string();
+                    sent.push((seq, text.clone()));
+
+                    if live {
+                        live = ws.send(Message::Text(text)).await.is_ok();
+                    }
+                },
+                Ok(Command::Close(code)) => {
+                    let frame = CloseFrame {
+                        code: CloseCode::from(code),
+                        reason: "".into(),
+                    };
+                    let _ = ws.send(Message::Close(Some(frame))).await;
+                    live = false;
+                },
+                Err(_) => commands_open = false,
+            },
+            resumption = resumes.recv_async() => match resumption {
+                Ok((new_ws, acked)) => {
+                    ws = new_ws;
+                    *seq_ack.lock() = acked;
+                    live = true;
+
+                    for (_, text) in sent.iter().filter(|(s, _)| Some(*s) > acked) {
+                        live &= ws.send(Message::Text(text.clone())).await.is_ok();
+                    }
+
+                    live &= send_event(&mut ws, &Event::Resumed).await.is_ok();
+                },
+                Err(_) => break,
+            },
+            _ = shutdown.recv_async() => break,
+        }
+    }
+}
+
+/// Builds the reply to a heartbeat, if `text` contains one, along with the
+/// sequence number it acknowledges.
+fn heartbeat_ack(text: &str) -> Option<(Value, Option<u64>)> {
+    let value: Value = serde_json::from_str(text).ok()?;
+
+    if value.get("op")?.as_u64()? != 3 {
+        return None;
+    }
+
+    // v8 heartbeats nest their nonce alongside a sequence acknowledgement.
+    let (nonce, seq_ack) = match &value["d"] {
+        Value::Object(d) => (
+            d.get("t")?.clone(),
+            d.get("seq_ack").and_then(Value::as_u64),
+        ),
+        other => (other.clone(), None),
+    };
+
+    Some((json!({"op": 6, "d": {"t": nonce}}), seq_ack))
+}
+
+/// Decrypts all voice packets received from the driver.
+async fn run_udp(
+    udp: Arc<MemoryUdpSocket>,
+    mode: CryptoMode,
+    cipher: Cipher,
+    packets: Sender<MockPacket>,
+    shutdown: Receiver<()>,
+) {
+    let mut buf = [0u8; VOICE_PACKET_MAX];
+
+    loop {
+        let len = select! {
+            len = udp.recv(&mut buf) => match len {
+                Ok(len) => len,
+                Err(_) => break,
+            },
+            _ = shutdown.recv_async() => break,
+        };
+
+        if let DemuxedMut::Rtp(mut rtp) = demux::demux_mut(&mut buf[..len]) {
+            let (start, tail) = match mode.decrypt_rtp_in_place(&mut rtp, &cipher) {
+                Ok(bounds) => bounds,
+                Err(_) => {
+                    debug!("Mock server failed to decrypt voice packet.");
+                    continue;
+                },
+            };
+
+            let payload = rtp.payload();
+            let packet = MockPacket {
+                ssrc: rtp.get_ssrc(),
+                sequence: rtp.get_sequence().into(),
+                timestamp: rtp.get_timestamp().into(),
+                payload: payload[start..payload.len() - tail].to_vec(),
+            };
+
+            if packets.send(packet).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+async fn send_event(ws: &mut WsStream, event: &Event) -> IoResult<()> {
+    let text = serde_json::to_string(event)?;
+
+    ws.send(Message::Text(text)).await.map_err(other_error)
+}
+
+/// Sends a gateway event which the driver must acknowledge, numbering it after `seq`.
+async fn send_sequenced(ws: &mut WsStream, seq: &mut u64, event: &Event) -> IoResult<()> {
+    *seq += 1;
+
+    let mut value = serde_json::to_value(event)?;
+    value["seq"] = (*seq).into();
+
+    ws.send(Message::Text(value.to_string()))
+        .await
+        .map_err(other_error)
+}
+
+async fn recv_event(ws: &mut WsStream) -> IoResult<Event> {
+    Ok(serde_json::from_str(&recv_text(ws).await?)?)
+}
+
+async fn recv_text(ws: &mut WsStream) -> IoResult<String> {
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => return Ok(text),
+            Some(Ok(_)) => {},
+            Some(Err(e)) => return Err(other_error(e)),
+            None => return Err(closed()),
+        }
+    }
+}
+
+fn other_error(e: impl Into<Box<dyn StdError + Send + Sync>>) -> IoError {
+    IoError::new(IoErrorKind::Other, e)
+}
+
+fn closed() -> IoError {
+    IoError::from(IoErrorKind::ConnectionAborted)
+}
+
+/// A decrypted voice packet, sent by a driver to a [`MockServer`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct MockPacket {
+    /// RTP synchronisation source of the sender.
+    pub ssrc: u32,
+    /// RTP sequence number.
+    pub sequence: u16,
+    /// RTP timestamp, in samples.
+    pub timestamp: u32,
+    /// Opus-encoded audio.
+    pub payload: Vec<u8>,
+}
+
+/// A driver's voice session on a [`MockServer`].
+///
+/// Clones of a `MockSession` refer to the same session.
+#[derive(Clone, Debug)]
+pub struct MockSession {
+    inner: Arc<SessionInner>,
+}
+
+struct SessionInner {
+    session_id: String,
+    ssrc: u32,
+    mode: CryptoMode,
+    cipher: Cipher,
+    crypto_state: Mutex<CryptoState>,
+    udp: Arc<MemoryUdpSocket>,
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+    packets: Receiver<MockPacket>,
+    decoder: Mutex<OpusDecoder>,
+    seq_ack: Arc<Mutex<Option<u64>>>,
+}
+
+impl fmt::Debug for SessionInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionInner")
+            .field("session_id", &self.session_id)
+            .field("ssrc", &self.ssrc)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+impl MockSession {
+    /// Returns the ID of this session, as given by the driver.
+    pub fn session_id(&self) -> &str {
+        &self.inner.session_id
+    }
+
+    /// Returns the SSRC assigned to the driver.
+    pub fn ssrc(&self) -> u32 {
+        self.inner.ssrc
+    }
+
+    /// Returns the encryption scheme chosen by the driver.
+    pub fn crypto_mode(&self) -> CryptoMode {
+        self.inner.mode
+    }
+
+    /// Returns the sequence number most recently acknowledged by the driver, in a
+    /// heartbeat or when resuming this session.
+    pub fn seq_ack(&self) -> Option<u64> {
+        *self.inner.seq_ack.lock()
+    }
+
+    /// Waits for the next gateway event sent by the driver, such as [`Speaking`].
+    ///
+    /// Heartbeats are answered by the server, and are not returned.
+    ///
+    /// [`Speaking`]: crate::model::payload::Speaking
+    pub async fn recv_event(&self) -> Option<Event> {
+        self.inner.events.recv_async().await.ok()
+    }
+
+    /// Waits for the next voice packet sent by the driver.
+    pub async fn recv_packet(&self) -> Option<MockPacket> {
+        self.inner.packets.recv_async().await.ok()
+    }
+
+    /// Waits for the next voice packet sent by the driver, returning its audio
+    /// as interleaved stereo samples.
+    ///
+    /// Packets which cannot be decoded are skipped.
+    pub async fn recv_audio(&self) -> Option<Vec<f32>> {
+        loop {
+            let packet = self.recv_packet().await?;
+            // Opus packets hold at most 120ms of audio.
+            let mut audio = vec![0.0; 6 * STEREO_FRAME_SIZE];
+
+            let decoded = OpusPacket::try_from(&packet.payload[..])
+                .ok()
+                .and_then(|opus| {
+                    self.inner
+                        .decoder
+                        .lock()
+                        .decode_float(Some(opus), (&mut audio[..]).try_into().ok()?, false)
+                        .ok()
+                });
+
+            if let Some(len) = decoded {
+                audio.truncate(2 * len);
+                return Some(audio);
+            }
+        }
+    }
+
+    /// Sends a gateway event to the driver, such as a [`Speaking`] update for a fake user.
+    ///
+    /// [`Speaking`]: crate::model::payload::Speaking
+    pub fn send_event(&self, event: impl Into<Event>) -> IoResult<()> {
+        let value = serde_json::to_value(event.into())?;
+
+        self.inner
+            .commands
+            .send(Command::Send(value))
+            .map_err(|_| closed())
+    }
+
+    /// Sends an encrypted voice packet containing the Opus frame `opus` to the driver,
+    /// as though it came from the user with the given `ssrc`.
+    pub fn send_voice(
+        &self,
+        ssrc: u32,
+        sequence: u16,
+        timestamp: u32,
+        opus: &[u8],
+    ) -> IoResult<()> {
+        let mode = self.inner.mode;
+        let mut buf =
+            vec![0u8; RtpPacket::minimum_packet_size() + mode.payload_overhead() + opus.len()];
+
+        let len = {
+            let mut rtp =
+                MutableRtpPacket::new(&mut buf[..]).expect("Buffer is sized for an RTP header.");
+            rtp.set_version(RTP_VERSION);
+            rtp.set_payload_type(RTP_PROFILE_TYPE);
+            rtp.set_sequence(sequence.into());
+            rtp.set_timestamp(timestamp.into());
+            rtp.set_ssrc(ssrc);
+
+            let start = mode.payload_prefix_len();
+            rtp.payload_mut()[start..start + opus.len()].copy_from_slice(opus);
+
+            let payload_len = self
+                .inner
+                .crypto_state
+                .lock()
+                .write_packet_nonce(&mut rtp, start + opus.len());
+
+            mode.encrypt_in_place(&mut rtp, &self.inner.cipher, payload_len)
+                .map_err(|_| IoError::new(IoErrorKind::InvalidData, "encryption failed"))?;
+
+            RtpPacket::minimum_packet_size() + payload_len
+        };
+
+        self.inner.udp.send(&buf[..len]).map(|_| ())
+    }
+
+    /// Closes the driver's websocket with the given close code.
+    ///
+    /// If the code allows it, the driver then resumes this session, and is sent
+    /// any events which it missed.
+    pub fn close(&self, code: u16) -> IoResult<()> {
+        self.inner
+            .commands
+            .send(Command::Close(code))
+            .map_err(|_| closed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        driver::{
+            retry::{Retry, Strategy},
+            Driver,
+            EncoderConfig,
+            FrameLength,
+        },
+        events::{CoreEvent, Event as DriverEvent, EventContext, EventHandler},
+        input::Input,
+        model::{payload::Speaking, SpeakingState},
+    };
+    use async_trait::async_trait;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /// Half a second of a full-scale square wave.
+    fn square_wave() -> Input {
+        let samples: Vec<u8> = (0..STEREO_FRAME_SIZE * 25)
+            .flat_map(|i| if (i / 96) % 2 == 0 { 0.5f32 } else { -0.5 }.to_le_bytes())
+            .collect();
+
+        Input::float_pcm(true, samples.into())
+    }
+
+    struct PacketForwarder(Sender<u32>);
+
+    #[async_trait]
+    impl EventHandler for PacketForwarder {
+        async fn act(&self, ctx: &EventContext<'_>) -> Option<DriverEvent> {
+            if let EventContext::VoicePacket(data) = ctx {
+                let _ = self.0.send(data.packet.ssrc);
+            }
+
+            None
+        }
+    }
+
+    struct SpeakingForwarder(Sender<u32>);
+
+    #[async_trait]
+    impl EventHandler for SpeakingForwarder {
+        async fn act(&self, ctx: &EventContext<'_>) -> Option<DriverEvent> {
+            if let EventContext::SpeakingStateUpdate(speaking) = ctx {
+                let _ = self.0.send(speaking.ssrc);
+            }
+
+            None
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn driver_plays_and_receives_through_mock() {
+        let server = MockServer::new();
+        let mut driver = Driver::new(server.config());
+        driver
+            .connect(MockServer::connection_info(1, 2))
+            .await
+            .unwrap();
+
+        let session = timeout(WAIT, server.next_session()).await.unwrap().unwrap();
+        assert_eq!(session.ssrc(), FIRST_SSRC);
+        assert_eq!(session.crypto_mode(), CryptoMode::Aes256Gcm);
+
+        driver.play_source(square_wave());
+
+        let speaking = timeout(WAIT, session.recv_event()).await.unwrap().unwrap();
+        assert!(matches!(speaking, Event::Speaking(s) if s.ssrc == FIRST_SSRC));
+
+        let audio = timeout(WAIT, session.recv_audio()).await.unwrap().unwrap();
+        assert_eq!(audio.len(), STEREO_FRAME_SIZE);
+        assert!(audio.iter().any(|s| s.abs() > 0.1));
+
+        let (tx, rx) = flume::unbounded();
+        driver.add_global_event(
+            DriverEvent::Core(CoreEvent::VoicePacket),
+            PacketForwarder(tx),
+        );
+
+        session
+            .send_event(Speaking {
+                delay: None,
+                speaking: SpeakingState::MICROPHONE,
+                ssrc: 42,
+                user_id: None,
+            })
+            .unwrap();
+        session.send_voice(42, 1, 960, &SILENT_FRAME).unwrap();
+
+        assert_eq!(timeout(WAIT, rx.recv_async()).await.unwrap().unwrap(), 42);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn driver_sends_configured_frame_length() {
+        let server = MockServer::new();
+        let encoder = EncoderConfig::default()
+            .channels(Channels::Mono)
+            .frame_length(FrameLength::Ms40);
+        let mut driver = Driver::new(server.config().encoder(encoder));
+        driver
+            .connect(MockServer::connection_info(1, 2))
+            .await
+            .unwrap();
+
+        let session = timeout(WAIT, server.next_session()).await.unwrap().unwrap();
+        driver.play_source(square_wave());
+
+        let first = timeout(WAIT, session.recv_packet()).await.unwrap().unwrap();
+        let second = timeout(WAIT, session.recv_packet()).await.unwrap().unwrap();
+        assert_eq!(second.sequence.wrapping_sub(first.sequence), 1);
+        assert_eq!(
+            second.timestamp.wrapping_sub(first.timestamp),
+            2 * MONO_FRAME_SIZE as u32
+        );
+
+        let audio = timeout(WAIT, session.recv_audio()).await.unwrap().unwrap();
+        assert_eq!(audio.len(), 2 * STEREO_FRAME_SIZE);
+        assert!(audio.iter().any(|s| s.abs() > 0.1));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_migration_drops_old_connection() {
+        let server = MockServer::new();
+        let retry = Retry {
+            retry_limit: Some(0),
+            ..Default::default()
+        };
+        let mut driver = Driver::new(server.config().driver_retry(retry));
+        driver
+            .connect(MockServer::connection_info(1, 2))
+            .await
+            .unwrap();
+
+        let session = timeout(WAIT, server.next_session()).await.unwrap().unwrap();
+        driver.play_source(square_wave());
+        timeout(WAIT, session.recv_packet()).await.unwrap().unwrap();
+
+        let unreachable = ConnectionInfo {
+            endpoint: "unreachable.mock".into(),
+            ..MockServer::connection_info(1, 2)
+        };
+        assert!(driver.connect(unreachable).await.is_err());
+
+        // The old session only stops receiving once its UDP socket is dropped.
+        timeout(WAIT, async {
+            while session.recv_packet().await.is_some() {}
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retried_migration_keeps_old_connection() {
+        let server = MockServer::new();
+        let retry = Retry {
+            strategy: Strategy::Every(Duration::from_secs(2)),
+            retry_limit: Some(1),
+        };
+        let mut driver = Driver::new(server.config().driver_retry(retry));
+        driver
+            .connect(MockServer::connection_info(1, 2))
+            .await
+            .unwrap();
+
+        let session = timeout(WAIT, server.next_session()).await.unwrap().unwrap();
+        driver.play_source(square_wave()).enable_loop().unwrap();
+        timeout(WAIT, session.recv_packet()).await.unwrap().unwrap();
+
+        let unreachable = ConnectionInfo {
+            endpoint: "unreachable.mock".into(),
+            ..MockServer::connection_info(1, 2)
+        };
+        let migration = driver.connect(unreachable);
+
+        // A second of audio spans the first failure, while the retry is pending.
+        for _ in 0..50 {
+            timeout(WAIT, session.recv_packet()).await.unwrap().unwrap();
+        }
+
+        assert!(migration.await.is_err());
+
+        timeout(WAIT, async {
+            while session.recv_packet().await.is_some() {}
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn resumed_sessions_replay_missed_events() {
+        let server = MockServer::new();
+        let mut driver = Driver::new(server.config());
+        driver
+            .connect(MockServer::connection_info(1, 2))
+            .await
+            .unwrap();
+
+        let session = timeout(WAIT, server.next_session()).await.unwrap().unwrap();
+
+        let (tx, rx) = flume::unbounded();
+        driver.add_global_event(
+            DriverEvent::Core(CoreEvent::SpeakingStateUpdate),
+            SpeakingForwarder(tx),
+        );
+
+        // Sent while the driver is disconnected, so it only arrives by replay.
+        session.close(4015).unwrap();
+        session
+            .send_event(Speaking {
+                delay: None,
+                speaking: SpeakingState::MICROPHONE,
+                ssrc: 42,
+                user_id: None,
+            })
+            .unwrap();
+
+        assert_eq!(timeout(WAIT, rx.recv_async()).await.unwrap().unwrap(), 42);
+
+        // Ready and SessionDescription are acknowledged, though no events followed.
+        assert_eq!(session.seq_ack(), Some(2));
+    }
+}
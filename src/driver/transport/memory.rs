@@ -0,0 +1,235 @@
+use super::{Transport, UdpTransport, WsStream};
+use crate::error::{ConnectionError, ConnectionResult};
+use async_trait::async_trait;
+use async_tungstenite::tokio::{accept_async, client_async};
+use flume::{Receiver, Sender};
+use futures::future::poll_fn;
+use parking_lot::Mutex;
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    net::SocketAddr,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{duplex, DuplexStream},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use tracing::debug;
+use url::Url;
+
+/// Size of the in-memory buffer carrying each websocket connection.
+const WS_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A transport which never touches the network, handing every connection
+/// to a [`MemoryListener`] in the same process.
+///
+/// This allows a driver to be tested end-to-end against a local voice server.
+/// Clones of a `MemoryTransport` connect to the same listener.
+///
+/// # Example
+/// ```rust,no_run
+/// use songbird::{driver::transport::MemoryTransport, Config};
+///
+/// # async {
+/// let (transport, listener) = MemoryTransport::new();
+/// let config = Config::default().transport(transport);
+///
+/// // ... connect a driver using `config`, then:
+/// let (url, ws) = listener.accept_ws().await.unwrap();
+/// # };
+/// ```
+#[derive(Clone, Debug)]
+pub struct MemoryTransport {
+    ws: Sender<(Url, DuplexStream)>,
+    udp: Sender<(SocketAddr, MemoryUdpSocket)>,
+}
+
+impl MemoryTransport {
+    /// Creates a new transport, along with the listener receiving its connections.
+    pub fn new() -> (Self, MemoryListener) {
+        let (ws_tx, ws_rx) = flume::unbounded();
+        let (udp_tx, udp_rx) = flume::unbounded();
+
+        (
+            Self {
+                ws: ws_tx,
+                udp: udp_tx,
+            },
+            MemoryListener {
+                ws: ws_rx,
+                udp: udp_rx,
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn connect_ws(&self, url: Url) -> ConnectionResult<WsStream> {
+        let (client, server) = duplex(WS_BUFFER_SIZE);
+
+        self.ws
+            .send((url.clone(), server))
+            .map_err(|_| listener_closed())?;
+
+        let (stream, _) = client_async(url, client)
+            .await
+            .map_err(crate::ws::Error::from)?;
+
+        Ok(Box::new(stream))
+    }
+
+    async fn connect_udp(&self, addr: SocketAddr) -> ConnectionResult<Box<dyn UdpTransport>> {
+        let (client, server) = MemoryUdpSocket::pair();
+
+        self.udp
+            .send((addr, server))
+            .map_err(|_| listener_closed())?;
+
+        Ok(Box::new(client))
+    }
+}
+
+fn listener_closed() -> ConnectionError {
+    IoError::new(
+        IoErrorKind::ConnectionRefused,
+        "memory transport listener was dropped",
+    )
+    .into()
+}
+
+/// Receives the websocket and UDP connections opened via a [`MemoryTransport`].
+#[derive(Debug)]
+pub struct MemoryListener {
+    ws: Receiver<(Url, DuplexStream)>,
+    udp: Receiver<(SocketAddr, MemoryUdpSocket)>,
+}
+
+impl MemoryListener {
+    /// Waits for the next websocket connection, completing the server side of
+    /// its handshake.
+    ///
+    /// Returns `None` once all handles to the transport have been dropped.
+    pub async fn accept_ws(&self) -> Option<(Url, WsStream)> {
+        loop {
+            let (url, stream) = self.ws.recv_async().await.ok()?;
+
+            match accept_async(stream).await {
+                Ok(ws) => return Some((url, Box::new(ws))),
+                Err(e) => debug!("Memory websocket handshake failed: {:?}", e),
+            }
+        }
+    }
+
+    /// Waits for the next UDP socket to be opened, returning the address it
+    /// was opened to and the server's end of the socket.
+    ///
+    /// Returns `None` once all handles to the transport have been dropped.
+    pub async fn accept_udp(&self) -> Option<(SocketAddr, MemoryUdpSocket)> {
+        self.udp.recv_async().await.ok()
+    }
+}
+
+/// One end of an in-memory datagram socket, created by a [`MemoryTransport`].
+///
+/// Datagrams are never lost or reordered, and sends never block.
+#[derive(Debug)]
+pub struct MemoryUdpSocket {
+    tx: UnboundedSender<Vec<u8>>,
+    rx: Mutex<UnboundedReceiver<Vec<u8>>>,
+}
+
+impl MemoryUdpSocket {
+    /// Creates two connected sockets, each receiving the datagrams sent by the other.
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+
+        (
+            Self {
+                tx: a_tx,
+                rx: Mutex::new(b_rx),
+            },
+            Self {
+                tx: b_tx,
+                rx: Mutex::new(a_rx),
+            },
+        )
+    }
+
+    /// Receives a single datagram into `buf`, returning its length.
+    pub async fn recv(&self, buf: &mut [u8]) -> IoResult<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    /// Sends the datagram `buf` to the other end of this socket.
+    pub fn send(&self, buf: &[u8]) -> IoResult<usize> {
+        self.try_send(buf)
+    }
+}
+
+impl UdpTransport for MemoryUdpSocket {
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IoResult<usize>> {
+        self.rx.lock().poll_recv(cx).map(|packet| {
+            let packet = packet.ok_or_else(|| IoError::from(IoErrorKind::ConnectionReset))?;
+            let len = packet.len().min(buf.len());
+            buf[..len].copy_from_slice(&packet[..len]);
+
+            Ok(len)
+        })
+    }
+
+    fn poll_send(&self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Poll::Ready(self.try_send(buf))
+    }
+
+    fn try_send(&self, buf: &[u8]) -> IoResult<usize> {
+        self.tx
+            .send(buf.to_vec())
+            .map(|_| buf.len())
+            .map_err(|_| IoErrorKind::ConnectionReset.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_tungstenite::tungstenite::Message;
+    use futures::{SinkExt, StreamExt};
+
+    #[tokio::test]
+    async fn connections_reach_listener() {
+        let (transport, listener) = MemoryTransport::new();
+        let url = Url::parse("wss://voice.example/?v=8").unwrap();
+
+        let server = tokio::spawn(async move {
+            let (url, mut ws) = listener.accept_ws().await.unwrap();
+            let msg = ws.next().await.unwrap().unwrap();
+            ws.send(msg).await.unwrap();
+
+            let (addr, udp) = listener.accept_udp().await.unwrap();
+            let mut buf = [0u8; 16];
+            let len = udp.recv(&mut buf).await.unwrap();
+            udp.send(&buf[..len]).unwrap();
+
+            (url, addr)
+        });
+
+        let mut ws = transport.connect_ws(url.clone()).await.unwrap();
+        ws.send(Message::Text("hello".into())).await.unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            Message::Text("hello".into())
+        );
+
+        let addr = "127.0.0.1:50000".parse().unwrap();
+        let udp: Box<dyn UdpTransport> = transport.connect_udp(addr).await.unwrap();
+        udp.try_send(&[1, 2, 3]).unwrap();
+
+        let mut buf = [0u8; 16];
+        assert_eq!(udp.recv(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+
+        assert_eq!(server.await.unwrap(), (url, addr));
+    }
+}
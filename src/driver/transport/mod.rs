@@ -0,0 +1,149 @@
+//! Network transports used by the driver to reach Discord's voice servers.
+//!
+//! Each voice connection is made up of two legs: a websocket to the voice
+//! gateway, and a UDP socket carrying RTP/RTCP traffic. By default, both are
+//! opened directly by tokio ([`TokioTransport`]). Custom [`Transport`]s allow this
+//! traffic to be routed elsewhere, such as through a proxy, or to a local test
+//! server via [`MemoryTransport`].
+
+mod memory;
+
+pub use self::memory::{MemoryListener, MemoryTransport, MemoryUdpSocket};
+pub use crate::ws::{WsConnection, WsStream};
+
+use crate::error::ConnectionResult;
+use async_trait::async_trait;
+use futures::{future::poll_fn, ready};
+use std::{
+    fmt::Debug,
+    io::Result as IoResult,
+    net::{SocketAddr, UdpSocket as StdUdpSocket},
+    task::{Context, Poll},
+};
+use tokio::{io::ReadBuf, net::UdpSocket};
+use url::Url;
+
+#[cfg(all(feature = "rustls-marker", not(feature = "native-marker")))]
+use crate::ws::create_rustls_client;
+
+#[cfg(feature = "native-marker")]
+use crate::ws::create_native_tls_client;
+
+/// Opens the websocket and UDP legs of a voice connection.
+///
+/// Transports are set via [`Config::transport`], and are used for every
+/// connection (or reconnection) made by a driver.
+///
+/// [`Config::transport`]: crate::Config::transport
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    /// Opens a websocket connection to the voice gateway at `url`, completing
+    /// the websocket handshake.
+    async fn connect_ws(&self, url: Url) -> ConnectionResult<WsStream>;
+
+    /// Opens a UDP socket whose packets are sent to, and received from, `addr`.
+    async fn connect_udp(&self, addr: SocketAddr) -> ConnectionResult<Box<dyn UdpTransport>>;
+}
+
+/// A connected datagram socket carrying voice packets.
+///
+/// Receipt and async sends follow the polling model of tokio's `UdpSocket`.
+/// [`try_send`] is called directly by the mixer, and so must never block.
+///
+/// [`try_send`]: UdpTransport::try_send
+pub trait UdpTransport: Debug + Send + Sync {
+    /// Attempts to receive a single datagram into `buf`, returning its length.
+    ///
+    /// Bytes beyond the length of `buf` are discarded.
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IoResult<usize>>;
+
+    /// Attempts to send the datagram `buf`.
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>>;
+
+    /// Sends the datagram `buf` without waiting.
+    ///
+    /// This must return an error of kind [`WouldBlock`] rather than block if
+    /// the packet cannot be sent right away.
+    ///
+    /// [`WouldBlock`]: std::io::ErrorKind::WouldBlock
+    fn try_send(&self, buf: &[u8]) -> IoResult<usize>;
+}
+
+impl dyn UdpTransport {
+    /// Receives a single datagram into `buf`, returning its length.
+    pub async fn recv(&self, buf: &mut [u8]) -> IoResult<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    /// Sends the datagram `buf`.
+    pub async fn send(&self, buf: &[u8]) -> IoResult<usize> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+}
+
+/// The default transport, connecting directly to Discord using tokio.
+///
+/// The websocket leg uses TLS from either `rustls` or `native-tls`,
+/// depending on the enabled features.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioTransport;
+
+#[async_trait]
+impl Transport for TokioTransport {
+    async fn connect_ws(&self, url: Url) -> ConnectionResult<WsStream> {
+        #[cfg(all(feature = "rustls-marker", not(feature = "native-marker")))]
+        let client = create_rustls_client(url).await?;
+
+        #[cfg(feature = "native-marker")]
+        let client = create_native_tls_client(url).await?;
+
+        Ok(client)
+    }
+
+    async fn connect_udp(&self, addr: SocketAddr) -> ConnectionResult<Box<dyn UdpTransport>> {
+        let udp = UdpSocket::bind("0.0.0.0:0").await?;
+        udp.connect(addr).await?;
+
+        Ok(Box::new(TokioUdpSocket::new(udp)?))
+    }
+}
+
+/// A UDP socket registered with tokio, used by [`TokioTransport`].
+#[derive(Debug)]
+pub struct TokioUdpSocket {
+    socket: UdpSocket,
+    // The mixer sends voice packets through its own (non-blocking) handle to this
+    // socket, avoiding a hop through the async runtime for every packet.
+    sync_socket: StdUdpSocket,
+}
+
+impl TokioUdpSocket {
+    /// Wraps a connected tokio `UdpSocket`.
+    pub fn new(socket: UdpSocket) -> IoResult<Self> {
+        let std_socket = socket.into_std()?;
+        let sync_socket = std_socket.try_clone()?;
+        let socket = UdpSocket::from_std(std_socket)?;
+
+        Ok(Self {
+            socket,
+            sync_socket,
+        })
+    }
+}
+
+impl UdpTransport for TokioUdpSocket {
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IoResult<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(self.socket.poll_recv(cx, &mut buf))?;
+
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        self.socket.poll_send(cx, buf)
+    }
+
+    fn try_send(&self, buf: &[u8]) -> IoResult<usize> {
+        self.sync_socket.send(buf)
+    }
+}
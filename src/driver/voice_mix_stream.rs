@@ -0,0 +1,185 @@
+use crate::{
+    constants::{STEREO_FRAME_SIZE, TIMESTEP_LENGTH},
+    input::{reader::MediaSource, Input, Reader},
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use flume::{Receiver, RecvTimeoutError, TryRecvError};
+use std::io::{
+    Cursor,
+    Error as IoError,
+    ErrorKind as IoErrorKind,
+    Read,
+    Result as IoResult,
+    Seek,
+    SeekFrom,
+};
+
+/// A continuous, stereo mixdown of all audio received by a [`Driver`].
+///
+/// Reads produce 48kHz interleaved stereo floating-point PCM (in little-endian
+/// byte order), matching the format expected by [`Input::float_pcm`]. This allows
+/// a call to be written to disk, or played back out through another driver
+/// via [`Input::from`].
+///
+/// Reads wait for up to 20ms for the next frame of audio, producing 20ms of silence
+/// if none arrives, so that the stream keeps pace with real time. Once converted into
+/// an [`Input`], reads never block so that this may be safely played by another
+/// driver's mixer: if the next 20ms of audio has not yet been mixed, silence is read
+/// instead. New audio is produced only while the parent driver is connected, and is
+/// configured with [`DecodeMode::Decode`]. The stream ends once its driver is dropped.
+///
+/// At most [`BUFFERED_FRAMES`] frames are held for each stream: any audio
+/// received while this buffer is full is discarded.
+///
+/// [`Driver`]: super::Driver
+/// [`Input`]: crate::input::Input
+/// [`Input::float_pcm`]: crate::input::Input::float_pcm
+/// [`Input::from`]: crate::input::Input
+/// [`DecodeMode::Decode`]: super::DecodeMode::Decode
+/// [`BUFFERED_FRAMES`]: VoiceMixStream::BUFFERED_FRAMES
+#[derive(Debug)]
+pub struct VoiceMixStream {
+    rx: Receiver<Vec<f32>>,
+    frame: Cursor<Vec<u8>>,
+    // Set when played by a mixer, which must never wait on received audio.
+    non_blocking: bool,
+}
+
+impl VoiceMixStream {
+    /// Number of 20ms frames of received audio held for each stream, waiting to be read.
+    pub const BUFFERED_FRAMES: usize = 10;
+
+    pub(crate) fn new(rx: Receiver<Vec<f32>>) -> Self {
+        Self {
+            rx,
+            frame: Default::default(),
+            non_blocking: false,
+        }
+    }
+
+    fn next_frame(&mut self) -> bool {
+        let audio = if self.non_blocking {
+            match self.rx.try_recv() {
+                Ok(audio) => audio,
+                Err(TryRecvError::Empty) => vec![0.0; STEREO_FRAME_SIZE],
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        } else {
+            match self.rx.recv_timeout(TIMESTEP_LENGTH) {
+                Ok(audio) => audio,
+                Err(RecvTimeoutError::Timeout) => vec![0.0; STEREO_FRAME_SIZE],
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(audio.len() * std::mem::size_of::<f32>());
+
+        for sample in audio {
+            // Writes to a Vec cannot fail.
+            let _ = bytes.write_f32::<LittleEndian>(sample);
+        }
+
+        self.frame = Cursor::new(bytes);
+        true
+    }
+}
+
+impl Read for VoiceMixStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let read = self.frame.read(buf)?;
+
+            if read != 0 || !self.next_frame() {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+impl Seek for VoiceMixStream {
+    fn seek(&mut self, _pos: SeekFrom) -> IoResult<u64> {
+        Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            "Seeking not supported on VoiceMixStream.",
+        ))
+    }
+}
+
+impl MediaSource for VoiceMixStream {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl From<VoiceMixStream> for Input {
+    fn from(mut stream: VoiceMixStream) -> Self {
+        stream.non_blocking = true;
+
+        Input::float_pcm(true, Reader::Extension(Box::new(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::ReadBytesExt;
+    use std::time::Instant;
+
+    #[test]
+    fn reads_span_frames_and_end_on_disconnect() {
+        let (tx, rx) = flume::bounded(VoiceMixStream::BUFFERED_FRAMES);
+        let mut stream = VoiceMixStream::new(rx);
+
+        tx.send(vec![0.5, -0.5]).unwrap();
+        tx.send(vec![1.0]).unwrap();
+        drop(tx);
+
+        assert_eq!(stream.read_f32::<LittleEndian>().unwrap(), 0.5);
+        assert_eq!(stream.read_f32::<LittleEndian>().unwrap(), -0.5);
+        assert_eq!(stream.read_f32::<LittleEndian>().unwrap(), 1.0);
+        assert_eq!(stream.read(&mut [0u8; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn reads_wait_for_audio_before_padding() {
+        let (tx, rx) = flume::bounded(VoiceMixStream::BUFFERED_FRAMES);
+        let mut stream = VoiceMixStream::new(rx);
+
+        let start = Instant::now();
+        let mut silence = vec![0f32; STEREO_FRAME_SIZE];
+        stream
+            .read_f32_into::<LittleEndian>(&mut silence[..])
+            .unwrap();
+        assert!(start.elapsed() >= TIMESTEP_LENGTH);
+        assert!(silence.iter().all(|s| *s == 0.0));
+
+        tx.send(vec![0.25]).unwrap();
+        assert_eq!(stream.read_f32::<LittleEndian>().unwrap(), 0.25);
+    }
+
+    #[test]
+    fn mixer_reads_silence_without_blocking() {
+        let (tx, rx) = flume::bounded(VoiceMixStream::BUFFERED_FRAMES);
+        let mut stream = VoiceMixStream::new(rx);
+        stream.non_blocking = true;
+
+        let start = Instant::now();
+        let mut silence = vec![0f32; STEREO_FRAME_SIZE];
+        stream
+            .read_f32_into::<LittleEndian>(&mut silence[..])
+            .unwrap();
+        assert!(start.elapsed() < TIMESTEP_LENGTH);
+        assert!(silence.iter().all(|s| *s == 0.0));
+
+        tx.send(vec![0.25]).unwrap();
+        assert_eq!(stream.read_f32::<LittleEndian>().unwrap(), 0.25);
+    }
+}
@@ -45,6 +45,11 @@ pub enum DisconnectKind {
     /// This requires explicit handling at the gateway level
     /// to either reconnect or fully disconnect.
     Reconnect,
+    /// The voice driver failed to move its session to a new voice server.
+    ///
+    /// This requires explicit handling at the gateway level
+    /// to either reconnect or fully disconnect.
+    Migrate,
     /// The voice connection was terminated mid-session by either
     /// the user or Discord.
     ///
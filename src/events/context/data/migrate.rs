@@ -0,0 +1,11 @@
+use super::ConnectData;
+
+/// Voice connection details from before and after moving to a new voice server.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct MigrateData<'a> {
+    /// The connection which was replaced.
+    pub from: ConnectData<'a>,
+    /// The newly established connection, now used for all audio.
+    pub to: ConnectData<'a>,
+}
@@ -3,10 +3,24 @@
 //! [`EventContext`]: super::EventContext
 mod connect;
 mod disconnect;
+mod migrate;
+mod overload;
 mod rtcp;
 mod speaking;
 mod voice;
+mod voice_mix;
+mod voice_tick;
 
 use discortp::{rtcp::Rtcp, rtp::Rtp};
 
-pub use self::{connect::*, disconnect::*, rtcp::*, speaking::*, voice::*};
+pub use self::{
+    connect::*,
+    disconnect::*,
+    migrate::*,
+    overload::*,
+    rtcp::*,
+    speaking::*,
+    voice::*,
+    voice_mix::*,
+    voice_tick::*,
+};
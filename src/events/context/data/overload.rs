@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+/// Timing information from a window of mixer cycles in which at least one
+/// audio frame missed its deadline.
+///
+/// Mixing and encoding costs are included in [`mean_cycle_time`] and
+/// [`max_cycle_time`], excluding any time spent waiting for the next deadline.
+/// Cycle times which approach 20ms indicate that the mixer thread is being
+/// starved of CPU time.
+///
+/// [`mean_cycle_time`]: Self::mean_cycle_time
+/// [`max_cycle_time`]: Self::max_cycle_time
+pub struct MixerOverload {
+    /// Number of frames mixed during this report window.
+    pub frames: u32,
+    /// Number of frames which finished mixing after their deadline.
+    pub late_frames: u32,
+    /// Number of frames skipped entirely, as dictated by [`CatchUpPolicy::Drop`].
+    ///
+    /// [`CatchUpPolicy::Drop`]: crate::driver::CatchUpPolicy::Drop
+    pub dropped_frames: u32,
+    /// The largest amount by which any frame missed its deadline.
+    pub max_lateness: Duration,
+    /// Average time taken to mix and encode each frame.
+    pub mean_cycle_time: Duration,
+    /// Longest time taken to mix and encode a single frame.
+    pub max_cycle_time: Duration,
+}
@@ -6,14 +6,16 @@ use super::*;
 /// `payload_offset` contains the true payload location within the raw packet's `payload()`,
 /// if extensions or raw packet data are required.
 ///
-/// Valid audio data (`Some(audio)` where `audio.len >= 0`) contains up to 20ms of 16-bit stereo PCM audio
-/// at 48kHz, using native endianness. Songbird will not send audio for silent regions, these should
-/// be inferred using [`SpeakingUpdate`]s (and filled in by the user if required using arrays of zeroes).
+/// Valid audio data (`Some(audio)`) contains all audio held in this packet (typically 20ms) as 16-bit
+/// stereo PCM audio at 48kHz, using native endianness. Songbird will not send audio for silent regions,
+/// these should be inferred using [`SpeakingUpdate`]s (and filled in by the user if required using
+/// arrays of zeroes). Decoded packets are delivered in order, after passing through a jitter buffer:
+/// [`VoiceTick`] events additionally provide concealed audio for lost packets.
 ///
-/// If `audio.len() == 0`, then this packet arrived out-of-order. If `None`, songbird was not configured
-/// to decode received packets.
+/// If `None`, songbird was not configured to decode received packets.
 ///
 /// [`SpeakingUpdate`]: crate::events::CoreEvent::SpeakingUpdate
+/// [`VoiceTick`]: crate::events::CoreEvent::VoiceTick
 pub struct VoiceData<'a> {
     /// Decoded audio from this packet.
     pub audio: &'a Option<Vec<i16>>,
@@ -0,0 +1,21 @@
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+/// A single 20ms stereo mixdown of the audio received from all users in a call.
+///
+/// Each user's decoded [`VoiceFrame`] is scaled by its receive volume (as set by
+/// [`Driver::set_receive_volume`]) and summed. Users who are silent during a tick
+/// contribute silence, so consecutive mixes form a continuous, time-aligned stream.
+///
+/// [`VoiceFrame`]: super::VoiceFrame
+/// [`Driver::set_receive_volume`]: crate::driver::Driver::set_receive_volume
+pub struct VoiceMix {
+    /// Exactly 20ms of interleaved stereo floating-point PCM audio at 48kHz.
+    ///
+    /// Samples are not clamped, and may exceed `[-1.0, 1.0]` when several
+    /// users speak at once.
+    pub audio: Vec<f32>,
+    /// The SSRCs whose audio was included in this mix.
+    pub speaking: HashSet<u32>,
+}
@@ -0,0 +1,41 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+/// Audio received from every known user in a call, released once every 20ms
+/// after passing through a per-user jitter buffer.
+///
+/// Each user's packets are reordered by RTP sequence number, and are held until
+/// [`Config::playout_buffer_length`] frames have been received. Afterwards,
+/// exactly one frame is released for each user on every tick. Lost packets are
+/// concealed using Opus's in-band FEC (where available) or packet loss concealment
+/// if [`DecodeMode::Decode`] is active.
+///
+/// [`Config::playout_buffer_length`]: crate::Config::playout_buffer_length
+/// [`DecodeMode::Decode`]: crate::driver::DecodeMode::Decode
+pub struct VoiceTick {
+    /// Audio for each SSRC who produced a frame during this tick.
+    pub speaking: HashMap<u32, VoiceFrame>,
+    /// Set of all other known SSRCs, who were silent (or buffering) during this tick.
+    pub silent: HashSet<u32>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+/// One 20ms frame of a single user's audio, played out during a [`VoiceTick`].
+pub struct VoiceFrame {
+    /// Raw RTP packet data played out during this tick.
+    ///
+    /// This is `None` if the packet for this tick was lost, or if this frame continues
+    /// a packet containing more than 20ms of audio.
+    pub packet: Option<Rtp>,
+    /// Byte index into the packet body (after headers) for where the payload begins.
+    pub payload_offset: usize,
+    /// Number of bytes at the end of the packet to discard.
+    pub payload_end_pad: usize,
+    /// Exactly 20ms of 16-bit stereo PCM audio at 48kHz, using native endianness.
+    ///
+    /// This is `Some` only if songbird is configured to decode received packets.
+    pub decoded_voice: Option<Vec<i16>>,
+}
@@ -8,6 +8,12 @@ pub struct InternalConnect {
     pub ssrc: u32,
 }
 
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InternalMigrate {
+    pub from: InternalConnect,
+    pub to: InternalConnect,
+}
+
 #[derive(Debug)]
 pub struct InternalDisconnect {
     pub kind: DisconnectKind,
@@ -48,6 +54,15 @@ impl<'a> From<&'a InternalConnect> for ConnectData<'a> {
     }
 }
 
+impl<'a> From<&'a InternalMigrate> for MigrateData<'a> {
+    fn from(val: &'a InternalMigrate) -> Self {
+        Self {
+            from: ConnectData::from(&val.from),
+            to: ConnectData::from(&val.to),
+        }
+    }
+}
+
 impl<'a> From<&'a InternalDisconnect> for DisconnectData<'a> {
     fn from(val: &'a InternalDisconnect) -> Self {
         Self {
@@ -3,6 +3,7 @@ pub(crate) mod internal_data;
 
 use super::*;
 use crate::{
+    driver::{ConnectionStats, RecordingError},
     model::payload::{ClientDisconnect, Speaking},
     tracks::{TrackHandle, TrackState},
 };
@@ -36,6 +37,10 @@ pub enum EventContext<'a> {
     SpeakingUpdate(SpeakingUpdateData),
     /// Opus audio packet, received from another stream.
     VoicePacket(VoiceData<'a>),
+    /// Reordered and loss-concealed audio from every known user, released every 20ms.
+    VoiceTick(&'a VoiceTick),
+    /// Stereo mixdown of the audio from every known user, released every 20ms.
+    VoiceMix(&'a VoiceMix),
     /// Telemetry/statistics packet, received from another stream.
     RtcpPacket(RtcpData<'a>),
     /// Fired whenever a client disconnects.
@@ -44,8 +49,19 @@ pub enum EventContext<'a> {
     DriverConnect(ConnectData<'a>),
     /// Fires when this driver successfully reconnects after a network error.
     DriverReconnect(ConnectData<'a>),
+    /// Fires when this driver resumes its websocket session after a drop,
+    /// without interrupting audio.
+    DriverResume(ConnectData<'a>),
+    /// Fires when this driver moves its session to a new voice server.
+    DriverMigrate(MigrateData<'a>),
     /// Fires when this driver fails to connect to, or drops from, a voice channel.
     DriverDisconnect(DisconnectData<'a>),
+    /// Fires when a call recording fails, ending the recording.
+    RecordingError(&'a RecordingError),
+    /// Network and performance statistics for the voice connection.
+    ConnectionStats(&'a ConnectionStats),
+    /// Timing information from the mixer, sent when frames miss their deadline.
+    MixerOverload(&'a MixerOverload),
 }
 
 #[derive(Debug)]
@@ -53,11 +69,18 @@ pub enum CoreContext {
     SpeakingStateUpdate(Speaking),
     SpeakingUpdate(InternalSpeakingUpdate),
     VoicePacket(InternalVoicePacket),
+    VoiceTick(VoiceTick),
+    VoiceMix(VoiceMix),
     RtcpPacket(InternalRtcpPacket),
     ClientDisconnect(ClientDisconnect),
     DriverConnect(InternalConnect),
     DriverReconnect(InternalConnect),
+    DriverResume(InternalConnect),
+    DriverMigrate(InternalMigrate),
     DriverDisconnect(InternalDisconnect),
+    RecordingError(RecordingError),
+    ConnectionStats(ConnectionStats),
+    MixerOverload(MixerOverload),
 }
 
 impl<'a> CoreContext {
@@ -68,11 +91,18 @@ impl<'a> CoreContext {
             SpeakingStateUpdate(evt) => EventContext::SpeakingStateUpdate(*evt),
             SpeakingUpdate(evt) => EventContext::SpeakingUpdate(SpeakingUpdateData::from(evt)),
             VoicePacket(evt) => EventContext::VoicePacket(VoiceData::from(evt)),
+            VoiceTick(evt) => EventContext::VoiceTick(evt),
+            VoiceMix(evt) => EventContext::VoiceMix(evt),
             RtcpPacket(evt) => EventContext::RtcpPacket(RtcpData::from(evt)),
             ClientDisconnect(evt) => EventContext::ClientDisconnect(*evt),
             DriverConnect(evt) => EventContext::DriverConnect(ConnectData::from(evt)),
             DriverReconnect(evt) => EventContext::DriverReconnect(ConnectData::from(evt)),
+            DriverResume(evt) => EventContext::DriverResume(ConnectData::from(evt)),
+            DriverMigrate(evt) => EventContext::DriverMigrate(MigrateData::from(evt)),
             DriverDisconnect(evt) => EventContext::DriverDisconnect(DisconnectData::from(evt)),
+            RecordingError(evt) => EventContext::RecordingError(evt),
+            ConnectionStats(evt) => EventContext::ConnectionStats(evt),
+            MixerOverload(evt) => EventContext::MixerOverload(evt),
         }
     }
 }
@@ -87,11 +117,18 @@ impl EventContext<'_> {
             SpeakingStateUpdate(_) => Some(CoreEvent::SpeakingStateUpdate),
             SpeakingUpdate(_) => Some(CoreEvent::SpeakingUpdate),
             VoicePacket(_) => Some(CoreEvent::VoicePacket),
+            VoiceTick(_) => Some(CoreEvent::VoiceTick),
+            VoiceMix(_) => Some(CoreEvent::VoiceMix),
             RtcpPacket(_) => Some(CoreEvent::RtcpPacket),
             ClientDisconnect(_) => Some(CoreEvent::ClientDisconnect),
             DriverConnect(_) => Some(CoreEvent::DriverConnect),
             DriverReconnect(_) => Some(CoreEvent::DriverReconnect),
+            DriverResume(_) => Some(CoreEvent::DriverResume),
+            DriverMigrate(_) => Some(CoreEvent::DriverMigrate),
             DriverDisconnect(_) => Some(CoreEvent::DriverDisconnect),
+            RecordingError(_) => Some(CoreEvent::RecordingError),
+            ConnectionStats(_) => Some(CoreEvent::ConnectionStats),
+            MixerOverload(_) => Some(CoreEvent::MixerOverload),
             _ => None,
         }
     }
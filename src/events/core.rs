@@ -6,8 +6,10 @@
 ///
 /// ## Events from other users
 /// Songbird can observe when a user *speaks for the first time* ([`SpeakingStateUpdate`]),
-/// when a client leaves the session ([`ClientDisconnect`]), voice packets ([`VoicePacket`]), and
-/// telemetry data ([`RtcpPacket`]). The format of voice packets is described by [`VoiceData`].
+/// when a client leaves the session ([`ClientDisconnect`]), voice packets ([`VoicePacket`]),
+/// time-aligned audio from all users ([`VoiceTick`]), a mixdown of all received audio
+/// ([`VoiceMix`]), and telemetry data ([`RtcpPacket`]).
+/// The format of voice packets is described by [`VoiceData`].
 ///
 /// To detect when a user connects, you must correlate gateway (e.g., VoiceStateUpdate) events
 /// from the main part of your bot.
@@ -19,6 +21,8 @@
 /// [`SpeakingStateUpdate`]: Self::SpeakingStateUpdate
 /// [`ClientDisconnect`]: Self::ClientDisconnect
 /// [`VoicePacket`]: Self::VoicePacket
+/// [`VoiceTick`]: Self::VoiceTick
+/// [`VoiceMix`]: Self::VoiceMix
 /// [`RtcpPacket`]: Self::RtcpPacket
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 #[non_exhaustive]
@@ -40,7 +44,25 @@ pub enum CoreEvent {
     /// As RTP packets do not map to Discord's notion of users, SSRCs must be mapped
     /// back using the user IDs seen through client connection, disconnection,
     /// or speaking state update.
+    ///
+    /// If the driver is configured to decode received audio, this instead fires
+    /// in playout order, once each packet leaves its user's jitter buffer.
     VoicePacket,
+    /// Fires every 20ms, containing one frame of audio from each user who is
+    /// currently speaking, and the set of known users who are silent.
+    ///
+    /// Received packets are reordered and lost packets are concealed before
+    /// playout. This does not fire if the driver is configured not to decrypt
+    /// received packets, or if no other users have transmitted audio.
+    VoiceTick,
+    /// Fires every 20ms, containing a single stereo mixdown of all users'
+    /// audio, after each [`VoiceTick`] has been processed.
+    ///
+    /// This only fires if the driver is configured to decode received audio,
+    /// and while the driver is connected to a voice channel.
+    ///
+    /// [`VoiceTick`]: Self::VoiceTick
+    VoiceMix,
     /// Fires on receipt of an RTCP packet, containing various call stats
     /// such as latency reports.
     RtcpPacket,
@@ -49,7 +71,30 @@ pub enum CoreEvent {
     /// Fires when this driver successfully connects to a voice channel.
     DriverConnect,
     /// Fires when this driver successfully reconnects after a network error.
+    ///
+    /// This follows a full reconnection, during which audio playback is interrupted.
     DriverReconnect,
+    /// Fires when this driver resumes its websocket session after a drop,
+    /// without interrupting audio.
+    DriverResume,
+    /// Fires when this driver moves its session to a new voice server, such as
+    /// during Discord region maintenance.
+    ///
+    /// Playback continues uninterrupted, and track positions are preserved.
+    DriverMigrate,
     /// Fires when this driver fails to connect to, or drops from, a voice channel.
     DriverDisconnect,
+    /// Fires when a call recording fails, ending the recording.
+    RecordingError,
+    /// Fires periodically while connected, containing network and performance
+    /// statistics for the voice connection.
+    ///
+    /// These are sent each time the voice server acknowledges a websocket heartbeat.
+    ConnectionStats,
+    /// Fires at most once per second while connected, if any audio frames were
+    /// mixed after their deadline.
+    ///
+    /// Frequent reports indicate that the mixer thread is starved of CPU time,
+    /// and that listeners are likely to hear stuttering audio.
+    MixerOverload,
 }
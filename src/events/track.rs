@@ -22,4 +22,16 @@ pub enum TrackEvent {
     End,
     /// The attached track has looped.
     Loop,
+    /// A fade applied to the attached track has finished.
+    ///
+    /// This does not fire if the fade was replaced by another fade,
+    /// or cancelled by setting the track's volume.
+    FadeComplete,
+    /// The attached track's source has reported new metadata, such as the title
+    /// of the current song on a live radio stream.
+    ///
+    /// The new metadata is available from [`TrackHandle::current_metadata`].
+    ///
+    /// [`TrackHandle::current_metadata`]: crate::tracks::TrackHandle::current_metadata
+    MetadataUpdate,
 }
@@ -390,10 +390,14 @@ impl Call {
 
     /// Updates the voice server data.
     ///
+    /// If the driver is connected, it moves its session to the new server in the
+    /// background, firing [`CoreEvent::DriverMigrate`] once audio is sent there.
+    ///
     /// You should only need to use this if you initialized the `Call` via
     /// [`standalone`].
     ///
     /// [`standalone`]: Call::standalone
+    /// [`CoreEvent::DriverMigrate`]: crate::events::CoreEvent::DriverMigrate
     #[instrument(skip(self, token))]
     pub fn update_server(&mut self, endpoint: String, token: String) {
         let try_conn = if let Some((ref mut progress, _)) = self.connection.as_mut() {
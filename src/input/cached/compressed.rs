@@ -2,7 +2,8 @@ use super::{apply_length_hint, compressed_cost_per_sec, default_config};
 use crate::{
     constants::*,
     input::{
-        error::{Error, Result},
+        dca::{self, DcaMetadata, Opus},
+        error::{DcaError, Error, Result},
         CodecType,
         Container,
         Input,
@@ -22,7 +23,7 @@ use audiopus::{
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
     convert::TryInto,
-    io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult},
+    io::{self, Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Write},
     mem,
     sync::atomic::{AtomicUsize, Ordering},
 };
@@ -54,6 +55,7 @@ pub struct Compressed {
     pub metadata: Metadata,
     /// Stereo-ness of the captured source.
     pub stereo: bool,
+    opus: Opus,
 }
 
 impl Compressed {
@@ -74,14 +76,7 @@ impl Compressed {
     /// [`Input`]: Input
     /// [`Metadata::duration`]: crate::input::Metadata::duration
     pub fn with_config(source: Input, bitrate: Bitrate, config: Option<Config>) -> Result<Self> {
-        let channels = if source.stereo {
-            Channels::Stereo
-        } else {
-            Channels::Mono
-        };
-        let mut encoder = OpusEncoder::new(SampleRate::Hz48000, channels, Application::Audio)?;
-
-        encoder.set_bitrate(bitrate)?;
+        let encoder = new_encoder(source.stereo, bitrate)?;
 
         Self::with_encoder(source, encoder, config)
     }
@@ -100,6 +95,7 @@ impl Compressed {
         config: Option<Config>,
     ) -> Result<Self> {
         let bitrate = encoder.bitrate()?;
+        let opus = Opus::from_encoder(&encoder, source.stereo)?;
         let cost_per_sec = compressed_cost_per_sec(bitrate);
         let stereo = source.stereo;
         let metadata = source.metadata.take();
@@ -121,6 +117,7 @@ impl Compressed {
             raw,
             metadata,
             stereo,
+            opus,
         })
     }
 
@@ -131,8 +128,58 @@ impl Compressed {
             raw: self.raw.new_handle(),
             metadata: self.metadata.clone(),
             stereo: self.stereo,
+            opus: self.opus.clone(),
         }
     }
+
+    /// Writes the full contents of this cache to `writer` as a
+    /// [DCA1 file](https://github.com/bwmarrin/dca), which may later be
+    /// loaded using [`dca`].
+    ///
+    /// The stored Opus frames are copied as-is, reading the remainder of
+    /// the source if it has not yet been fully cached. This is a blocking operation.
+    ///
+    /// [`dca`]: crate::input::dca()
+    pub fn write_dca(&self, writer: impl Write) -> std::result::Result<(), DcaError> {
+        self.write_keyed_dca(writer, None)
+    }
+
+    /// Writes this cache as in [`write_dca`], recording the [`DiskCache`] key it is
+    /// stored under (if any).
+    ///
+    /// [`write_dca`]: Self::write_dca
+    /// [`DiskCache`]: super::DiskCache
+    pub(crate) fn write_keyed_dca(
+        &self,
+        mut writer: impl Write,
+        cache_key: Option<&str>,
+    ) -> std::result::Result<(), DcaError> {
+        let header = DcaMetadata::new(&self.metadata, self.opus.clone(), cache_key);
+        dca::write_header(&mut writer, &header)?;
+
+        io::copy(&mut self.raw.new_handle(), &mut writer).map_err(DcaError::IoError)?;
+
+        writer.flush().map_err(DcaError::IoError)
+    }
+}
+
+/// Creates the Opus encoder used for a source by [`Compressed::new`] and [`write_dca`].
+///
+/// [`write_dca`]: crate::input::write_dca
+pub(crate) fn new_encoder(
+    stereo: bool,
+    bitrate: Bitrate,
+) -> std::result::Result<OpusEncoder, OpusError> {
+    let channels = if stereo {
+        Channels::Stereo
+    } else {
+        Channels::Mono
+    };
+    let mut encoder = OpusEncoder::new(SampleRate::Hz48000, channels, Application::Audio)?;
+
+    encoder.set_bitrate(bitrate)?;
+
+    Ok(encoder)
 }
 
 impl From<Compressed> for Input {
@@ -165,7 +212,7 @@ pub struct OpusCompressor {
 }
 
 impl OpusCompressor {
-    fn new(encoder: OpusEncoder, stereo_input: bool) -> Self {
+    pub(crate) fn new(encoder: OpusEncoder, stereo_input: bool) -> Self {
         Self {
             encoder,
             last_frame: Vec::with_capacity(4000),
@@ -0,0 +1,463 @@
+use super::{new_encoder, Compressed};
+use crate::input::{
+    dca,
+    error::{DcaError, Error, Result},
+    Input,
+};
+use audiopus::Bitrate;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    future::Future,
+    io::{BufWriter, ErrorKind as IoErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+use tokio::{fs, sync::Mutex as AsyncMutex, task};
+use tracing::{debug, warn};
+
+const ENTRY_EXTENSION: &str = "dca";
+const PARTIAL_EXTENSION: &str = "part";
+
+/// A persistent store of Opus-encoded sources on disk, shared between drivers
+/// and kept within a fixed byte budget.
+///
+/// Each entry is saved as a [DCA1 file](https://github.com/bwmarrin/dca) along
+/// with its [`Metadata`], and is opened as a seekable [`Input`] which is not re-encoded
+/// (and can often be passed through directly to the driver). Concurrent readers of
+/// an entry each read from the same file, and concurrent calls to [`get_or_insert_with`]
+/// for a missing entry will only create it once.
+///
+/// Entries are identified by a string key, typically the URL of the source.
+/// Once the budget is exceeded, the least recently used entries are evicted:
+/// usage is tracked from file modification times when the cache is first opened.
+///
+/// Cloning a `DiskCache` creates a new handle to the same store.
+///
+/// [`Metadata`]: crate::input::Metadata
+/// [`get_or_insert_with`]: DiskCache::get_or_insert_with
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    dir: PathBuf,
+    budget: u64,
+    index: Mutex<Index>,
+    // Held while an entry is being created, so that concurrent requests
+    // for the same key wait on (and then share) the first.
+    in_flight: Mutex<HashMap<u64, Arc<AsyncMutex<()>>>>,
+    next_partial: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<u64, Entry>,
+    used: u64,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+impl Index {
+    fn touch(&mut self, hash: u64) -> bool {
+        self.clock += 1;
+        let clock = self.clock;
+
+        self.entries
+            .get_mut(&hash)
+            .map(|entry| entry.last_used = clock)
+            .is_some()
+    }
+
+    fn insert(&mut self, hash: u64, size: u64) {
+        self.clock += 1;
+
+        let last_used = self.clock;
+        if let Some(old) = self.entries.insert(hash, Entry { size, last_used }) {
+            self.used -= old.size;
+        }
+
+        self.used += size;
+    }
+
+    fn remove(&mut self, hash: u64) -> bool {
+        self.entries
+            .remove(&hash)
+            .map(|old| self.used -= old.size)
+            .is_some()
+    }
+
+    /// Removes least recently used entries (other than `keep`) until the budget is met.
+    fn evict(&mut self, budget: u64, keep: Option<u64>) -> Vec<u64> {
+        let mut evicted = vec![];
+
+        while self.used > budget {
+            let victim = self
+                .entries
+                .iter()
+                .filter(|(hash, _)| Some(**hash) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(hash, _)| *hash);
+
+            match victim {
+                Some(hash) => {
+                    self.remove(hash);
+                    evicted.push(hash);
+                },
+                None => break,
+            }
+        }
+
+        evicted
+    }
+}
+
+impl DiskCache {
+    /// Opens (or creates) a cache in the directory `dir`, limited to `budget` bytes.
+    ///
+    /// Any existing entries in `dir` are kept, unless they exceed the new budget.
+    pub async fn open(dir: impl Into<PathBuf>, budget: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+
+        let mut found = vec![];
+        let mut listing = fs::read_dir(&dir).await?;
+
+        while let Some(file) = listing.next_entry().await? {
+            let path = file.path();
+            let extension = path.extension().and_then(|ext| ext.to_str());
+
+            if extension == Some(PARTIAL_EXTENSION) {
+                // Left behind by an interrupted write.
+                if let Err(e) = fs::remove_file(&path).await {
+                    debug!("Failed to remove partial cache entry {:?}: {:?}", path, e);
+                }
+                continue;
+            }
+
+            let hash = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok());
+
+            if let (Some(ENTRY_EXTENSION), Some(hash)) = (extension, hash) {
+                let meta = file.metadata().await?;
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                found.push((modified, hash, meta.len()));
+            }
+        }
+
+        found.sort_unstable();
+
+        let mut index = Index::default();
+        for (_, hash, size) in found {
+            index.insert(hash, size);
+        }
+
+        let cache = Self {
+            inner: Arc::new(Inner {
+                dir,
+                budget,
+                index: Mutex::new(Index::default()),
+                in_flight: Default::default(),
+                next_partial: AtomicU64::new(0),
+            }),
+        };
+
+        let evicted = index.evict(budget, None);
+        *cache.inner.index.lock() = index;
+        cache.delete_entries(evicted).await;
+
+        Ok(cache)
+    }
+
+    /// Returns the directory holding this cache's entries.
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// Returns the maximum number of bytes this cache may store.
+    pub fn budget(&self) -> u64 {
+        self.inner.budget
+    }
+
+    /// Returns the number of bytes currently used by stored entries.
+    pub fn used(&self) -> u64 {
+        self.inner.index.lock().used
+    }
+
+    /// Returns whether an entry is currently stored for `key`.
+    pub fn contains(&self, key: impl AsRef<str>) -> bool {
+        let hash = key_hash(key.as_ref());
+
+        self.inner.index.lock().entries.contains_key(&hash)
+    }
+
+    /// Opens the entry stored for `key`, if one exists.
+    pub async fn get(&self, key: impl AsRef<str>) -> Result<Option<Input>> {
+        let key = key.as_ref();
+
+        self.get_hashed(key, key_hash(key)).await
+    }
+
+    /// Opens the entry stored for `key`, first creating it from the output of
+    /// `make` if needed.
+    ///
+    /// New entries are encoded at the given `bitrate`. If several tasks request a
+    /// missing entry at once, `make` is only called by the first of these.
+    pub async fn get_or_insert_with<F, Fut>(
+        &self,
+        key: impl AsRef<str>,
+        bitrate: Bitrate,
+        make: F,
+    ) -> Result<Input>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Input>>,
+    {
+        let key = key.as_ref();
+        let hash = key_hash(key);
+
+        if let Some(input) = self.get_hashed(key, hash).await? {
+            return Ok(input);
+        }
+
+        let lock = self.inner.in_flight.lock().entry(hash).or_default().clone();
+
+        let out = async {
+            let _guard = lock.lock().await;
+
+            if let Some(input) = self.get_hashed(key, hash).await? {
+                return Ok(input);
+            }
+
+            self.insert_hashed(key, hash, make().await?, bitrate)
+                .await?;
+
+            self.get_hashed(key, hash)
+                .await?
+                .ok_or_else(|| Error::Io(IoErrorKind::NotFound.into()))
+        }
+        .await;
+
+        let mut in_flight = self.inner.in_flight.lock();
+        // One reference is held by the map, and the other by this task.
+        if Arc::strong_count(&lock) <= 2 {
+            in_flight.remove(&hash);
+        }
+
+        out
+    }
+
+    /// Encodes `source` at the given `bitrate`, storing it as the entry for `key`.
+    ///
+    /// Any existing entry for `key` is replaced. [`Memory`] sources may be stored
+    /// by converting a handle into an [`Input`].
+    ///
+    /// [`Memory`]: super::Memory
+    pub async fn insert(
+        &self,
+        key: impl AsRef<str>,
+        source: Input,
+        bitrate: Bitrate,
+    ) -> Result<()> {
+        let key = key.as_ref();
+
+        self.insert_hashed(key, key_hash(key), source, bitrate)
+            .await
+    }
+
+    /// Stores the contents of `source` as the entry for `key`, without re-encoding.
+    ///
+    /// Any existing entry for `key` is replaced. If `source` has not yet been fully
+    /// cached in memory, the remainder of its input will be read.
+    pub async fn insert_compressed(&self, key: impl AsRef<str>, source: &Compressed) -> Result<()> {
+        let hash = key_hash(key.as_ref());
+        let key = key.as_ref().to_owned();
+        let source = source.new_handle();
+
+        self.write_entry(hash, move |file| source.write_keyed_dca(file, Some(&key)))
+            .await
+    }
+
+    /// Deletes the entry stored for `key`, returning whether one existed.
+    pub async fn remove(&self, key: impl AsRef<str>) -> Result<bool> {
+        let hash = key_hash(key.as_ref());
+
+        if !self.inner.index.lock().remove(hash) {
+            return Ok(false);
+        }
+
+        match fs::remove_file(self.entry_path(hash)).await {
+            Err(e) if e.kind() != IoErrorKind::NotFound => Err(e.into()),
+            _ => Ok(true),
+        }
+    }
+
+    async fn get_hashed(&self, key: &str, hash: u64) -> Result<Option<Input>> {
+        if !self.inner.index.lock().touch(hash) {
+            return Ok(None);
+        }
+
+        let path = self.entry_path(hash);
+
+        match dca::dca_with_cache_key(path.as_os_str()).await {
+            Ok((input, Some(stored))) if stored == key => {
+                touch_file(path).await;
+                Ok(Some(input))
+            },
+            Ok((_, stored)) => {
+                // Another key with the same hash owns this file: treat it as a miss,
+                // so that the entry is replaced if this key is inserted.
+                debug!(
+                    "Cache entry {:016x} is held by key {:?}, not {:?}.",
+                    hash, stored, key
+                );
+                Ok(None)
+            },
+            Err(DcaError::IoError(e)) if e.kind() == IoErrorKind::NotFound => {
+                // Evicted or removed since we checked the index.
+                debug!(
+                    "Cache entry {:016x} vanished before it could be opened.",
+                    hash
+                );
+                Ok(None)
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn insert_hashed(
+        &self,
+        key: &str,
+        hash: u64,
+        source: Input,
+        bitrate: Bitrate,
+    ) -> Result<()> {
+        let key = key.to_owned();
+
+        self.write_entry(hash, move |file| {
+            let encoder = new_encoder(source.stereo, bitrate).map_err(DcaError::Opus)?;
+            dca::write_keyed_dca(source, encoder, file, Some(&key))
+        })
+        .await
+    }
+
+    /// Writes a new entry alongside the cache, before moving it into place.
+    async fn write_entry<F>(&self, hash: u64, write: F) -> Result<()>
+    where
+        F: FnOnce(BufWriter<File>) -> std::result::Result<(), DcaError> + Send + 'static,
+    {
+        let partial = self.inner.dir.join(format!(
+            "{:016x}-{}.{}",
+            hash,
+            self.inner.next_partial.fetch_add(1, Ordering::Relaxed),
+            PARTIAL_EXTENSION
+        ));
+
+        let written = {
+            let partial = partial.clone();
+
+            task::spawn_blocking(move || {
+                let file = File::create(partial).map_err(DcaError::IoError)?;
+                write(BufWriter::new(file))
+            })
+            .await
+        };
+
+        let size = match written {
+            Ok(Ok(())) => fs::metadata(&partial)
+                .await
+                .map(|meta| meta.len())
+                .map_err(Error::from),
+            Ok(Err(e)) => Err(e.into()),
+            Err(e) => Err(Error::Io(e.into())),
+        };
+
+        let size = match size {
+            Ok(size) => size,
+            Err(e) => {
+                if let Err(e) = fs::remove_file(&partial).await {
+                    debug!(
+                        "Failed to remove partial cache entry {:?}: {:?}",
+                        partial, e
+                    );
+                }
+                return Err(e);
+            },
+        };
+
+        fs::rename(&partial, self.entry_path(hash)).await?;
+
+        let evicted = {
+            let mut index = self.inner.index.lock();
+            index.insert(hash, size);
+            index.evict(self.inner.budget, Some(hash))
+        };
+
+        self.delete_entries(evicted).await;
+
+        Ok(())
+    }
+
+    async fn delete_entries(&self, hashes: Vec<u64>) {
+        for hash in hashes {
+            let path = self.entry_path(hash);
+
+            match fs::remove_file(&path).await {
+                Err(e) if e.kind() != IoErrorKind::NotFound =>
+                    warn!("Failed to evict cache entry {:?}: {:?}", path, e),
+                _ => debug!("Evicted cache entry {:?}.", path),
+            }
+        }
+    }
+
+    fn entry_path(&self, hash: u64) -> PathBuf {
+        self.inner
+            .dir
+            .join(format!("{:016x}.{}", hash, ENTRY_EXTENSION))
+    }
+}
+
+/// Marks an entry as recently used, so that it is ordered correctly when the cache
+/// is next opened.
+///
+/// `File::set_modified` is unavailable at our MSRV: rewriting the (unchanged) magic
+/// number of the DCA file updates its modification time instead.
+async fn touch_file(path: PathBuf) {
+    let touched = task::spawn_blocking(move || {
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(b"DCA1"))
+            .map_err(|e| (path, e))
+    })
+    .await;
+
+    if let Ok(Err((path, e))) = touched {
+        debug!(
+            "Failed to update use time of cache entry {:?}: {:?}",
+            path, e
+        );
+    }
+}
+
+/// 64-bit FNV-1a, which (unlike std's hashers) is stable between releases
+/// and so suitable for naming files.
+pub(crate) fn key_hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -2,12 +2,13 @@
 //! direct Opus frame passthrough.
 
 mod compressed;
+mod disk;
 mod hint;
 mod memory;
 #[cfg(test)]
 mod tests;
 
-pub use self::{compressed::*, hint::*, memory::*};
+pub use self::{compressed::*, disk::*, hint::*, memory::*};
 
 use crate::constants::*;
 use crate::input::utils;
@@ -1,7 +1,7 @@
 use super::*;
 use crate::{
     constants::*,
-    input::{error::Error, Codec, Container, Input},
+    input::{dca::DcaMetadata, error::Error, write_dca, Codec, Container, Input, Metadata},
     test_utils::*,
 };
 use audiopus::{coder::Decoder, Bitrate, Channels, SampleRate};
@@ -9,6 +9,8 @@ use byteorder::{LittleEndian, ReadBytesExt};
 use std::{
     convert::TryInto,
     io::{Cursor, Read},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 #[tokio::test]
@@ -62,12 +64,191 @@ fn compressed_triggers_valid_passthrough() {
         .unwrap();
 }
 
-fn one_s_compressed_sine(stereo: bool) -> Compressed {
+#[test]
+fn compressed_export_matches_direct_dca() {
+    let direct_input = Input::new(
+        true,
+        make_sine(50 * MONO_FRAME_SIZE, true).into(),
+        Codec::FloatPcm,
+        Container::Raw,
+        None,
+    );
+
+    let mut direct = vec![];
+    write_dca(direct_input, Bitrate::BitsPerSecond(128_000), &mut direct).unwrap();
+
+    let mut exported = vec![];
+    one_s_compressed_sine(true)
+        .write_dca(&mut exported)
+        .unwrap();
+
+    assert_eq!(direct, exported);
+
+    let mut file = Cursor::new(direct);
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).unwrap();
+    assert_eq!(&magic, b"DCA1");
+
+    let header_len = file.read_i32::<LittleEndian>().unwrap();
+    let mut header = vec![0u8; header_len as usize];
+    file.read_exact(&mut header).unwrap();
+
+    let header: DcaMetadata = serde_json::from_slice(&header).unwrap();
+    assert_eq!(header.dca.version, 1);
+    assert_eq!(header.opus.channels, 2);
+    assert_eq!(header.opus.abr, 128_000);
+
+    run_through_dca(file);
+}
+
+#[tokio::test]
+async fn disk_cache_persists_entries() {
+    let dir = temp_cache_dir();
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+
+    let mut input = one_s_sine(true);
+    *input.metadata = Metadata {
+        title: Some("Sine".into()),
+        duration: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    cache
+        .insert(
+            "https://example.com/sine",
+            input,
+            Bitrate::BitsPerSecond(128_000),
+        )
+        .await
+        .unwrap();
+    cache
+        .insert_compressed("compressed", &one_s_compressed_sine(false))
+        .await
+        .unwrap();
+    assert!(cache.get("missing").await.unwrap().is_none());
+
+    let used = cache.used();
+    drop(cache);
+
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+    assert_eq!(cache.used(), used);
+
+    let mut input = cache
+        .get("https://example.com/sine")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(input.metadata.title.as_deref(), Some("Sine"));
+    assert_eq!(input.metadata.duration, Some(Duration::from_secs(1)));
+    assert!(input.is_seekable());
+    assert!(input.supports_passthrough());
+
+    let mut opus_buf = [0u8; 10_000];
+    assert!(input.read_opus_frame(&mut opus_buf[..]).unwrap() > 0);
+
+    let input = cache.get("compressed").await.unwrap().unwrap();
+    assert_eq!(input.metadata.channels, Some(1));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn disk_cache_evicts_least_recently_used() {
+    let dir = temp_cache_dir();
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+    let bitrate = Bitrate::BitsPerSecond(128_000);
+
+    cache.insert("a", one_s_sine(true), bitrate).await.unwrap();
+    let entry_size = cache.used();
+    drop(cache);
+
+    let cache = DiskCache::open(&dir, 2 * entry_size).await.unwrap();
+    cache.insert("b", one_s_sine(true), bitrate).await.unwrap();
+    assert!(cache.get("a").await.unwrap().is_some());
+
+    let mut made = 0;
+    for _ in 0..2 {
+        cache
+            .get_or_insert_with("c", bitrate, || {
+                made += 1;
+                async { Ok(one_s_sine(true)) }
+            })
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(made, 1);
+    assert!(cache.contains("a"));
+    assert!(!cache.contains("b"));
+    assert!(cache.contains("c"));
+    assert_eq!(cache.used(), 2 * entry_size);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn disk_cache_remembers_use_when_reopened() {
+    let dir = temp_cache_dir();
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+    let bitrate = Bitrate::BitsPerSecond(128_000);
+
+    // Pauses keep file modification times distinct on coarse-grained filesystems.
+    cache.insert("a", one_s_sine(true), bitrate).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    cache.insert("b", one_s_sine(true), bitrate).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(cache.get("a").await.unwrap().is_some());
+
+    let entry_size = cache.used() / 2;
+    drop(cache);
+
+    let cache = DiskCache::open(&dir, entry_size).await.unwrap();
+    assert!(cache.contains("a"));
+    assert!(!cache.contains("b"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn disk_cache_checks_keys_of_colliding_entries() {
+    let dir = temp_cache_dir();
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+    let bitrate = Bitrate::BitsPerSecond(128_000);
+
+    cache.insert("a", one_s_sine(true), bitrate).await.unwrap();
+    drop(cache);
+
+    // Stand in for a hash collision by moving the entry for "a" to where "b" belongs.
+    std::fs::rename(entry_path(&dir, "a"), entry_path(&dir, "b")).unwrap();
+
+    let cache = DiskCache::open(&dir, u64::MAX).await.unwrap();
+    assert!(cache.get("b").await.unwrap().is_none());
+
+    let input = cache
+        .get_or_insert_with("b", bitrate, || async { Ok(one_s_sine(false)) })
+        .await
+        .unwrap();
+    assert_eq!(input.metadata.channels, Some(1));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{:016x}.dca", key_hash(key)))
+}
+
+fn temp_cache_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("songbird-cache-{}", uuid::Uuid::new_v4()))
+}
+
+fn one_s_sine(stereo: bool) -> Input {
     let data = make_sine(50 * MONO_FRAME_SIZE, stereo);
 
-    let input = Input::new(stereo, data.into(), Codec::FloatPcm, Container::Raw, None);
+    Input::new(stereo, data.into(), Codec::FloatPcm, Container::Raw, None)
+}
 
-    Compressed::new(input, Bitrate::BitsPerSecond(128_000)).unwrap()
+fn one_s_compressed_sine(stereo: bool) -> Compressed {
+    Compressed::new(one_s_sine(stereo), Bitrate::BitsPerSecond(128_000)).unwrap()
 }
 
 fn run_through_dca(mut src: impl Read) {
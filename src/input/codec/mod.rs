@@ -2,6 +2,7 @@
 
 mod opus;
 
+pub(crate) use self::opus::is_twenty_millis;
 pub use self::opus::OpusDecoderState;
 
 use super::*;
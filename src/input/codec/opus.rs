@@ -19,6 +19,14 @@ pub struct OpusDecoderState {
     pub(crate) current_frame: Vec<f32>,
     pub(crate) frame_pos: usize,
     pub(crate) should_reset: bool,
+    /// The last frame sent via passthrough, used to prime the decoder
+    /// if decoding resumes from the next frame.
+    pub(crate) primer: Vec<u8>,
+    /// Number of samples (per channel) to discard from the start of the stream,
+    /// i.e., an Ogg Opus file's pre-skip or a WebM track's codec delay.
+    pub(crate) pre_skip: usize,
+    /// Number of decoded samples (per channel) still to be discarded.
+    pub(crate) skip: usize,
 }
 
 impl OpusDecoderState {
@@ -38,6 +46,21 @@ impl OpusDecoderState {
             current_frame: Vec::with_capacity(STEREO_FRAME_SIZE),
             frame_pos: 0,
             should_reset: false,
+            primer: Vec::new(),
+            pre_skip: 0,
+            skip: 0,
         }
     }
 }
+
+/// Returns whether an Opus packet holds exactly 20ms of audio, such that a source
+/// beginning with it is likely to be safe for passthrough.
+pub(crate) fn is_twenty_millis(packet: &[u8]) -> bool {
+    use audiopus::packet::{self, Packet};
+    use std::convert::TryFrom;
+
+    Packet::try_from(packet)
+        .and_then(|pkt| packet::nb_samples(pkt, SAMPLE_RATE))
+        .map(|samples| samples == MONO_FRAME_SIZE)
+        .unwrap_or(false)
+}
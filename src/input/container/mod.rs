@@ -1,12 +1,16 @@
 mod frame;
+pub(crate) mod ogg;
+pub(crate) mod webm;
 
-pub use frame::*;
+pub use self::frame::*;
 
-use super::CodecType;
+use ogg::OggState;
+
+use super::{CodecType, Reader};
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::{
     fmt::Debug,
-    io::{Read, Result as IoResult},
+    io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult},
     mem,
 };
 
@@ -23,10 +27,39 @@ pub enum Container {
         /// Byte index of the first frame after the JSON header.
         first_frame: usize,
     },
+    /// Opus packets stored in an Ogg file, as created by [`ogg`].
+    ///
+    /// Frames may span several Ogg pages, and so can only be read by an [`Input`]
+    /// which tracks its position within the current page.
+    ///
+    /// [`ogg`]: crate::input::ogg
+    /// [`Input`]: crate::input::Input
+    Ogg {
+        /// Byte index of the first page after the Opus header packets.
+        first_page: usize,
+        /// Serial number of the logical Opus stream.
+        serial: u32,
+    },
+    /// Opus frames stored in a WebM or Matroska file, as created by [`webm`].
+    ///
+    /// [`webm`]: crate::input::webm
+    WebM {
+        /// Byte index of the first cluster.
+        first_cluster: usize,
+        /// Track number of the Opus track.
+        track: u64,
+        /// Length of one timestamp tick, in nanoseconds.
+        timestamp_scale: u64,
+    },
 }
 
 impl Container {
     /// Tries to read the header of the next frame from an input stream.
+    ///
+    /// This is not supported by [`Ogg`] containers, whose frames may be split
+    /// by page headers.
+    ///
+    /// [`Ogg`]: Container::Ogg
     pub fn next_frame_length(
         &mut self,
         mut reader: impl Read,
@@ -43,9 +76,64 @@ impl Container {
                 header_len: mem::size_of::<i16>(),
                 frame_len: frame_len.max(0) as usize,
             }),
+            Ogg { .. } => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Ogg frames must be read using Container::read_frame.",
+            )),
+            WebM { track, .. } =>
+                webm::next_block(reader, *track).map(|(header_len, frame_len)| Frame {
+                    header_len,
+                    frame_len,
+                }),
+        }
+    }
+
+    /// Reads the next frame from an input stream into `buf`, returning its length.
+    ///
+    /// Frames larger than `buf` are skipped over, returning an error.
+    pub(crate) fn read_frame(
+        &mut self,
+        mut reader: impl Read,
+        input: CodecType,
+        state: &mut DemuxState,
+        buf: &mut [u8],
+    ) -> IoResult<usize> {
+        if let Container::Ogg { serial, .. } = self {
+            return state.ogg().read_packet(reader, *serial, buf);
+        }
+
+        let frame = self.next_frame_length(&mut reader, input)?;
+
+        if frame.frame_len > buf.len() {
+            ogg::io_skip(reader, frame.frame_len)?;
+
+            Err(IoError::new(
+                IoErrorKind::InvalidData,
+                "Frame was too large for buffer.",
+            ))
+        } else {
+            reader
+                .read_exact(&mut buf[..frame.frame_len])
+                .map(|_| frame.frame_len)
         }
     }
 
+    /// Consumes the next frame from an input stream without reading its contents
+    /// into memory, returning its length.
+    pub(crate) fn skip_frame(
+        &mut self,
+        mut reader: impl Read,
+        input: CodecType,
+        state: &mut DemuxState,
+    ) -> IoResult<usize> {
+        if let Container::Ogg { serial, .. } = self {
+            return state.ogg().skip_packet(reader, *serial);
+        }
+
+        let frame = self.next_frame_length(&mut reader, input)?;
+        ogg::io_skip(reader, frame.frame_len).map(|_| frame.frame_len)
+    }
+
     /// Tries to seek on an input directly using sample length, if the input
     /// is unframed.
     pub fn try_seek_trivial(&self, input: CodecType) -> Option<usize> {
@@ -57,6 +145,41 @@ impl Container {
         }
     }
 
+    /// Tries to move a seekable input to the frame containing sample `target`,
+    /// if the container includes timing information (i.e., Ogg granule positions,
+    /// or WebM cluster timestamps).
+    ///
+    /// On success, returns the index of the next sample which will be read from
+    /// the input. This will be at or before `target`.
+    pub(crate) fn try_seek_sample(
+        &self,
+        reader: &mut Reader,
+        state: &mut DemuxState,
+        target: u64,
+    ) -> Option<IoResult<u64>> {
+        use Container::*;
+
+        match self {
+            Ogg { first_page, serial } => Some(state.ogg().seek_granule(
+                reader,
+                *first_page,
+                *serial,
+                target,
+            )),
+            WebM {
+                first_cluster,
+                timestamp_scale,
+                ..
+            } => Some(webm::seek_cluster(
+                reader,
+                *first_cluster,
+                *timestamp_scale,
+                target,
+            )),
+            _ => None,
+        }
+    }
+
     /// Returns the byte index of the first frame containing audio payload data.
     pub fn input_start(&self) -> usize {
         use Container::*;
@@ -64,6 +187,26 @@ impl Container {
         match self {
             Raw => 0,
             Dca { first_frame } => *first_frame,
+            Ogg { first_page, .. } => *first_page,
+            WebM { first_cluster, .. } => *first_cluster,
         }
     }
 }
+
+/// Position within a [`Container`] which is not captured by the reader's offset alone,
+/// i.e., how much of the current Ogg page has been read.
+///
+/// This is held by each [`Input`] rather than its [`Container`], so that the latter
+/// remains `Copy`. It must be reset whenever the reader is moved by other means.
+///
+/// [`Input`]: crate::input::Input
+#[derive(Debug, Default)]
+pub(crate) struct DemuxState {
+    ogg: Option<Box<OggState>>,
+}
+
+impl DemuxState {
+    fn ogg(&mut self) -> &mut OggState {
+        self.ogg.get_or_insert_with(Default::default)
+    }
+}
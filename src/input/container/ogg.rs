@@ -0,0 +1,341 @@
+//! Minimal Ogg demuxing, sufficient to extract the packets of a single Opus stream.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{
+    fmt,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Seek, SeekFrom},
+};
+
+const CAPTURE_PATTERN: [u8; 4] = *b"OggS";
+const PAGE_HEADER_LEN: usize = 27;
+const MAX_SEGMENTS: usize = 255;
+
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_BOS: u8 = 0x02;
+
+/// Granule position used by pages on which no packet ends.
+const NO_GRANULE: u64 = u64::MAX;
+
+/// Current position within an Ogg page's segment table.
+///
+/// This is held for [`Container::Ogg`] sources by their [`DemuxState`], and tracks
+/// how much of the current page has been read.
+///
+/// [`Container::Ogg`]: super::Container::Ogg
+/// [`DemuxState`]: super::DemuxState
+#[derive(Clone, Copy)]
+pub(crate) struct OggState {
+    lacing: [u8; MAX_SEGMENTS],
+    segments: u8,
+    next_segment: u8,
+    /// Whether the page being read was expected to continue a partially-read packet.
+    continued: bool,
+}
+
+impl Default for OggState {
+    fn default() -> Self {
+        Self {
+            lacing: [0; MAX_SEGMENTS],
+            segments: 0,
+            next_segment: 0,
+            continued: false,
+        }
+    }
+}
+
+impl fmt::Debug for OggState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OggState")
+            .field("segments", &self.segments)
+            .field("next_segment", &self.next_segment)
+            .finish()
+    }
+}
+
+pub(crate) struct PageHeader {
+    pub flags: u8,
+    pub granule: u64,
+    pub serial: u32,
+    pub lacing: [u8; MAX_SEGMENTS],
+    pub segments: u8,
+}
+
+impl PageHeader {
+    pub fn body_len(&self) -> usize {
+        self.lacing[..self.segments as usize]
+            .iter()
+            .map(|v| *v as usize)
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        PAGE_HEADER_LEN + self.segments as usize + self.body_len()
+    }
+
+    pub fn is_bos(&self) -> bool {
+        self.flags & FLAG_BOS != 0
+    }
+
+    /// Number of packets which end on this page.
+    fn packets_ended(&self) -> usize {
+        self.lacing[..self.segments as usize]
+            .iter()
+            .filter(|v| **v < 255)
+            .count()
+    }
+}
+
+pub(crate) fn read_page_header(mut reader: impl Read) -> IoResult<PageHeader> {
+    let mut capture = [0u8; 4];
+    reader.read_exact(&mut capture)?;
+
+    if capture != CAPTURE_PATTERN {
+        return Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "Ogg page did not begin with capture pattern.",
+        ));
+    }
+
+    let _version = reader.read_u8()?;
+    let flags = reader.read_u8()?;
+    let granule = reader.read_u64::<LittleEndian>()?;
+    let serial = reader.read_u32::<LittleEndian>()?;
+    let _sequence = reader.read_u32::<LittleEndian>()?;
+    let _crc = reader.read_u32::<LittleEndian>()?;
+    let segments = reader.read_u8()?;
+
+    let mut lacing = [0u8; MAX_SEGMENTS];
+    reader.read_exact(&mut lacing[..segments as usize])?;
+
+    Ok(PageHeader {
+        flags,
+        granule,
+        serial,
+        lacing,
+        segments,
+    })
+}
+
+impl OggState {
+    /// Returns whether the reader is positioned at the start of a page.
+    pub(crate) fn at_page_boundary(&self) -> bool {
+        self.next_segment == self.segments
+    }
+
+    /// Reads the next packet belonging to stream `serial` into `buf`,
+    /// returning its length.
+    ///
+    /// Packets which are larger than `buf` are consumed, and return an error.
+    pub(crate) fn read_packet(
+        &mut self,
+        reader: impl Read,
+        serial: u32,
+        buf: &mut [u8],
+    ) -> IoResult<usize> {
+        self.next_packet(reader, serial, Some(buf))
+    }
+
+    /// Consumes the next packet belonging to stream `serial`, returning its length.
+    pub(crate) fn skip_packet(&mut self, reader: impl Read, serial: u32) -> IoResult<usize> {
+        self.next_packet(reader, serial, None)
+    }
+
+    fn next_packet(
+        &mut self,
+        mut reader: impl Read,
+        serial: u32,
+        mut buf: Option<&mut [u8]>,
+    ) -> IoResult<usize> {
+        let mut len = 0;
+        let mut overflow = false;
+
+        loop {
+            if self.at_page_boundary() {
+                let header = read_page_header(&mut reader)?;
+
+                if header.serial != serial {
+                    io_skip(&mut reader, header.body_len())?;
+                    continue;
+                }
+
+                self.lacing = header.lacing;
+                self.segments = header.segments;
+                self.next_segment = 0;
+
+                if header.flags & FLAG_CONTINUED != 0 && !self.continued {
+                    // This page begins with the tail of a packet we never saw
+                    // (i.e., after a seek): discard it.
+                    while !self.at_page_boundary() {
+                        let lace = self.lacing[self.next_segment as usize];
+                        self.next_segment += 1;
+                        io_skip(&mut reader, lace as usize)?;
+
+                        if lace < 255 {
+                            break;
+                        }
+                    }
+
+                    continue;
+                }
+            }
+
+            while !self.at_page_boundary() {
+                let lace = self.lacing[self.next_segment as usize] as usize;
+                self.next_segment += 1;
+
+                match buf.as_deref_mut() {
+                    Some(buf) if !overflow && len + lace <= buf.len() =>
+                        reader.read_exact(&mut buf[len..len + lace])?,
+                    Some(_) => {
+                        overflow = true;
+                        io_skip(&mut reader, lace)?;
+                    },
+                    None => io_skip(&mut reader, lace)?,
+                }
+                len += lace;
+
+                if lace < 255 {
+                    self.continued = false;
+
+                    return if overflow {
+                        Err(IoError::new(
+                            IoErrorKind::InvalidData,
+                            "Ogg packet was too large for buffer.",
+                        ))
+                    } else {
+                        Ok(len)
+                    };
+                }
+            }
+
+            // Packet continues onto the next page.
+            self.continued = true;
+        }
+    }
+
+    /// Positions `reader` at the start of the page containing sample `target`
+    /// (after `first_page`), returning the sample index which will be read next.
+    ///
+    /// The page search is linear, but only reads each page's header.
+    pub(crate) fn seek_granule<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        first_page: usize,
+        serial: u32,
+        target: u64,
+    ) -> IoResult<u64> {
+        let mut page_start = first_page as u64;
+        let mut prev_granule = 0;
+        let mut reached = 0;
+
+        reader.seek(SeekFrom::Start(page_start))?;
+
+        loop {
+            let header = match read_page_header(&mut *reader) {
+                Ok(header) => header,
+                // Target lies beyond the final page: play out from there.
+                Err(e) if e.kind() == IoErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+
+            if header.serial == serial && header.granule != NO_GRANULE {
+                if header.granule > target {
+                    reached = prev_granule;
+
+                    if header.flags & FLAG_CONTINUED != 0 {
+                        // The first packet on this page will be discarded:
+                        // estimate its length from the others.
+                        let ended = header.packets_ended().max(1) as u64;
+                        reached += (header.granule - prev_granule) / ended;
+                    }
+
+                    break;
+                }
+
+                prev_granule = header.granule;
+                reached = prev_granule;
+            }
+
+            reader.seek(SeekFrom::Current(header.body_len() as i64))?;
+            page_start += header.len() as u64;
+        }
+
+        reader.seek(SeekFrom::Start(page_start))?;
+        *self = Self::default();
+
+        Ok(reached)
+    }
+}
+
+pub(crate) fn io_skip(reader: impl Read, amt: usize) -> IoResult<()> {
+    let skipped = std::io::copy(&mut reader.take(amt as u64), &mut std::io::sink())?;
+
+    if skipped < amt as u64 {
+        Err(IoErrorKind::UnexpectedEof.into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn page(serial: u32, flags: u8, lacing: &[u8]) -> Vec<u8> {
+        let mut out = CAPTURE_PATTERN.to_vec();
+        out.extend_from_slice(&[0, flags]);
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&serial.to_le_bytes());
+        out.extend_from_slice(&[0; 8]);
+        out.push(lacing.len() as u8);
+        out.extend_from_slice(lacing);
+
+        for (i, lace) in lacing.iter().enumerate() {
+            out.extend(std::iter::repeat(i as u8).take(*lace as usize));
+        }
+
+        out
+    }
+
+    #[test]
+    fn packets_span_pages_and_skip_other_streams() {
+        let mut file = page(1, 0, &[10, 255]);
+        file.extend(page(2, 0, &[20]));
+        file.extend(page(1, FLAG_CONTINUED, &[5, 7]));
+
+        let mut reader = Cursor::new(file);
+        let mut state = OggState::default();
+        let mut buf = [0u8; 512];
+
+        assert_eq!(state.read_packet(&mut reader, 1, &mut buf).unwrap(), 10);
+        assert_eq!(state.read_packet(&mut reader, 1, &mut buf).unwrap(), 260);
+        assert_eq!(state.read_packet(&mut reader, 1, &mut buf).unwrap(), 7);
+        assert!(state.read_packet(&mut reader, 1, &mut buf).is_err());
+    }
+
+    #[test]
+    fn skipped_packets_ignore_buffer_size() {
+        let mut file = page(1, 0, &[10, 255]);
+        file.extend(page(1, FLAG_CONTINUED, &[255, 5, 7]));
+
+        let mut reader = Cursor::new(file);
+        let mut state = OggState::default();
+        let mut buf = [0u8; 16];
+
+        assert_eq!(state.skip_packet(&mut reader, 1).unwrap(), 10);
+        assert_eq!(state.skip_packet(&mut reader, 1).unwrap(), 515);
+        assert_eq!(state.read_packet(&mut reader, 1, &mut buf).unwrap(), 7);
+    }
+
+    #[test]
+    fn orphaned_continuation_is_discarded() {
+        let file = page(1, FLAG_CONTINUED, &[30, 12]);
+
+        let mut reader = Cursor::new(file);
+        let mut state = OggState::default();
+        let mut buf = [0u8; 512];
+
+        assert_eq!(state.read_packet(&mut reader, 1, &mut buf).unwrap(), 12);
+    }
+}
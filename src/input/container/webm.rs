@@ -0,0 +1,349 @@
+//! Minimal WebM/Matroska demuxing, sufficient to extract the frames of a single Opus track.
+
+use super::ogg::io_skip;
+use byteorder::ReadBytesExt;
+use std::io::{
+    Error as IoError,
+    ErrorKind as IoErrorKind,
+    Read,
+    Result as IoResult,
+    Seek,
+    SeekFrom,
+};
+
+pub(crate) const EBML_HEADER: u32 = 0x1A45_DFA3;
+pub(crate) const SEGMENT: u32 = 0x1853_8067;
+pub(crate) const INFO: u32 = 0x1549_A966;
+pub(crate) const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+pub(crate) const DURATION: u32 = 0x4489;
+pub(crate) const TITLE: u32 = 0x7BA9;
+pub(crate) const TRACKS: u32 = 0x1654_AE6B;
+pub(crate) const TRACK_ENTRY: u32 = 0xAE;
+pub(crate) const TRACK_NUMBER: u32 = 0xD7;
+pub(crate) const CODEC_ID: u32 = 0x86;
+pub(crate) const CODEC_DELAY: u32 = 0x56AA;
+pub(crate) const AUDIO: u32 = 0xE1;
+pub(crate) const CHANNELS: u32 = 0x9F;
+pub(crate) const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const BLOCK_GROUP: u32 = 0xA0;
+const BLOCK: u32 = 0xA1;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+/// Flags in a (Simple)Block header which indicate that several frames are laced together.
+const LACING_MASK: u8 = 0x06;
+
+/// Element header of a Matroska (EBML) element.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Element {
+    pub id: u32,
+    /// Length of the element's body, or `None` if unknown (i.e., live streams).
+    pub size: Option<u64>,
+    /// Length of the element's ID and size fields.
+    pub header_len: usize,
+}
+
+/// Reads a variable-length integer, returning its value (with its length marker removed)
+/// and its length in bytes.
+pub(crate) fn read_vint(mut reader: impl Read, keep_marker: bool) -> IoResult<(u64, usize)> {
+    let first = reader.read_u8()?;
+    let len = first.leading_zeros() as usize + 1;
+
+    if len > 8 {
+        return Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "Invalid EBML variable-length integer.",
+        ));
+    }
+
+    let mut value = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first) & (0xFF >> len)
+    };
+
+    for _ in 1..len {
+        value = (value << 8) | u64::from(reader.read_u8()?);
+    }
+
+    Ok((value, len))
+}
+
+pub(crate) fn read_element(mut reader: impl Read) -> IoResult<Element> {
+    let (id, id_len) = read_vint(&mut reader, true)?;
+    let (size, size_len) = read_vint(&mut reader, false)?;
+
+    let unknown = (1u64 << (7 * size_len)) - 1;
+
+    Ok(Element {
+        id: id as u32,
+        size: if size == unknown { None } else { Some(size) },
+        header_len: id_len + size_len,
+    })
+}
+
+pub(crate) fn read_uint(mut reader: impl Read, size: u64) -> IoResult<u64> {
+    if size > 8 {
+        return Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "EBML unsigned integer too large.",
+        ));
+    }
+
+    let mut value = 0;
+    for _ in 0..size {
+        value = (value << 8) | u64::from(reader.read_u8()?);
+    }
+
+    Ok(value)
+}
+
+pub(crate) fn skip_element(reader: impl Read, element: &Element) -> IoResult<()> {
+    match element.size {
+        Some(size) => io_skip(reader, size as usize),
+        None => Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "Cannot skip EBML element of unknown size.",
+        )),
+    }
+}
+
+/// Reads element headers until the next block belonging to `track`, leaving `reader`
+/// positioned at the start of its frame data and returning the frame's length.
+pub(crate) fn next_block(mut reader: impl Read, track: u64) -> IoResult<(usize, usize)> {
+    loop {
+        let element = read_element(&mut reader)?;
+
+        match element.id {
+            // Master elements containing blocks: descend into them.
+            SEGMENT | CLUSTER | BLOCK_GROUP => {},
+            SIMPLE_BLOCK | BLOCK => {
+                let size = element.size.ok_or_else(|| {
+                    IoError::new(IoErrorKind::InvalidData, "Block had unknown size.")
+                })? as usize;
+
+                let (block_track, track_len) = read_vint(&mut reader, false)?;
+                let mut timecode_and_flags = [0u8; 3];
+                reader.read_exact(&mut timecode_and_flags)?;
+
+                let block_header_len = track_len + timecode_and_flags.len();
+                let data_len = size.checked_sub(block_header_len).ok_or_else(|| {
+                    IoError::new(IoErrorKind::InvalidData, "Block was too short.")
+                })?;
+
+                if block_track != track {
+                    io_skip(&mut reader, data_len)?;
+                    continue;
+                }
+
+                if timecode_and_flags[2] & LACING_MASK != 0 {
+                    // Laced frames are not produced by common Opus muxers.
+                    io_skip(&mut reader, data_len)?;
+
+                    return Err(IoError::new(
+                        IoErrorKind::InvalidData,
+                        "Laced WebM blocks are not supported.",
+                    ));
+                }
+
+                return Ok((element.header_len + block_header_len, data_len));
+            },
+            _ => skip_element(&mut reader, &element)?,
+        }
+    }
+}
+
+/// Positions `reader` at the start of the last cluster beginning at or before
+/// sample `target` (from `first_cluster`), returning that cluster's start time in samples.
+pub(crate) fn seek_cluster<R: Read + Seek>(
+    reader: &mut R,
+    first_cluster: usize,
+    timestamp_scale: u64,
+    target: u64,
+) -> IoResult<u64> {
+    let mut pos = first_cluster as u64;
+    let mut best = (pos, 0);
+
+    reader.seek(SeekFrom::Start(pos))?;
+
+    loop {
+        let element = match read_element(&mut *reader) {
+            Ok(el) => el,
+            Err(e) if e.kind() == IoErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+
+        let size = match element.size {
+            Some(size) => size,
+            // Live streams cannot be scanned, so start from the best cluster found.
+            None => break,
+        };
+
+        if element.id == CLUSTER {
+            let child = read_element(&mut *reader)?;
+
+            if child.id == TIMESTAMP {
+                let time = read_uint(&mut *reader, child.size.unwrap_or(0))?;
+                let samples = timestamp_to_samples(time, timestamp_scale);
+
+                if samples > target {
+                    break;
+                }
+
+                best = (pos, samples);
+            }
+        }
+
+        pos += element.header_len as u64 + size;
+        reader.seek(SeekFrom::Start(pos))?;
+    }
+
+    reader.seek(SeekFrom::Start(best.0))?;
+
+    Ok(best.1)
+}
+
+/// Converts a Matroska timestamp into a sample count at 48kHz.
+pub(crate) fn timestamp_to_samples(time: u64, timestamp_scale: u64) -> u64 {
+    ((u128::from(time) * u128::from(timestamp_scale) * 48_000) / 1_000_000_000) as u64
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Encodes an EBML element, using an 8-byte size field.
+    pub(crate) fn element(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut out = id_bytes(id);
+        out.push(0x01);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// Encodes an EBML element whose size is unknown, as in live streams.
+    fn unsized_element(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut out = id_bytes(id);
+        out.extend_from_slice(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        out.extend_from_slice(body);
+        out
+    }
+
+    pub(crate) fn cluster(timestamp: u64, blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = element(TIMESTAMP, &timestamp.to_be_bytes());
+        blocks
+            .iter()
+            .for_each(|block| body.extend_from_slice(block));
+
+        element(CLUSTER, &body)
+    }
+
+    pub(crate) fn simple_block(track: u8, flags: u8, frame: &[u8]) -> Vec<u8> {
+        element(SIMPLE_BLOCK, &block_body(track, flags, frame))
+    }
+
+    fn block_body(track: u8, flags: u8, frame: &[u8]) -> Vec<u8> {
+        let mut body = vec![0x80 | track, 0, 0, flags];
+        body.extend_from_slice(frame);
+        body
+    }
+
+    fn id_bytes(id: u32) -> Vec<u8> {
+        id.to_be_bytes()
+            .iter()
+            .copied()
+            .skip_while(|byte| *byte == 0)
+            .collect()
+    }
+
+    fn read_block(reader: &mut Cursor<Vec<u8>>, track: u64) -> IoResult<Vec<u8>> {
+        let (_, len) = next_block(&mut *reader, track)?;
+        let mut frame = vec![0u8; len];
+        reader.read_exact(&mut frame)?;
+
+        Ok(frame)
+    }
+
+    #[test]
+    fn simple_and_grouped_blocks_are_extracted() {
+        let group = [
+            element(BLOCK, &block_body(1, 0, &[3; 7])),
+            // BlockDuration.
+            element(0x9B, &[20]),
+        ]
+        .concat();
+
+        let file = cluster(
+            0,
+            &[
+                simple_block(1, 0x80, &[1; 5]),
+                simple_block(2, 0x80, &[2; 6]),
+                element(BLOCK_GROUP, &group),
+                simple_block(1, 0x80, &[4; 8]),
+            ],
+        );
+
+        let mut reader = Cursor::new(file);
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [1; 5]);
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [3; 7]);
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [4; 8]);
+        assert!(read_block(&mut reader, 1).is_err());
+    }
+
+    #[test]
+    fn unknown_size_elements_are_descended_into() {
+        let mut body = element(TIMESTAMP, &[0]);
+        body.extend(simple_block(1, 0x80, &[1; 5]));
+        let file = unsized_element(SEGMENT, &unsized_element(CLUSTER, &body));
+
+        let mut reader = Cursor::new(file);
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [1; 5]);
+    }
+
+    #[test]
+    fn laced_blocks_are_rejected() {
+        let file = cluster(
+            0,
+            &[
+                simple_block(1, 0x80 | 0x02, &[1; 5]),
+                simple_block(1, 0x80, &[2; 5]),
+            ],
+        );
+
+        let mut reader = Cursor::new(file);
+        let err = read_block(&mut reader, 1).unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidData);
+
+        // The laced block is consumed, so later blocks remain readable.
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [2; 5]);
+    }
+
+    #[test]
+    fn seeks_to_latest_cluster_before_target() {
+        // Timestamps are in milliseconds, at 48 samples each.
+        let file = [
+            cluster(0, &[simple_block(1, 0x80, &[1; 5])]),
+            cluster(1000, &[simple_block(1, 0x80, &[2; 5])]),
+            cluster(2000, &[simple_block(1, 0x80, &[3; 5])]),
+        ]
+        .concat();
+
+        let mut reader = Cursor::new(file);
+
+        assert_eq!(
+            seek_cluster(&mut reader, 0, 1_000_000, 60_000).unwrap(),
+            48_000
+        );
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [2; 5]);
+
+        assert_eq!(seek_cluster(&mut reader, 0, 1_000_000, 10).unwrap(), 0);
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [1; 5]);
+
+        assert_eq!(
+            seek_cluster(&mut reader, 0, 1_000_000, u64::MAX).unwrap(),
+            96_000
+        );
+        assert_eq!(read_block(&mut reader, 1).unwrap(), [3; 5]);
+    }
+}
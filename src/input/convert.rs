@@ -0,0 +1,566 @@
+//! Sample rate and channel layout conversion for raw audio sources.
+
+use super::{reader::MediaSource, Input, Metadata, Reader};
+use crate::constants::SAMPLE_RATE_RAW;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    f64::consts::PI,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    io::{
+        Cursor,
+        Error as IoError,
+        ErrorKind as IoErrorKind,
+        Read,
+        Result as IoResult,
+        Seek,
+        SeekFrom,
+    },
+    mem,
+};
+
+const STEREO_FRAME_BYTES: usize = 2 * mem::size_of::<f32>();
+
+/// Number of source frames converted per read.
+const BLOCK_FRAMES: usize = 960;
+
+/// Half-width of the windowed-sinc filter used by [`ResampleQuality::High`], in frames.
+const SINC_HALF_TAPS: usize = 8;
+
+/// Replaces an unknown (zero) sample rate with 48kHz, which would otherwise
+/// require infinitely many output frames per input frame.
+fn valid_rate(sample_rate: u32) -> u32 {
+    if sample_rate == 0 {
+        SAMPLE_RATE_RAW as u32
+    } else {
+        sample_rate
+    }
+}
+
+/// Interpolation used when converting a source to 48kHz.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ResampleQuality {
+    /// Linear interpolation between adjacent samples.
+    ///
+    /// This is the cheapest option, but introduces audible aliasing
+    /// on bright material.
+    Low,
+    /// Cubic (Catmull-Rom) interpolation over four samples.
+    Medium,
+    /// Windowed-sinc interpolation over 16 samples, with low-pass filtering
+    /// when downsampling.
+    ///
+    /// This is the most expensive option, and is best suited to music.
+    High,
+}
+
+impl ResampleQuality {
+    /// Number of frames needed before and after the interpolation point.
+    fn taps(self) -> (usize, usize) {
+        match self {
+            Self::Low => (0, 1),
+            Self::Medium => (1, 2),
+            Self::High => (SINC_HALF_TAPS - 1, SINC_HALF_TAPS),
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ResampleQuality {
+    fn default() -> Self {
+        Self::Medium
+    }
+}
+
+/// Encoding of individual samples in a raw audio source.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SampleFormat {
+    /// Signed 16-bit little-endian PCM.
+    I16,
+    /// 32-bit little-endian floating-point PCM.
+    F32,
+}
+
+impl SampleFormat {
+    fn sample_len(self) -> usize {
+        match self {
+            Self::I16 => mem::size_of::<i16>(),
+            Self::F32 => mem::size_of::<f32>(),
+        }
+    }
+
+    fn read_sample(self, mut reader: impl Read) -> IoResult<f32> {
+        match self {
+            Self::I16 => reader
+                .read_i16::<LittleEndian>()
+                .map(|s| f32::from(s) / 32768.0),
+            Self::F32 => reader.read_f32::<LittleEndian>(),
+        }
+    }
+}
+
+/// Converts interleaved audio of any sample rate and channel count into
+/// 48kHz stereo.
+#[derive(Clone, Debug)]
+pub(crate) struct Converter {
+    channels: usize,
+    /// Contribution of each input channel to the left and right outputs.
+    gains: Vec<[f32; 2]>,
+    quality: ResampleQuality,
+    /// Input frames consumed per output frame.
+    step: f64,
+    /// Low-pass cutoff for the sinc filter, relative to the input Nyquist rate.
+    cutoff: f64,
+    /// Downmixed input frames, including history needed for interpolation.
+    history: Vec<[f32; 2]>,
+    /// Position of the next output frame within `history`.
+    phase: f64,
+}
+
+impl Converter {
+    pub(crate) fn new(sample_rate: u32, channels: usize, quality: ResampleQuality) -> Self {
+        let step = f64::from(valid_rate(sample_rate)) / SAMPLE_RATE_RAW as f64;
+        let channels = channels.max(1);
+
+        let mut out = Self {
+            channels,
+            gains: downmix_gains(channels),
+            quality,
+            step,
+            cutoff: (1.0 / step).min(1.0),
+            history: Vec::with_capacity(BLOCK_FRAMES * 2),
+            phase: 0.0,
+        };
+
+        out.reset();
+
+        out
+    }
+
+    /// Clears all history, i.e., after a seek.
+    pub(crate) fn reset(&mut self) {
+        let (before, _) = self.quality.taps();
+
+        self.history.clear();
+        self.history.resize(before, [0.0; 2]);
+        self.phase = before as f64;
+    }
+
+    fn is_passthrough(&self) -> bool {
+        (self.step - 1.0).abs() < f64::EPSILON
+    }
+
+    /// Converts a block of interleaved samples, appending the output to `out`
+    /// as little-endian floating-point stereo.
+    pub(crate) fn process(&mut self, samples: &[f32], out: &mut Vec<u8>) {
+        let channels = self.channels;
+        let gains = &self.gains;
+
+        let frames = samples.chunks_exact(channels).map(|frame| match channels {
+            1 => [frame[0]; 2],
+            2 => [frame[0], frame[1]],
+            _ => frame
+                .iter()
+                .zip(gains)
+                .fold([0.0; 2], |[l, r], (s, g)| [l + s * g[0], r + s * g[1]]),
+        });
+
+        if self.is_passthrough() {
+            out.reserve(samples.len() / channels * STEREO_FRAME_BYTES);
+
+            for frame in frames {
+                write_frame(out, frame);
+            }
+
+            return;
+        }
+
+        self.history.extend(frames);
+
+        let (before, after) = self.quality.taps();
+
+        while (self.phase as usize) + after < self.history.len() {
+            let frame = self.interpolate(self.phase);
+            write_frame(out, frame);
+            self.phase += self.step;
+        }
+
+        // Keep only those frames needed to produce the next output.
+        let drained = (self.phase as usize).saturating_sub(before);
+        self.history.drain(..drained);
+        self.phase -= drained as f64;
+    }
+
+    fn interpolate(&self, pos: f64) -> [f32; 2] {
+        let index = pos as usize;
+        let t = (pos - index as f64) as f32;
+        let h = &self.history;
+
+        match self.quality {
+            ResampleQuality::Low => {
+                let (a, b) = (h[index], h[index + 1]);
+
+                [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+            },
+            ResampleQuality::Medium => {
+                let mut out = [0.0; 2];
+
+                for (c, out) in out.iter_mut().enumerate() {
+                    let (p0, p1, p2, p3) = (
+                        h[index - 1][c],
+                        h[index][c],
+                        h[index + 1][c],
+                        h[index + 2][c],
+                    );
+
+                    *out = p1
+                        + 0.5
+                            * t
+                            * (p2 - p0
+                                + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3
+                                    + t * (3.0 * (p1 - p2) + p3 - p0)));
+                }
+
+                out
+            },
+            ResampleQuality::High => {
+                let mut out = [0.0f64; 2];
+                let mut total = 0.0;
+
+                let first = index + 1 - SINC_HALF_TAPS;
+                for (i, frame) in h[first..=index + SINC_HALF_TAPS].iter().enumerate() {
+                    let x = (first + i) as f64 - pos;
+                    let weight = sinc(x * self.cutoff) * blackman(x / SINC_HALF_TAPS as f64);
+
+                    out[0] += f64::from(frame[0]) * weight;
+                    out[1] += f64::from(frame[1]) * weight;
+                    total += weight;
+                }
+
+                [(out[0] / total) as f32, (out[1] / total) as f32]
+            },
+        }
+    }
+}
+
+fn write_frame(out: &mut Vec<u8>, frame: [f32; 2]) {
+    // Writes to a Vec cannot fail.
+    let _ = out.write_f32::<LittleEndian>(frame[0]);
+    let _ = out.write_f32::<LittleEndian>(frame[1]);
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window over `x` in `[-1, 1]`.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+    }
+}
+
+/// Computes how much each channel contributes to a stereo downmix, assuming
+/// the standard WAV/SMPTE channel order (i.e., `FL FR FC LFE BL BR SL SR`).
+///
+/// Centre channels contribute at -3dB to both sides, and LFE is dropped.
+/// Gains are normalised such that a full-scale signal on every channel
+/// cannot clip.
+fn downmix_gains(channels: usize) -> Vec<[f32; 2]> {
+    use std::f32::consts::FRAC_1_SQRT_2 as C;
+
+    const L: [f32; 2] = [1.0, 0.0];
+    const R: [f32; 2] = [0.0, 1.0];
+    const CENTRE: [f32; 2] = [C, C];
+    const SL: [f32; 2] = [C, 0.0];
+    const SR: [f32; 2] = [0.0, C];
+    const LFE: [f32; 2] = [0.0, 0.0];
+
+    let mut gains = match channels {
+        1 => vec![[1.0, 1.0]],
+        2 => vec![L, R],
+        3 => vec![L, R, CENTRE],
+        4 => vec![L, R, SL, SR],
+        5 => vec![L, R, CENTRE, SL, SR],
+        6 => vec![L, R, CENTRE, LFE, SL, SR],
+        7 => vec![L, R, CENTRE, LFE, CENTRE, SL, SR],
+        8 => vec![L, R, CENTRE, LFE, SL, SR, SL, SR],
+        n => (0..n).map(|i| if i % 2 == 0 { L } else { R }).collect(),
+    };
+
+    if channels > 2 {
+        let max_sum = gains
+            .iter()
+            .fold([0.0f32; 2], |[l, r], g| [l + g[0], r + g[1]]);
+        let norm = max_sum[0].max(max_sum[1]).max(1.0);
+
+        for gain in gains.iter_mut() {
+            gain[0] /= norm;
+            gain[1] /= norm;
+        }
+    }
+
+    gains
+}
+
+/// A raw PCM source of any sample rate and channel count, converted into
+/// 48kHz stereo floating-point PCM.
+///
+/// This allows audio such as TTS output or captured game audio to be played
+/// without first passing it through `ffmpeg`. Channel layouts of more than two
+/// channels are downmixed assuming the standard WAV channel order (e.g., 5.1
+/// audio as `FL FR FC LFE BL BR`).
+///
+/// Seeking is supported if the underlying source is seekable.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use songbird::input::{ConvertedSource, Input, ResampleQuality, SampleFormat};
+/// use std::fs::File;
+///
+/// let raw = File::open("tts_output.raw").unwrap();
+/// let source = ConvertedSource::new(
+///     Box::new(raw),
+///     SampleFormat::I16,
+///     22_050,
+///     1,
+///     ResampleQuality::High,
+/// );
+///
+/// let input = Input::from(source);
+/// ```
+pub struct ConvertedSource {
+    source: Box<dyn MediaSource>,
+    format: SampleFormat,
+    sample_rate: u32,
+    channels: u8,
+    converter: Converter,
+    raw: Vec<u8>,
+    samples: Vec<f32>,
+    /// Converted audio which has not yet been read.
+    frame: Cursor<Vec<u8>>,
+    /// Current position in the output, in bytes.
+    pos: u64,
+}
+
+impl ConvertedSource {
+    /// Wraps a raw source of interleaved samples, with the given sample rate
+    /// and channel count.
+    ///
+    /// A `sample_rate` of zero is treated as 48kHz.
+    pub fn new(
+        source: Box<dyn MediaSource>,
+        format: SampleFormat,
+        sample_rate: u32,
+        channels: u8,
+        quality: ResampleQuality,
+    ) -> Self {
+        let sample_rate = valid_rate(sample_rate);
+
+        Self {
+            source,
+            format,
+            sample_rate,
+            channels,
+            converter: Converter::new(sample_rate, channels.into(), quality),
+            raw: vec![],
+            samples: Vec::with_capacity(BLOCK_FRAMES * usize::from(channels)),
+            frame: Default::default(),
+            pos: 0,
+        }
+    }
+
+    fn next_frame(&mut self) -> IoResult<bool> {
+        let channels = usize::from(self.channels.max(1));
+        let frame_len = channels * self.format.sample_len();
+
+        self.raw.resize(BLOCK_FRAMES * frame_len, 0);
+
+        let mut len = 0;
+        while len < self.raw.len() {
+            match self.source.read(&mut self.raw[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == IoErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Drop any incomplete trailing frame.
+        let len = len - len % frame_len;
+
+        self.samples.clear();
+        let mut raw = &self.raw[..len];
+        while let Ok(sample) = self.format.read_sample(&mut raw) {
+            self.samples.push(sample);
+        }
+
+        let mut bytes = Vec::with_capacity(
+            ((len / frame_len) as f64 / self.converter.step).ceil() as usize * STEREO_FRAME_BYTES,
+        );
+        self.converter.process(&self.samples, &mut bytes);
+        self.frame = Cursor::new(bytes);
+
+        Ok(len != 0)
+    }
+}
+
+impl Read for ConvertedSource {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let read = self.frame.read(buf)?;
+
+            if read != 0 || !self.next_frame()? {
+                self.pos += read as u64;
+                return Ok(read);
+            }
+        }
+    }
+}
+
+impl Seek for ConvertedSource {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let target = match pos {
+            SeekFrom::Start(pos) => pos,
+            SeekFrom::Current(rel) => self.pos.wrapping_add(rel as u64),
+            SeekFrom::End(_) =>
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "Seeking from end not supported on converted sources.",
+                )),
+        };
+
+        let out_frame = target / STEREO_FRAME_BYTES as u64;
+        let in_frame = (out_frame as f64 * self.converter.step) as u64;
+        let in_frame_len = u64::from(self.channels.max(1)) * self.format.sample_len() as u64;
+
+        self.source.seek(SeekFrom::Start(in_frame * in_frame_len))?;
+        self.converter.reset();
+        self.frame = Default::default();
+        self.pos = out_frame * STEREO_FRAME_BYTES as u64;
+
+        Ok(self.pos)
+    }
+}
+
+impl MediaSource for ConvertedSource {
+    fn is_seekable(&self) -> bool {
+        self.source.is_seekable()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl Debug for ConvertedSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ConvertedSource")
+            .field("format", &self.format)
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field("quality", &self.converter.quality)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+impl From<ConvertedSource> for Input {
+    fn from(source: ConvertedSource) -> Self {
+        let metadata = Metadata {
+            channels: Some(source.channels),
+            sample_rate: Some(source.sample_rate),
+            ..Default::default()
+        };
+
+        let mut input = Input::float_pcm(true, Reader::Extension(Box::new(source)));
+        input.metadata = Box::new(metadata);
+
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(rate: u32, channels: usize, quality: ResampleQuality, input: &[f32]) -> Vec<f32> {
+        let mut converter = Converter::new(rate, channels, quality);
+        let mut bytes = vec![];
+
+        for block in input.chunks(channels * 100) {
+            converter.process(block, &mut bytes);
+        }
+
+        let mut bytes = &bytes[..];
+        let mut out = vec![];
+        while let Ok(sample) = bytes.read_f32::<LittleEndian>() {
+            out.push(sample);
+        }
+
+        out
+    }
+
+    #[test]
+    fn resampling_preserves_rate_and_dc() {
+        for quality in [
+            ResampleQuality::Low,
+            ResampleQuality::Medium,
+            ResampleQuality::High,
+        ] {
+            let out = convert(44_100, 1, quality, &[0.5; 44_100]);
+            let frames = out.len() / 2;
+
+            assert!(
+                (47_990..=48_000).contains(&frames),
+                "{:?}: {} frames",
+                quality,
+                frames
+            );
+
+            // Away from the start, a constant signal must remain constant.
+            for sample in &out[2 * SINC_HALF_TAPS..] {
+                assert!((sample - 0.5).abs() < 1e-4, "{:?}: {}", quality, sample);
+            }
+        }
+    }
+
+    #[test]
+    fn surround_downmix_cannot_clip() {
+        let out = convert(48_000, 6, ResampleQuality::Low, &[1.0; 6]);
+
+        assert_eq!(out.len(), 2);
+        assert!(out.iter().all(|s| *s <= 1.0 && *s > 0.5));
+    }
+
+    #[test]
+    fn zero_rate_is_not_resampled() {
+        let out = convert(0, 2, ResampleQuality::High, &[0.25; 2 * 480]);
+        assert_eq!(out.len(), 2 * 480);
+
+        let mut source = ConvertedSource::new(
+            Box::new(Cursor::new(vec![0u8; 4 * 480])),
+            SampleFormat::I16,
+            0,
+            2,
+            ResampleQuality::Low,
+        );
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes).unwrap();
+
+        assert_eq!(source.sample_rate, SAMPLE_RATE_RAW as u32);
+        assert_eq!(bytes.len(), 480 * STEREO_FRAME_BYTES);
+    }
+}
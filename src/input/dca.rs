@@ -1,15 +1,42 @@
-use super::{codec::OpusDecoderState, error::DcaError, Codec, Container, Input, Metadata, Reader};
-use serde::Deserialize;
-use std::{ffi::OsStr, mem};
+use super::{
+    cached::{self, OpusCompressor},
+    codec::OpusDecoderState,
+    error::DcaError,
+    Codec,
+    Container,
+    Input,
+    Metadata,
+    Reader,
+};
+use crate::constants::*;
+use audiopus::{coder::Encoder as OpusEncoder, Application, Bitrate, Error as OpusError};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    ffi::OsStr,
+    io::{ErrorKind as IoErrorKind, Result as IoResult, Write},
+    mem,
+    time::Duration,
+};
+use streamcatcher::{Transform, TransformPosition};
 use tokio::{fs::File as TokioFile, io::AsyncReadExt};
 
 /// Creates a streamed audio source from a DCA file.
 /// Currently only accepts the [DCA1 format](https://github.com/bwmarrin/dca).
 pub async fn dca<P: AsRef<OsStr>>(path: P) -> Result<Input, DcaError> {
-    _dca(path.as_ref()).await
+    _dca(path.as_ref()).await.map(|(input, _)| input)
 }
 
-async fn _dca(path: &OsStr) -> Result<Input, DcaError> {
+/// Opens a DCA file as in [`dca`], along with the [`DiskCache`] key it was stored under.
+///
+/// [`DiskCache`]: super::cached::DiskCache
+/// [`dca`]: dca()
+pub(crate) async fn dca_with_cache_key(path: &OsStr) -> Result<(Input, Option<String>), DcaError> {
+    _dca(path).await
+}
+
+async fn _dca(path: &OsStr) -> Result<(Input, Option<String>), DcaError> {
     let mut reader = TokioFile::open(path).await.map_err(DcaError::IoError)?;
 
     let mut header = [0u8; 4];
@@ -45,13 +72,14 @@ async fn _dca(path: &OsStr) -> Result<Input, DcaError> {
 
     let reader = json_reader.into_inner().into_std().await;
 
-    let metadata: Metadata = serde_json::from_slice::<DcaMetadata>(raw_json.as_slice())
-        .map_err(DcaError::InvalidMetadata)?
-        .into();
+    let dca_metadata = serde_json::from_slice::<DcaMetadata>(raw_json.as_slice())
+        .map_err(DcaError::InvalidMetadata)?;
+    let cache_key = dca_metadata.cache_key().map(String::from);
+    let metadata: Metadata = dca_metadata.into();
 
     let stereo = metadata.channels == Some(2);
 
-    Ok(Input::new(
+    let input = Input::new(
         stereo,
         Reader::from_file(reader),
         Codec::Opus(OpusDecoderState::new().map_err(DcaError::Opus)?),
@@ -59,28 +87,163 @@ async fn _dca(path: &OsStr) -> Result<Input, DcaError> {
             first_frame: (size as usize) + mem::size_of::<i32>() + header.len(),
         },
         Some(metadata),
-    ))
+    );
+
+    Ok((input, cache_key))
+}
+
+/// Encodes `source` using Opus at the given `bitrate`, writing it to `writer`
+/// as a [DCA1 file](https://github.com/bwmarrin/dca).
+///
+/// The whole of `source` is read and encoded before this returns, so it should
+/// not be given a live or endless stream. This is a blocking operation: async
+/// code should call it using [`spawn_blocking`].
+///
+/// The output can be played back using [`dca`], which allows the driver
+/// to send its Opus frames without any decoding or re-encoding.
+///
+/// [`spawn_blocking`]: tokio::task::spawn_blocking
+/// [`dca`]: dca()
+pub fn write_dca(source: Input, bitrate: Bitrate, writer: impl Write) -> Result<(), DcaError> {
+    let encoder = cached::new_encoder(source.stereo, bitrate).map_err(DcaError::Opus)?;
+
+    write_dca_with_encoder(source, encoder, writer)
+}
+
+/// Encodes `source` using a user-defined Opus encoder, writing it to `writer`
+/// as a [DCA1 file](https://github.com/bwmarrin/dca).
+///
+/// This behaves as [`write_dca`], and the same restrictions on `encoder` apply as
+/// for [`Compressed::with_encoder`].
+///
+/// [`Compressed::with_encoder`]: super::cached::Compressed::with_encoder
+pub fn write_dca_with_encoder(
+    source: Input,
+    encoder: OpusEncoder,
+    writer: impl Write,
+) -> Result<(), DcaError> {
+    write_keyed_dca(source, encoder, writer, None)
+}
+
+/// Encodes `source` as in [`write_dca_with_encoder`], recording the [`DiskCache`]
+/// key it is stored under (if any).
+///
+/// [`DiskCache`]: super::cached::DiskCache
+pub(crate) fn write_keyed_dca(
+    mut source: Input,
+    encoder: OpusEncoder,
+    mut writer: impl Write,
+    cache_key: Option<&str>,
+) -> Result<(), DcaError> {
+    let stereo = source.stereo;
+    let opus = Opus::from_encoder(&encoder, stereo).map_err(DcaError::Opus)?;
+    let metadata = source.metadata.take();
+
+    write_header(&mut writer, &DcaMetadata::new(&metadata, opus, cache_key))?;
+
+    let mut compressor = OpusCompressor::new(encoder, stereo);
+    let mut buf = [0u8; 4096];
+
+    loop {
+        match compressor.transform_read(&mut source, &mut buf) {
+            Ok(TransformPosition::Read(len)) =>
+                writer.write_all(&buf[..len]).map_err(DcaError::IoError)?,
+            Ok(TransformPosition::Finished) => break,
+            Err(e) if e.kind() == IoErrorKind::Interrupted => {},
+            Err(e) => return Err(DcaError::IoError(e)),
+        }
+    }
+
+    writer.flush().map_err(DcaError::IoError)
+}
+
+/// Writes the magic number and JSON metadata block which begin every DCA1 file.
+pub(crate) fn write_header(writer: &mut impl Write, header: &DcaMetadata) -> Result<(), DcaError> {
+    let raw_json = serde_json::to_vec(header).map_err(DcaError::InvalidMetadata)?;
+
+    let write = |writer: &mut dyn Write| -> IoResult<()> {
+        writer.write_all(b"DCA1")?;
+        writer.write_i32::<LittleEndian>(raw_json.len() as i32)?;
+        writer.write_all(&raw_json)
+    };
+
+    write(writer).map_err(DcaError::IoError)
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct DcaMetadata {
     pub(crate) dca: Dca,
     pub(crate) opus: Opus,
     pub(crate) info: Option<Info>,
     pub(crate) origin: Option<Origin>,
-    pub(crate) extra: Option<serde_json::Value>,
+    pub(crate) extra: Option<Value>,
+}
+
+/// Key within a DCA file's `extra` block holding the full [`Metadata`] of
+/// files written by songbird.
+const SONGBIRD_EXTRA_KEY: &str = "songbird_metadata";
+
+/// Key within a DCA file's `extra` block holding the [`DiskCache`] key of the entry,
+/// so that entries whose key hashes collide are never confused.
+///
+/// [`DiskCache`]: super::cached::DiskCache
+const CACHE_EXTRA_KEY: &str = "songbird_cache_key";
+
+impl DcaMetadata {
+    pub(crate) fn new(metadata: &Metadata, opus: Opus, cache_key: Option<&str>) -> Self {
+        let mut extra = json!({ SONGBIRD_EXTRA_KEY: StoredMetadata::from(metadata) });
+
+        if let Some(key) = cache_key {
+            extra[CACHE_EXTRA_KEY] = key.into();
+        }
+
+        Self {
+            dca: Dca {
+                version: 1,
+                tool: Tool {
+                    name: env!("CARGO_PKG_NAME").into(),
+                    version: env!("CARGO_PKG_VERSION").into(),
+                    url: env!("CARGO_PKG_HOMEPAGE").into(),
+                    author: "serenity-rs".into(),
+                },
+            },
+            origin: Some(Origin {
+                source: None,
+                abr: None,
+                channels: metadata.channels,
+                encoding: None,
+                url: metadata.source_url.clone(),
+            }),
+            info: Some(Info {
+                title: metadata.track.clone().or_else(|| metadata.title.clone()),
+                artist: metadata.artist.clone(),
+                album: None,
+                genre: None,
+                cover: metadata.thumbnail.clone(),
+            }),
+            opus,
+            extra: Some(extra),
+        }
+    }
+
+    /// Returns the [`DiskCache`] key this file was stored under, if it was written by one.
+    ///
+    /// [`DiskCache`]: super::cached::DiskCache
+    pub(crate) fn cache_key(&self) -> Option<&str> {
+        self.extra.as_ref()?.get(CACHE_EXTRA_KEY)?.as_str()
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Dca {
     pub(crate) version: u64,
     pub(crate) tool: Tool,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Tool {
     pub(crate) name: String,
     pub(crate) version: String,
@@ -89,18 +252,51 @@ pub(crate) struct Tool {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub(crate) struct Opus {
     pub(crate) mode: String,
     pub(crate) sample_rate: u32,
     pub(crate) frame_size: u64,
     pub(crate) abr: u64,
-    pub(crate) vbr: u64,
+    pub(crate) vbr: Vbr,
     pub(crate) channels: u8,
 }
 
+impl Opus {
+    pub(crate) fn from_encoder(encoder: &OpusEncoder, stereo: bool) -> Result<Self, OpusError> {
+        let mode = match encoder.application()? {
+            Application::Voip => "voip",
+            Application::Audio => "audio",
+            Application::LowDelay => "lowdelay",
+        };
+
+        let abr = match encoder.bitrate()? {
+            Bitrate::BitsPerSecond(i) => i as u64,
+            Bitrate::Auto | Bitrate::Max => 0,
+        };
+
+        Ok(Self {
+            mode: mode.into(),
+            sample_rate: SAMPLE_RATE_RAW as u32,
+            frame_size: MONO_FRAME_SIZE as u64,
+            abr,
+            vbr: Vbr::Flag(encoder.vbr()?),
+            channels: if stereo { 2 } else { 1 },
+        })
+    }
+}
+
+/// The DCA1 spec stores `vbr` as a boolean, but some encoders write an integer.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum Vbr {
+    Flag(bool),
+    Int(u64),
+}
+
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Info {
     pub(crate) title: Option<String>,
     pub(crate) artist: Option<String>,
@@ -110,7 +306,7 @@ pub(crate) struct Info {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Origin {
     pub(crate) source: Option<String>,
     pub(crate) abr: Option<u64>,
@@ -119,17 +315,71 @@ pub(crate) struct Origin {
     pub(crate) url: Option<String>,
 }
 
+/// The fields of [`Metadata`] which don't have a place in the DCA1 spec.
+#[derive(Debug, Deserialize, Serialize)]
+struct StoredMetadata {
+    track: Option<String>,
+    artist: Option<String>,
+    date: Option<String>,
+    channel: Option<String>,
+    start_time: Option<f64>,
+    duration: Option<f64>,
+    source_url: Option<String>,
+    title: Option<String>,
+    thumbnail: Option<String>,
+}
+
+impl From<&Metadata> for StoredMetadata {
+    fn from(m: &Metadata) -> Self {
+        Self {
+            track: m.track.clone(),
+            artist: m.artist.clone(),
+            date: m.date.clone(),
+            channel: m.channel.clone(),
+            start_time: m.start_time.map(|t| t.as_secs_f64()),
+            duration: m.duration.map(|t| t.as_secs_f64()),
+            source_url: m.source_url.clone(),
+            title: m.title.clone(),
+            thumbnail: m.thumbnail.clone(),
+        }
+    }
+}
+
 impl From<DcaMetadata> for Metadata {
     fn from(mut d: DcaMetadata) -> Self {
+        let channels = Some(d.opus.channels);
+        let sample_rate = Some(d.opus.sample_rate);
+
+        let stored = d
+            .extra
+            .as_mut()
+            .and_then(|extra| extra.get_mut(SONGBIRD_EXTRA_KEY))
+            .map(Value::take)
+            .and_then(|v| serde_json::from_value::<StoredMetadata>(v).ok());
+
+        if let Some(m) = stored {
+            return Self {
+                track: m.track,
+                artist: m.artist,
+                date: m.date,
+
+                channels,
+                channel: m.channel,
+                start_time: m.start_time.map(Duration::from_secs_f64),
+                duration: m.duration.map(Duration::from_secs_f64),
+                sample_rate,
+                source_url: m.source_url,
+                title: m.title,
+                thumbnail: m.thumbnail,
+            };
+        }
+
         let (track, artist) = d
             .info
             .take()
             .map(|mut m| (m.title.take(), m.artist.take()))
             .unwrap_or_else(|| (None, None));
 
-        let channels = Some(d.opus.channels);
-        let sample_rate = Some(d.opus.sample_rate);
-
         Self {
             track,
             artist,
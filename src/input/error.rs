@@ -2,9 +2,13 @@
 
 use audiopus::Error as OpusError;
 use core::fmt;
+#[cfg(feature = "http")]
+use reqwest::Error as ReqwestError;
 use serde_json::{Error as JsonError, Value};
 use std::{error::Error as StdError, io::Error as IoError, process::Output};
 use streamcatcher::CatcherError;
+#[cfg(feature = "symphonia")]
+use symphonia::core::errors::Error as SymphoniaError;
 
 /// An error returned when creating a new [`Input`].
 ///
@@ -12,8 +16,13 @@ use streamcatcher::CatcherError;
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error {
+    /// An error occurred while opening a new Ogg or WebM source.
+    Container(ContainerError),
     /// An error occurred while opening a new DCA source.
     Dca(DcaError),
+    /// An error occurred while opening a remote source over HTTP(S).
+    #[cfg(feature = "http")]
+    Http(ReqwestError),
     /// An error occurred while reading, or opening a file.
     Io(IoError),
     /// An error occurred while parsing JSON (i.e., during metadata/stereo detection).
@@ -33,6 +42,9 @@ pub enum Error {
     Streams,
     /// Configuration error for a cached Input.
     Streamcatcher(CatcherError),
+    /// An error occurred while probing or decoding a source using symphonia.
+    #[cfg(feature = "symphonia")]
+    Symphonia(SymphoniaError),
     /// An error occurred while processing the JSON output from `youtube-dl`.
     ///
     /// The JSON output is given.
@@ -51,12 +63,32 @@ impl From<CatcherError> for Error {
     }
 }
 
+#[cfg(feature = "symphonia")]
+impl From<SymphoniaError> for Error {
+    fn from(e: SymphoniaError) -> Self {
+        Error::Symphonia(e)
+    }
+}
+
+impl From<ContainerError> for Error {
+    fn from(e: ContainerError) -> Self {
+        Error::Container(e)
+    }
+}
+
 impl From<DcaError> for Error {
     fn from(e: DcaError) -> Self {
         Error::Dca(e)
     }
 }
 
+#[cfg(feature = "http")]
+impl From<ReqwestError> for Error {
+    fn from(e: ReqwestError) -> Error {
+        Error::Http(e)
+    }
+}
+
 impl From<IoError> for Error {
     fn from(e: IoError) -> Error {
         Error::Io(e)
@@ -72,7 +104,10 @@ impl From<OpusError> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Error::Container(_) => write!(f, "opening Ogg/WebM file failed"),
             Error::Dca(_) => write!(f, "opening file DCA failed"),
+            #[cfg(feature = "http")]
+            Error::Http(e) => e.fmt(f),
             Error::Io(e) => e.fmt(f),
             Error::Json {
                 error: _,
@@ -83,6 +118,8 @@ impl fmt::Display for Error {
             Error::Stdout => write!(f, "creating stdout failed"),
             Error::Streams => write!(f, "checking if path is stereo failed"),
             Error::Streamcatcher(_) => write!(f, "invalid config for cached input"),
+            #[cfg(feature = "symphonia")]
+            Error::Symphonia(e) => e.fmt(f),
             Error::YouTubeDlProcessing(_) => write!(f, "youtube-dl returned invalid JSON"),
             Error::YouTubeDlRun(o) => write!(f, "youtube-dl encontered an error: {:?}", o),
             Error::YouTubeDlUrl(_) => write!(f, "missing youtube-dl url"),
@@ -93,7 +130,10 @@ impl fmt::Display for Error {
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
+            Error::Container(e) => Some(e),
             Error::Dca(e) => Some(e),
+            #[cfg(feature = "http")]
+            Error::Http(e) => Some(e),
             Error::Io(e) => e.source(),
             Error::Json {
                 error,
@@ -104,6 +144,8 @@ impl StdError for Error {
             Error::Stdout => None,
             Error::Streams => None,
             Error::Streamcatcher(e) => Some(e),
+            #[cfg(feature = "symphonia")]
+            Error::Symphonia(e) => Some(e),
             Error::YouTubeDlProcessing(_) => None,
             Error::YouTubeDlRun(_) => None,
             Error::YouTubeDlUrl(_) => None,
@@ -125,7 +167,7 @@ pub enum DcaError {
     InvalidMetadata(JsonError),
     /// The file's header reported an invalid metadata block size.
     InvalidSize(i32),
-    /// An error was encountered while creating a new Opus decoder.
+    /// An error was encountered while creating a new Opus decoder or encoder.
     Opus(OpusError),
 }
 
@@ -153,6 +195,51 @@ impl StdError for DcaError {
     }
 }
 
+/// An error returned from the [`ogg`] and [`webm`] methods.
+///
+/// [`ogg`]: crate::input::ogg()
+/// [`webm`]: crate::input::webm()
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ContainerError {
+    /// An error occurred while reading, or opening a file.
+    IoError(IoError),
+    /// The file opened did not have a valid Ogg or EBML header.
+    InvalidHeader,
+    /// The file did not contain an Opus audio stream.
+    MissingOpusStream,
+    /// An error was encountered while creating a new Opus decoder or encoder.
+    Opus(OpusError),
+}
+
+impl From<IoError> for ContainerError {
+    fn from(e: IoError) -> Self {
+        ContainerError::IoError(e)
+    }
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::IoError(e) => e.fmt(f),
+            ContainerError::InvalidHeader => write!(f, "invalid header"),
+            ContainerError::MissingOpusStream => write!(f, "no Opus stream found"),
+            ContainerError::Opus(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for ContainerError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ContainerError::IoError(e) => e.source(),
+            ContainerError::InvalidHeader => None,
+            ContainerError::MissingOpusStream => None,
+            ContainerError::Opus(e) => e.source(),
+        }
+    }
+}
+
 /// Convenience type for fallible return of [`Input`]s.
 ///
 /// [`Input`]: crate::input::Input
@@ -0,0 +1,469 @@
+use super::{error::Result, reader::MediaSource, symphonia_source, Input};
+use bytes::Bytes;
+use flume::{Receiver, Sender, TryRecvError};
+use reqwest::{
+    header::{CONTENT_RANGE, CONTENT_TYPE, RANGE},
+    Client,
+    Response,
+    StatusCode,
+};
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    io::{
+        self,
+        Error as IoError,
+        ErrorKind as IoErrorKind,
+        Read,
+        Result as IoResult,
+        Seek,
+        SeekFrom,
+    },
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{runtime::Handle, task::JoinHandle, time::sleep};
+use tracing::debug;
+use url::Url;
+
+/// Number of response chunks fetched ahead of the current read position.
+const READ_AHEAD_CHUNKS: usize = 64;
+
+/// Number of consecutive failed attempts to resume a dropped stream before giving up.
+const MAX_RETRIES: u32 = 5;
+
+/// Base delay between attempts to resume a dropped stream, multiplied by the attempt count.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Streams and decodes a remote audio file over HTTP(S), using a new [`Client`].
+///
+/// See [`http_with_client`] for details.
+///
+/// [`Client`]: reqwest::Client
+pub async fn http(url: impl AsRef<str>) -> Result<Input> {
+    http_with_client(Client::new(), url).await
+}
+
+/// Streams and decodes a remote audio file over HTTP(S).
+///
+/// Audio is decoded in-process using [`symphonia`], so neither `youtube-dl` nor
+/// `ffmpeg` are required. If the server accepts range requests, then the returned
+/// source supports seeking directly, and resumes from the current position if the
+/// connection drops mid-stream.
+///
+/// Reuse one `client` between sources where possible, to share its connection pool.
+///
+/// This requires the `"http"` feature.
+///
+/// [`symphonia`]: super::symphonia
+pub async fn http_with_client(client: Client, url: impl AsRef<str>) -> Result<Input> {
+    let stream = HttpStream::new(client, url.as_ref()).await?;
+    let extension = stream.extension_hint();
+
+    symphonia_source(Box::new(stream), extension).await
+}
+
+/// A byte stream over HTTP(S), usable as a [`MediaSource`].
+///
+/// Response data is fetched ahead of the read position by a task on the async
+/// runtime. Reads block until the next chunk of data arrives.
+///
+/// If the server accepts range requests, then this source supports seeking
+/// (forward seeks into data which has already arrived are served from the buffer),
+/// and dropped connections are resumed from the last byte received.
+///
+/// [`MediaSource`]: super::reader::MediaSource
+pub struct HttpStream {
+    remote: Arc<Remote>,
+    handle: Handle,
+    content_type: Option<String>,
+    position: u64,
+    chunk: Bytes,
+    rx: Receiver<IoResult<Bytes>>,
+    fetcher: JoinHandle<()>,
+}
+
+struct Remote {
+    client: Client,
+    url: String,
+    len: Option<u64>,
+    seekable: bool,
+}
+
+impl HttpStream {
+    /// Opens a stream to `url`, and begins fetching its contents.
+    ///
+    /// This must be called from within a Tokio runtime.
+    pub async fn new(client: Client, url: impl Into<String>) -> Result<Self> {
+        let url = url.into();
+
+        let response = client
+            .get(&url)
+            .header(RANGE, "bytes=0-")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let seekable = response.status() == StatusCode::PARTIAL_CONTENT;
+        let len = if seekable {
+            content_range_total(&response)
+        } else {
+            response.content_length()
+        };
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let remote = Arc::new(Remote {
+            client,
+            url,
+            len,
+            seekable,
+        });
+        let handle = Handle::current();
+
+        let (tx, rx) = flume::bounded(READ_AHEAD_CHUNKS);
+        let fetcher = handle.spawn(fetch(remote.clone(), 0, Some(response), tx));
+
+        Ok(Self {
+            remote,
+            handle,
+            content_type,
+            position: 0,
+            chunk: Bytes::new(),
+            rx,
+            fetcher,
+        })
+    }
+
+    /// Guesses the file extension of this stream's contents from its URL or `Content-Type`,
+    /// to speed up format detection.
+    fn extension_hint(&self) -> Option<String> {
+        let from_url = Url::parse(&self.remote.url).ok().and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .and_then(|name| name.rsplit_once('.'))
+                .map(|(_, ext)| ext.to_lowercase())
+        });
+
+        from_url.or_else(|| {
+            let mime = self.content_type.as_deref()?;
+            let ext = match mime.split(';').next()?.trim() {
+                "audio/mpeg" | "audio/mp3" => "mp3",
+                "audio/aac" | "audio/aacp" => "aac",
+                "audio/mp4" | "audio/x-m4a" => "m4a",
+                "audio/flac" | "audio/x-flac" => "flac",
+                "audio/ogg" | "application/ogg" => "ogg",
+                "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+                _ => return None,
+            };
+
+            Some(ext.to_string())
+        })
+    }
+
+    /// Discards any buffered data, and begins fetching from `offset`.
+    fn restart(&mut self, offset: u64) {
+        self.fetcher.abort();
+
+        let (tx, rx) = flume::bounded(READ_AHEAD_CHUNKS);
+        self.fetcher = self
+            .handle
+            .spawn(fetch(self.remote.clone(), offset, None, tx));
+        self.rx = rx;
+        self.chunk = Bytes::new();
+        self.position = offset;
+    }
+}
+
+impl Remote {
+    async fn request(&self, offset: u64) -> IoResult<Response> {
+        let response = self
+            .client
+            .get(&self.url)
+            .header(RANGE, format!("bytes={}-", offset))
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+            .map_err(|e| IoError::new(IoErrorKind::Other, e))?;
+
+        if response.status() == StatusCode::PARTIAL_CONTENT {
+            Ok(response)
+        } else {
+            Err(IoError::new(
+                IoErrorKind::InvalidData,
+                "Server ignored HTTP range request.",
+            ))
+        }
+    }
+}
+
+/// Reads the total resource length from a `Content-Range` header (i.e., `bytes 0-99/1234`).
+fn content_range_total(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Passes response data from `offset` onwards into `tx`, resuming the
+/// stream with a new range request if the connection drops.
+async fn fetch(
+    remote: Arc<Remote>,
+    mut offset: u64,
+    mut response: Option<Response>,
+    tx: Sender<IoResult<Bytes>>,
+) {
+    let mut failures = 0;
+
+    loop {
+        let error = match response.take() {
+            Some(mut live) => loop {
+                match live.chunk().await {
+                    Ok(Some(bytes)) => {
+                        offset += bytes.len() as u64;
+                        failures = 0;
+
+                        if tx.send_async(Ok(bytes)).await.is_err() {
+                            return;
+                        }
+                    },
+                    Ok(None) if !matches!(remote.len, Some(len) if offset < len) => return,
+                    Ok(None) =>
+                        break IoError::new(
+                            IoErrorKind::UnexpectedEof,
+                            "HTTP response ended before end of stream.",
+                        ),
+                    Err(e) => break IoError::new(IoErrorKind::Other, e),
+                }
+            },
+            None => match remote.request(offset).await {
+                Ok(live) => {
+                    response = Some(live);
+                    continue;
+                },
+                Err(e) => e,
+            },
+        };
+
+        failures += 1;
+        if !remote.seekable || failures > MAX_RETRIES {
+            let _ = tx.send_async(Err(error)).await;
+            return;
+        }
+
+        debug!(
+            "HTTP stream interrupted at byte {}, reconnecting: {}",
+            offset, error
+        );
+        sleep(RETRY_DELAY * failures).await;
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        while self.chunk.is_empty() {
+            match self.rx.recv() {
+                Ok(Ok(bytes)) => self.chunk = bytes,
+                Ok(Err(e)) => return Err(e),
+                // The fetcher only hangs up after reaching the end of the stream.
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = buffer.len().min(self.chunk.len());
+        buffer[..len].copy_from_slice(&self.chunk.split_to(len));
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl Seek for HttpStream {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => offset_by(self.position, delta),
+            SeekFrom::End(delta) => self.remote.len.and_then(|len| offset_by(len, delta)),
+        }
+        .ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "Invalid seek position, or stream length unknown.",
+            )
+        })?;
+
+        while target > self.position {
+            if self.chunk.is_empty() {
+                match self.rx.try_recv() {
+                    Ok(Ok(bytes)) => self.chunk = bytes,
+                    Ok(Err(e)) => return Err(e),
+                    Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+                }
+            }
+
+            let len = (target - self.position).min(self.chunk.len() as u64);
+            let _ = self.chunk.split_to(len as usize);
+            self.position += len;
+        }
+
+        if target == self.position {
+            Ok(target)
+        } else if self.remote.seekable {
+            self.restart(target);
+            Ok(target)
+        } else if target > self.position {
+            let remaining = target - self.position;
+            io::copy(&mut Read::by_ref(self).take(remaining), &mut io::sink())?;
+            Ok(self.position)
+        } else {
+            Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "Server does not support seeking backwards (no HTTP range requests).",
+            ))
+        }
+    }
+}
+
+fn offset_by(base: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.unsigned_abs())
+    }
+}
+
+impl MediaSource for HttpStream {
+    fn is_seekable(&self) -> bool {
+        self.remote.seekable
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.remote.len
+    }
+}
+
+impl Drop for HttpStream {
+    fn drop(&mut self) {
+        self.fetcher.abort();
+    }
+}
+
+impl Debug for HttpStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("HttpStream")
+            .field("url", &self.remote.url)
+            .field("len", &self.remote.len)
+            .field("seekable", &self.remote.seekable)
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Serves `data` over HTTP/1.1 with range support, closing the first
+    /// connection after `fail_after` bytes of its body.
+    fn serve(data: Vec<u8>, fail_after: Option<usize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/audio.raw", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            let mut fail_after = fail_after;
+
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut start = 0;
+
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some(range) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        start = range.trim().trim_end_matches('-').parse().unwrap();
+                    }
+                }
+
+                let body = &data[start..];
+                let header = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                    body.len(),
+                    start,
+                    data.len() - 1,
+                    data.len(),
+                );
+                let sent = fail_after.take().unwrap_or(body.len()).min(body.len());
+
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(&body[..sent]);
+            }
+        });
+
+        url
+    }
+
+    fn test_data() -> Vec<u8> {
+        (0..1 << 18).map(|i: u32| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn resumes_dropped_connection() {
+        let data = test_data();
+        let url = serve(data.clone(), Some(10_000));
+
+        let mut stream = HttpStream::new(Client::new(), url).await.unwrap();
+        assert!(MediaSource::is_seekable(&stream));
+        assert_eq!(stream.byte_len(), Some(data.len() as u64));
+
+        let out = tokio::task::spawn_blocking(move || {
+            let mut out = vec![];
+            stream.read_to_end(&mut out).unwrap();
+            out
+        })
+        .await
+        .unwrap();
+
+        assert!(out == data);
+    }
+
+    #[tokio::test]
+    async fn seeks_with_range_requests() {
+        let data = test_data();
+        let url = serve(data.clone(), None);
+
+        let mut stream = HttpStream::new(Client::new(), url).await.unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            let mut buf = [0u8; 1000];
+
+            stream.seek(SeekFrom::Start(200_000)).unwrap();
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..], data[200_000..201_000]);
+
+            stream.seek(SeekFrom::End(-1000)).unwrap();
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..], data[data.len() - 1000..]);
+
+            stream.seek(SeekFrom::Start(5)).unwrap();
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..], data[5..1005]);
+        })
+        .await
+        .unwrap();
+    }
+}
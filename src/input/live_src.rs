@@ -0,0 +1,870 @@
+use super::{error::Result, reader::MediaSource, symphonia_source, Input, Metadata};
+use bytes::Bytes;
+use flume::{Receiver, Sender};
+use parking_lot::Mutex;
+use reqwest::{header::CONTENT_TYPE, Client, Response};
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Seek, SeekFrom},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::debug;
+use url::Url;
+
+/// Number of response chunks (or HLS segments) fetched ahead of playback.
+const READ_AHEAD_CHUNKS: usize = 64;
+
+/// Number of consecutive failed requests before a live stream is abandoned.
+const MAX_RETRIES: u32 = 5;
+
+/// Base delay between reconnection attempts, multiplied by the attempt count.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Number of segments from the end of a live HLS playlist at which playback starts.
+const LIVE_EDGE_SEGMENTS: usize = 3;
+
+/// Plays an Icecast or SHOUTcast radio stream, using a new [`Client`].
+///
+/// See [`icy_with_client`] for details.
+///
+/// [`Client`]: reqwest::Client
+pub async fn icy(url: impl AsRef<str>) -> Result<Input> {
+    icy_with_client(Client::new(), url).await
+}
+
+/// Plays an Icecast or SHOUTcast radio stream.
+///
+/// Audio is decoded in-process using [`symphonia`], and the stream is reconnected
+/// if it drops. The station name (`icy-name`) is reported as [`Metadata::channel`].
+/// In-band `StreamTitle` updates set [`Metadata::title`] (and [`Metadata::artist`]
+/// and [`Metadata::track`], if the title has the form `"Artist - Track"`), and fire
+/// a [`TrackEvent::MetadataUpdate`] event while the stream is playing.
+///
+/// The server must respond using HTTP/1.x: legacy `ICY 200 OK` responses are
+/// not supported. Live streams do not support seeking.
+///
+/// This requires the `"http"` feature.
+///
+/// [`symphonia`]: super::symphonia
+/// [`TrackEvent::MetadataUpdate`]: crate::events::TrackEvent::MetadataUpdate
+pub async fn icy_with_client(client: Client, url: impl AsRef<str>) -> Result<Input> {
+    let url = url.as_ref().to_string();
+    let response = icy_request(&client, &url).await?;
+
+    let content_type = header_str(&response, CONTENT_TYPE.as_str());
+    let extension = content_type.as_deref().and_then(mime_extension);
+
+    let (titles, updates) = TitleUpdates::new(Metadata {
+        channel: header_str(&response, "icy-name"),
+        source_url: Some(url.clone()),
+        ..Default::default()
+    });
+    let template = titles.template.clone();
+
+    let stream = LiveStream::spawn(|tx| icy_fetch(client, url, response, titles, tx));
+    let input = symphonia_source(Box::new(stream), extension.map(str::to_string)).await?;
+
+    Ok(finish_input(input, &template, updates))
+}
+
+/// Plays an HTTP Live Streaming (HLS) playlist, using a new [`Client`].
+///
+/// See [`hls_with_client`] for details.
+///
+/// [`Client`]: reqwest::Client
+pub async fn hls(url: impl AsRef<str>) -> Result<Input> {
+    hls_with_client(Client::new(), url).await
+}
+
+/// Plays an HTTP Live Streaming (HLS) playlist.
+///
+/// If `url` points to a master playlist, the audio-only variant with the highest
+/// bandwidth is chosen. Live playlists are refreshed as new segments are published, with playback
+/// starting a few segments behind the live edge. Segment titles (from `#EXTINF`) set
+/// [`Metadata::title`] and fire a [`TrackEvent::MetadataUpdate`] event when they change.
+///
+/// Segments are decoded in-process using [`symphonia`], and so must contain packed
+/// audio (i.e., ADTS AAC or MP3), as used by most radio streams. MPEG-TS, fragmented MP4
+/// (i.e., using `#EXT-X-MAP`) and encrypted segments are not supported. Live streams do not support seeking.
+///
+/// This requires the `"http"` feature.
+///
+/// [`symphonia`]: super::symphonia
+/// [`TrackEvent::MetadataUpdate`]: crate::events::TrackEvent::MetadataUpdate
+pub async fn hls_with_client(client: Client, url: impl AsRef<str>) -> Result<Input> {
+    let mut url = Url::parse(url.as_ref()).map_err(invalid_data)?;
+
+    let playlist = loop {
+        match Playlist::parse(&url, &fetch_text(&client, &url).await?)? {
+            Playlist::Master(variants) => {
+                url = choose_variant(variants).ok_or_else(|| {
+                    invalid_data("HLS master playlist has no audio-only variants.")
+                })?;
+            },
+            Playlist::Media(media) => break media,
+        }
+    };
+
+    let extension = playlist
+        .segments
+        .first()
+        .and_then(|s| s.uri.path().rsplit_once('.'))
+        .map(|(_, ext)| ext.to_lowercase());
+
+    let (titles, updates) = TitleUpdates::new(Metadata {
+        source_url: Some(url.to_string()),
+        ..Default::default()
+    });
+    let template = titles.template.clone();
+
+    let stream = LiveStream::spawn(|tx| hls_fetch(client, url, playlist, titles, tx));
+    let input = symphonia_source(Box::new(stream), extension).await?;
+
+    Ok(finish_input(input, &template, updates))
+}
+
+/// Merges format details found by the decoder into a live source's metadata,
+/// and attaches the stream of updates.
+fn finish_input(
+    mut input: Input,
+    template: &Mutex<Metadata>,
+    updates: Receiver<Metadata>,
+) -> Input {
+    let mut template = template.lock();
+    template.channels = input.metadata.channels;
+    template.sample_rate = input.metadata.sample_rate;
+
+    *input.metadata = template.clone();
+
+    // Any titles seen while probing the stream are already included.
+    updates.drain();
+
+    input.with_metadata_updates(updates)
+}
+
+/// Produces a new [`Metadata`] whenever a live stream's title changes.
+struct TitleUpdates {
+    template: Arc<Mutex<Metadata>>,
+    tx: Sender<Metadata>,
+    last: Option<String>,
+}
+
+impl TitleUpdates {
+    fn new(base: Metadata) -> (Self, Receiver<Metadata>) {
+        let (tx, rx) = flume::unbounded();
+
+        let updates = Self {
+            template: Arc::new(Mutex::new(base)),
+            tx,
+            last: None,
+        };
+
+        (updates, rx)
+    }
+
+    fn update(&mut self, title: &str) {
+        if self.last.as_deref() == Some(title) {
+            return;
+        }
+
+        let mut metadata = self.template.lock();
+        let (artist, track) = match title.split_once(" - ") {
+            Some((artist, track)) => (Some(artist.to_string()), track),
+            None => (None, title),
+        };
+
+        metadata.title = Some(title.to_string());
+        metadata.artist = artist;
+        metadata.track = Some(track.to_string());
+
+        let _ = self.tx.send(metadata.clone());
+        self.last = Some(title.to_string());
+    }
+}
+
+/// A non-seekable byte stream, filled by a task on the async runtime.
+struct LiveStream {
+    rx: Receiver<IoResult<Bytes>>,
+    chunk: Bytes,
+    task: JoinHandle<()>,
+}
+
+impl LiveStream {
+    fn spawn<F, Fut>(fetcher: F) -> Self
+    where
+        F: FnOnce(Sender<IoResult<Bytes>>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (tx, rx) = flume::bounded(READ_AHEAD_CHUNKS);
+
+        Self {
+            rx,
+            chunk: Bytes::new(),
+            task: tokio::spawn(fetcher(tx)),
+        }
+    }
+}
+
+impl Read for LiveStream {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        while self.chunk.is_empty() {
+            match self.rx.recv() {
+                Ok(Ok(bytes)) => self.chunk = bytes,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = buffer.len().min(self.chunk.len());
+        buffer[..len].copy_from_slice(&self.chunk.split_to(len));
+
+        Ok(len)
+    }
+}
+
+impl Seek for LiveStream {
+    fn seek(&mut self, _pos: SeekFrom) -> IoResult<u64> {
+        Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            "Seeking not supported on live streams.",
+        ))
+    }
+}
+
+impl MediaSource for LiveStream {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl Drop for LiveStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Debug for LiveStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("LiveStream").finish()
+    }
+}
+
+fn invalid_data<E>(e: E) -> IoError
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    IoError::new(IoErrorKind::InvalidData, e)
+}
+
+fn request_error(e: reqwest::Error) -> IoError {
+    IoError::new(IoErrorKind::Other, e)
+}
+
+fn header_str(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn mime_extension(mime: &str) -> Option<&'static str> {
+    match mime.split(';').next()?.trim() {
+        "audio/mpeg" | "audio/mp3" => Some("mp3"),
+        "audio/aac" | "audio/aacp" => Some("aac"),
+        "audio/flac" | "audio/x-flac" => Some("flac"),
+        "audio/ogg" | "application/ogg" => Some("ogg"),
+        _ => None,
+    }
+}
+
+async fn icy_request(client: &Client, url: &str) -> reqwest::Result<Response> {
+    client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .send()
+        .await?
+        .error_for_status()
+}
+
+/// Passes audio from an ICY stream into `tx`, reconnecting whenever the stream drops.
+async fn icy_fetch(
+    client: Client,
+    url: String,
+    response: Response,
+    mut titles: TitleUpdates,
+    tx: Sender<IoResult<Bytes>>,
+) {
+    let mut response = Some(response);
+    let mut failures = 0;
+
+    loop {
+        let error = match response.take() {
+            Some(mut live) => {
+                let metaint = header_str(&live, "icy-metaint").and_then(|v| v.parse().ok());
+                let mut demuxer = IcyDemuxer::new(metaint);
+
+                loop {
+                    match live.chunk().await {
+                        Ok(Some(bytes)) => {
+                            failures = 0;
+
+                            let mut audio = Vec::with_capacity(bytes.len());
+                            if let Some(title) = demuxer.push(&bytes, &mut audio) {
+                                titles.update(&title);
+                            }
+
+                            if !audio.is_empty() && tx.send_async(Ok(audio.into())).await.is_err() {
+                                return;
+                            }
+                        },
+                        Ok(None) => break invalid_data("ICY stream ended."),
+                        Err(e) => break request_error(e),
+                    }
+                }
+            },
+            None => match icy_request(&client, &url).await {
+                Ok(live) => {
+                    response = Some(live);
+                    continue;
+                },
+                Err(e) => request_error(e),
+            },
+        };
+
+        failures += 1;
+        if failures > MAX_RETRIES {
+            let _ = tx.send_async(Err(error)).await;
+            return;
+        }
+
+        debug!("ICY stream interrupted, reconnecting: {}", error);
+        sleep(RETRY_DELAY * failures).await;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum IcyState {
+    /// Bytes of audio remaining until the next metadata block.
+    Audio(usize),
+    /// The next byte gives the length of a metadata block.
+    Length,
+    /// Bytes remaining in the current metadata block.
+    Metadata(usize),
+}
+
+/// Separates interleaved audio and metadata blocks in an ICY stream.
+struct IcyDemuxer {
+    metaint: Option<usize>,
+    state: IcyState,
+    block: Vec<u8>,
+}
+
+impl IcyDemuxer {
+    fn new(metaint: Option<usize>) -> Self {
+        Self {
+            metaint,
+            state: IcyState::Audio(metaint.unwrap_or_default()),
+            block: vec![],
+        }
+    }
+
+    /// Appends the audio in `data` to `audio`, returning the newest
+    /// `StreamTitle` found in any metadata blocks.
+    fn push(&mut self, mut data: &[u8], audio: &mut Vec<u8>) -> Option<String> {
+        let metaint = match self.metaint {
+            Some(metaint) if metaint > 0 => metaint,
+            _ => {
+                audio.extend_from_slice(data);
+                return None;
+            },
+        };
+
+        let mut title = None;
+
+        while !data.is_empty() {
+            match self.state {
+                IcyState::Audio(left) => {
+                    let len = left.min(data.len());
+                    audio.extend_from_slice(&data[..len]);
+                    data = &data[len..];
+
+                    self.state = if len == left {
+                        IcyState::Length
+                    } else {
+                        IcyState::Audio(left - len)
+                    };
+                },
+                IcyState::Length => {
+                    let len = usize::from(data[0]) * 16;
+                    data = &data[1..];
+
+                    self.block.clear();
+                    self.state = if len == 0 {
+                        IcyState::Audio(metaint)
+                    } else {
+                        IcyState::Metadata(len)
+                    };
+                },
+                IcyState::Metadata(left) => {
+                    let len = left.min(data.len());
+                    self.block.extend_from_slice(&data[..len]);
+                    data = &data[len..];
+
+                    self.state = if len == left {
+                        title = stream_title(&self.block).or(title);
+                        IcyState::Audio(metaint)
+                    } else {
+                        IcyState::Metadata(left - len)
+                    };
+                },
+            }
+        }
+
+        title
+    }
+}
+
+/// Extracts the `StreamTitle` field from an ICY metadata block,
+/// i.e., `StreamTitle='Artist - Track';StreamUrl='';`.
+fn stream_title(block: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(block);
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    let end = rest
+        .find("';")
+        .unwrap_or_else(|| rest.trim_end_matches('\0').len());
+    let title = rest[..end].trim();
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.to_string())
+    }
+}
+
+#[derive(Debug)]
+enum Playlist {
+    Master(Vec<Variant>),
+    Media(MediaPlaylist),
+}
+
+#[derive(Debug)]
+struct Variant {
+    bandwidth: u64,
+    codecs: Option<String>,
+    uri: Url,
+}
+
+impl Variant {
+    /// Returns whether this variant holds only (packed) audio, if its codecs are listed.
+    ///
+    /// Both AAC and MP3 use `mp4a` codec strings.
+    fn audio_only(&self) -> Option<bool> {
+        self.codecs
+            .as_ref()
+            .map(|codecs| codecs.split(',').all(|c| c.trim().starts_with("mp4a.")))
+    }
+}
+
+/// Picks the highest-bandwidth audio-only variant, falling back to those which
+/// do not list their codecs.
+fn choose_variant(variants: Vec<Variant>) -> Option<Url> {
+    let (audio, unknown): (Vec<_>, Vec<_>) = variants
+        .into_iter()
+        .filter(|v| v.audio_only() != Some(false))
+        .partition(|v| v.audio_only() == Some(true));
+
+    let candidates = if audio.is_empty() { unknown } else { audio };
+
+    candidates
+        .into_iter()
+        .max_by_key(|v| v.bandwidth)
+        .map(|v| v.uri)
+}
+
+/// Splits an M3U8 attribute list into names and values, removing any quotes.
+fn attributes(mut list: &str) -> Vec<(&str, &str)> {
+    let mut out = vec![];
+
+    while let Some((name, rest)) = list.split_once('=') {
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(',').unwrap_or((rest, "")),
+        };
+
+        out.push((name.trim(), value));
+        list = rest.strip_prefix(',').unwrap_or(rest);
+    }
+
+    out
+}
+
+#[derive(Debug)]
+struct MediaPlaylist {
+    media_sequence: u64,
+    target_duration: Duration,
+    segments: Vec<Segment>,
+    ended: bool,
+}
+
+#[derive(Debug)]
+struct Segment {
+    uri: Url,
+    title: Option<String>,
+}
+
+impl Playlist {
+    /// Parses an M3U8 playlist, resolving URIs relative to `base`.
+    fn parse(base: &Url, text: &str) -> IoResult<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+
+        if lines.next() != Some("#EXTM3U") {
+            return Err(invalid_data("Not an M3U8 playlist."));
+        }
+
+        let mut variants = vec![];
+        let mut media = MediaPlaylist {
+            media_sequence: 0,
+            target_duration: Duration::from_secs(10),
+            segments: vec![],
+            ended: false,
+        };
+
+        let mut stream_inf = None;
+        let mut title = None;
+
+        for line in lines {
+            if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                let attrs = attributes(attrs);
+                let find = |key| attrs.iter().find(|(name, _)| *name == key).map(|a| a.1);
+
+                stream_inf = Some((
+                    find("BANDWIDTH").and_then(|v| v.parse().ok()).unwrap_or(0),
+                    find("CODECS").map(str::to_string),
+                ));
+            } else if let Some(seq) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+                media.media_sequence = seq.parse().map_err(invalid_data)?;
+            } else if let Some(secs) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                media.target_duration = Duration::from_secs(secs.parse().map_err(invalid_data)?);
+            } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+                title = info
+                    .split_once(',')
+                    .map(|(_, t)| t.trim())
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string);
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+                if !attrs.contains("METHOD=NONE") {
+                    return Err(invalid_data("Encrypted HLS streams are not supported."));
+                }
+            } else if line.starts_with("#EXT-X-MAP:") {
+                return Err(invalid_data(
+                    "HLS streams with initialisation segments are not supported.",
+                ));
+            } else if line == "#EXT-X-ENDLIST" {
+                media.ended = true;
+            } else if !line.starts_with('#') {
+                let uri = base.join(line).map_err(invalid_data)?;
+
+                match stream_inf.take() {
+                    Some((bandwidth, codecs)) => variants.push(Variant {
+                        bandwidth,
+                        codecs,
+                        uri,
+                    }),
+                    None => media.segments.push(Segment {
+                        uri,
+                        title: title.take(),
+                    }),
+                }
+            }
+        }
+
+        if variants.is_empty() {
+            Ok(Playlist::Media(media))
+        } else {
+            Ok(Playlist::Master(variants))
+        }
+    }
+}
+
+async fn fetch_text(client: &Client, url: &Url) -> reqwest::Result<String> {
+    client
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+}
+
+async fn fetch_segment(client: &Client, url: &Url) -> IoResult<Bytes> {
+    let bytes = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(request_error)?
+        .bytes()
+        .await
+        .map_err(request_error)?;
+
+    let bytes = strip_id3(bytes);
+
+    if bytes.len() > 188 && bytes[0] == 0x47 && bytes[188] == 0x47 {
+        return Err(invalid_data("MPEG-TS HLS segments are not supported."));
+    }
+
+    Ok(bytes)
+}
+
+/// Removes any ID3 tags (used for timestamps in packed audio) from the start of
+/// an HLS segment, which would otherwise interrupt the audio stream.
+fn strip_id3(mut bytes: Bytes) -> Bytes {
+    while bytes.len() >= 10 && bytes.starts_with(b"ID3") {
+        let size = bytes[6..10]
+            .iter()
+            .fold(0usize, |acc, b| (acc << 7) | usize::from(b & 0x7F));
+        let footer = if bytes[5] & 0x10 != 0 { 10 } else { 0 };
+
+        let _ = bytes.split_to((10 + size + footer).min(bytes.len()));
+    }
+
+    bytes
+}
+
+/// Passes the contents of each new segment in an HLS playlist into `tx`,
+/// refreshing the playlist until it ends.
+async fn hls_fetch(
+    client: Client,
+    url: Url,
+    mut playlist: MediaPlaylist,
+    mut titles: TitleUpdates,
+    tx: Sender<IoResult<Bytes>>,
+) {
+    let mut next = None;
+    let mut failures = 0;
+
+    loop {
+        let first = playlist.media_sequence;
+        let end = first + playlist.segments.len() as u64;
+
+        let start = match next {
+            Some(next) if next >= first => next,
+            Some(_) => {
+                debug!("Fell behind HLS playlist, skipping to oldest segment.");
+                first
+            },
+            None if playlist.ended => first,
+            None => end.saturating_sub(LIVE_EDGE_SEGMENTS as u64).max(first),
+        };
+
+        let mut error = None;
+        for (seq, segment) in (first..).zip(&playlist.segments) {
+            if seq < start {
+                continue;
+            }
+
+            if let Some(title) = &segment.title {
+                titles.update(title);
+            }
+
+            match fetch_segment(&client, &segment.uri).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    if tx.send_async(Ok(bytes)).await.is_err() {
+                        return;
+                    }
+                },
+                Err(e) if e.kind() == IoErrorKind::InvalidData => {
+                    let _ = tx.send_async(Err(e)).await;
+                    return;
+                },
+                Err(e) => {
+                    // A missed segment is a short glitch: keep going with the next.
+                    failures += 1;
+                    debug!("Failed to fetch HLS segment {}: {}", seq, e);
+                    error = Some(e);
+                },
+            }
+
+            next = Some(seq + 1);
+        }
+
+        if playlist.ended && !matches!(next, Some(next) if next < end) {
+            return;
+        }
+
+        if failures > MAX_RETRIES {
+            if let Some(e) = error {
+                let _ = tx.send_async(Err(e)).await;
+            }
+            return;
+        }
+
+        // Wait less if the playlist had no new segments, as one is likely imminent.
+        let wait = if matches!(next, Some(next) if next > start) {
+            playlist.target_duration
+        } else {
+            playlist.target_duration / 2
+        };
+        sleep(wait).await;
+
+        playlist = loop {
+            let refreshed = fetch_text(&client, &url)
+                .await
+                .map_err(request_error)
+                .and_then(|text| Playlist::parse(&url, &text));
+
+            match refreshed {
+                Ok(Playlist::Media(media)) => break media,
+                Ok(Playlist::Master(_)) => {
+                    let _ = tx
+                        .send_async(Err(invalid_data("HLS media playlist became a master.")))
+                        .await;
+                    return;
+                },
+                Err(e) => {
+                    failures += 1;
+                    if failures > MAX_RETRIES {
+                        let _ = tx.send_async(Err(e)).await;
+                        return;
+                    }
+
+                    debug!("Failed to refresh HLS playlist, retrying: {}", e);
+                    sleep(RETRY_DELAY * failures).await;
+                },
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icy_demuxer_extracts_titles_across_chunks() {
+        let mut stream = vec![1u8; 16];
+        let block = b"StreamTitle='Artist - It\'s a Song';StreamUrl='';";
+        stream.push(((block.len() + 15) / 16) as u8);
+        stream.extend_from_slice(block);
+        stream.resize(stream.len() + (16 - block.len() % 16) % 16, 0);
+        stream.extend_from_slice(&[2u8; 16]);
+        stream.push(0);
+        stream.extend_from_slice(&[3u8; 8]);
+
+        let mut demuxer = IcyDemuxer::new(Some(16));
+        let mut audio = vec![];
+        let mut titles = vec![];
+        for chunk in stream.chunks(7) {
+            titles.extend(demuxer.push(chunk, &mut audio));
+        }
+
+        assert_eq!(titles, vec!["Artist - It's a Song".to_string()]);
+        assert_eq!(audio.len(), 40);
+        assert!(audio[..16].iter().all(|b| *b == 1));
+        assert!(audio[16..32].iter().all(|b| *b == 2));
+        assert!(audio[32..].iter().all(|b| *b == 3));
+    }
+
+    #[test]
+    fn hls_playlists_parse() {
+        let base = Url::parse("https://example.com/live/index.m3u8").unwrap();
+
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\n\
+            low/index.m3u8\n\
+            #EXT-X-STREAM-INF:AVERAGE-BANDWIDTH=120000,BANDWIDTH=128000\n\
+            high/index.m3u8\n";
+
+        match Playlist::parse(&base, master).unwrap() {
+            Playlist::Master(variants) => {
+                assert_eq!(variants.len(), 2);
+                assert_eq!(variants[0].codecs.as_deref(), Some("mp4a.40.2"));
+                assert_eq!(variants[1].bandwidth, 128000);
+                assert_eq!(variants[1].codecs, None);
+            },
+            p => panic!("Expected master playlist, got {:?}", p),
+        }
+
+        let media = "#EXTM3U\n\
+            #EXT-X-TARGETDURATION:6\n\
+            #EXT-X-MEDIA-SEQUENCE:1042\n\
+            #EXTINF:6.0,Artist - Song\n\
+            seg1042.aac\n\
+            #EXTINF:6.0,\n\
+            https://cdn.example.com/seg1043.aac\n";
+
+        match Playlist::parse(&base, media).unwrap() {
+            Playlist::Media(media) => {
+                assert_eq!(media.media_sequence, 1042);
+                assert_eq!(media.target_duration, Duration::from_secs(6));
+                assert!(!media.ended);
+                assert_eq!(media.segments.len(), 2);
+                assert_eq!(media.segments[0].title.as_deref(), Some("Artist - Song"));
+                assert_eq!(
+                    media.segments[0].uri.as_str(),
+                    "https://example.com/live/seg1042.aac"
+                );
+                assert_eq!(media.segments[1].title, None);
+            },
+            p => panic!("Expected media playlist, got {:?}", p),
+        }
+    }
+
+    #[test]
+    fn hls_variants_are_audio_only() {
+        let base = Url::parse("https://example.com/live/index.m3u8").unwrap();
+        let chosen = |master: &str| match Playlist::parse(&base, master).unwrap() {
+            Playlist::Master(variants) => choose_variant(variants).map(|url| url.to_string()),
+            p => panic!("Expected master playlist, got {:?}", p),
+        };
+
+        let mixed = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.4d401f,mp4a.40.2\"\n\
+            video.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=128000\n\
+            unknown.m3u8\n\
+            #EXT-X-STREAM-INF:CODECS=\"mp4a.40.2,mp4a.40.34\",BANDWIDTH=64000\n\
+            audio.m3u8\n";
+        assert_eq!(
+            chosen(mixed).as_deref(),
+            Some("https://example.com/live/audio.m3u8")
+        );
+
+        let unlabelled = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.4d401f,mp4a.40.2\"\n\
+            video.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=128000\n\
+            unknown.m3u8\n";
+        assert_eq!(
+            chosen(unlabelled).as_deref(),
+            Some("https://example.com/live/unknown.m3u8")
+        );
+
+        let video = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.4d401f,mp4a.40.2\"\n\
+            video.m3u8\n";
+        assert_eq!(chosen(video), None);
+    }
+
+    #[test]
+    fn hls_init_segments_are_rejected() {
+        let base = Url::parse("https://example.com/live/index.m3u8").unwrap();
+        let media = "#EXTM3U\n\
+            #EXT-X-TARGETDURATION:6\n\
+            #EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXTINF:6.0,\n\
+            seg1.m4s\n";
+
+        let err = Playlist::parse(&base, media).unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidData);
+    }
+}
@@ -9,16 +9,16 @@
 //! PCM stream at 48kHz, matching the channel count of the input source.
 //!
 //! ## Opus frame passthrough.
-//! Some sources, such as [`Compressed`] or the output of [`dca`], support
+//! Some sources, such as [`Compressed`] or the output of [`dca`], [`ogg`], and [`webm`], support
 //! direct frame passthrough to the driver. This lets you directly send the
 //! audio data you have *without decoding, re-encoding, or mixing*. In many
 //! cases, this can greatly reduce the processing/compute cost of the driver.
 //!
 //! This functionality requires that:
-//!  * only one track is active (including paused tracks),
-//!  * that track's input supports direct Opus frame reads,
+//!  * a playing track's input supports direct Opus frame reads,
 //!  * its [`Input`] [meets the promises described herein](codec/struct.OpusDecoderState.html#structfield.allow_passthrough),
-//!  * and that track's volume is set to `1.0`.
+//!  * that track's volume is set to `1.0`, with no effects or fades applied,
+//!  * and any other playing tracks are silent during that frame (paused tracks are ignored).
 //!
 //! [`Input`]: Input
 //! [`Reader`]: reader::Reader
@@ -27,37 +27,57 @@
 //! [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 //! [`Compressed`]: cached::Compressed
 //! [`dca`]: dca()
+//! [`ogg`]: ogg()
+//! [`webm`]: webm()
 
 pub mod cached;
 mod child;
 pub mod codec;
 mod container;
+mod convert;
 mod dca;
 pub mod error;
 mod ffmpeg_src;
+#[cfg(feature = "http")]
+mod http_src;
+#[cfg(feature = "http")]
+mod live_src;
 mod metadata;
+mod ogg_src;
 pub mod reader;
 pub mod restartable;
+#[cfg(feature = "symphonia")]
+mod symphonia_src;
 pub mod utils;
+mod webm_src;
 mod ytdl_src;
 
+#[cfg(feature = "symphonia")]
+pub use self::symphonia_src::{symphonia, symphonia_source};
 pub use self::{
     child::*,
     codec::{Codec, CodecType},
     container::{Container, Frame},
-    dca::dca,
+    convert::{ConvertedSource, ResampleQuality, SampleFormat},
+    dca::{dca, write_dca, write_dca_with_encoder},
     ffmpeg_src::*,
     metadata::Metadata,
+    ogg_src::ogg,
     reader::Reader,
     restartable::Restartable,
+    webm_src::webm,
     ytdl_src::*,
 };
+#[cfg(feature = "http")]
+pub use self::{http_src::*, live_src::*};
 
 use crate::constants::*;
 use audiopus::coder::GenericCtl;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use cached::OpusCompressor;
+use container::DemuxState;
 use error::{Error, Result};
+use flume::Receiver;
 use tokio::runtime::Handle;
 
 use std::{
@@ -94,6 +114,8 @@ pub struct Input {
     pub kind: Codec,
     /// Framing strategy needed to identify frames of compressed audio.
     pub container: Container,
+    demux: DemuxState,
+    metadata_updates: Option<Receiver<Metadata>>,
     pos: usize,
 }
 
@@ -106,6 +128,8 @@ impl Input {
             reader,
             kind: Codec::FloatPcm,
             container: Container::Raw,
+            demux: Default::default(),
+            metadata_updates: None,
             pos: 0,
         }
     }
@@ -124,10 +148,34 @@ impl Input {
             reader,
             kind,
             container,
+            demux: Default::default(),
+            metadata_updates: None,
             pos: 0,
         }
     }
 
+    /// Attaches a channel of updated [`Metadata`], for sources whose metadata
+    /// changes during playback (i.e., live radio streams).
+    ///
+    /// While playing, the newest update replaces [`metadata`] and fires a
+    /// [`TrackEvent::MetadataUpdate`] event.
+    ///
+    /// [`metadata`]: Self::metadata
+    /// [`TrackEvent::MetadataUpdate`]: crate::events::TrackEvent::MetadataUpdate
+    pub fn with_metadata_updates(mut self, updates: Receiver<Metadata>) -> Self {
+        self.metadata_updates = Some(updates);
+        self
+    }
+
+    /// Replaces this input's metadata with the newest update from its source,
+    /// returning the new metadata if it has changed.
+    pub(crate) fn poll_metadata(&mut self) -> Option<Metadata> {
+        let latest = self.metadata_updates.as_ref()?.try_iter().last()?;
+        *self.metadata = latest.clone();
+
+        Some(latest)
+    }
+
     /// Returns whether the inner [`Reader`] implements [`Seek`].
     ///
     /// [`Reader`]: reader::Reader
@@ -193,23 +241,29 @@ impl Input {
 
                     decoder_state.frame_pos = 0;
                     decoder_state.current_frame.truncate(0);
+                    decoder_state.primer.clear();
 
                     // Step two: take frames if we can.
                     while buffer.len() - aud_skipped >= STEREO_FRAME_BYTE_SIZE {
                         decoder_state.should_reset = true;
 
-                        let frame = self
-                            .container
-                            .next_frame_length(&mut self.reader, CodecType::Opus)?;
-                        self.reader.consume(frame.frame_len);
+                        self.container.skip_frame(
+                            &mut self.reader,
+                            CodecType::Opus,
+                            &mut self.demux,
+                        )?;
 
-                        aud_skipped += STEREO_FRAME_BYTE_SIZE;
+                        let discarded = decoder_state.skip.min(MONO_FRAME_SIZE);
+                        decoder_state.skip -= discarded;
+
+                        aud_skipped += STEREO_FRAME_BYTE_SIZE - discarded * 2 * sample_len;
                     }
 
                     Ok(aud_skipped)
                 } else {
-                    // get new frame *if needed*
-                    if decoder_state.frame_pos == decoder_state.current_frame.len() {
+                    // get new frame *if needed*, passing over any which lie wholly
+                    // within the pre-skip.
+                    while decoder_state.frame_pos == decoder_state.current_frame.len() {
                         let mut decoder = decoder_state.decoder.lock();
 
                         if decoder_state.should_reset {
@@ -217,20 +271,32 @@ impl Input {
                                 .reset_state()
                                 .expect("Critical failure resetting decoder.");
                             decoder_state.should_reset = false;
-                        }
-                        let frame = self
-                            .container
-                            .next_frame_length(&mut self.reader, CodecType::Opus)?;
 
+                            // Decoding the frame before this one (discarding its audio)
+                            // avoids a click when resuming after passthrough.
+                            if !decoder_state.primer.is_empty() {
+                                let mut discard = [0f32; STEREO_FRAME_SIZE];
+                                let _ = decoder.decode_float(
+                                    Some((&decoder_state.primer[..]).try_into().unwrap()),
+                                    (&mut discard[..]).try_into().unwrap(),
+                                    false,
+                                );
+                                decoder_state.primer.clear();
+                            }
+                        }
                         let mut opus_data_buffer = [0u8; 4000];
 
+                        let seen = self.container.read_frame(
+                            &mut self.reader,
+                            CodecType::Opus,
+                            &mut self.demux,
+                            &mut opus_data_buffer[..],
+                        )?;
+
                         decoder_state
                             .current_frame
                             .resize(decoder_state.current_frame.capacity(), 0.0);
 
-                        let seen =
-                            Read::read(&mut self.reader, &mut opus_data_buffer[..frame.frame_len])?;
-
                         let samples = decoder
                             .decode_float(
                                 Some((&opus_data_buffer[..seen]).try_into().unwrap()),
@@ -239,8 +305,16 @@ impl Input {
                             )
                             .unwrap_or(0);
 
+                        // Pre-skip samples are decoded, but never played.
+                        let discarded = decoder_state.skip.min(samples);
+                        decoder_state.skip -= discarded;
+
                         decoder_state.current_frame.truncate(2 * samples);
-                        decoder_state.frame_pos = 0;
+                        decoder_state.frame_pos = 2 * discarded;
+
+                        if discarded == 0 {
+                            break;
+                        }
                     }
 
                     // read from frame which is present.
@@ -248,7 +322,7 @@ impl Input {
 
                     let start = decoder_state.frame_pos;
                     let to_write = float_space.min(decoder_state.current_frame.len() - start);
-                    for val in &decoder_state.current_frame[start..start + float_space] {
+                    for val in &decoder_state.current_frame[start..start + to_write] {
                         buffer.write_f32::<LittleEndian>(*val)?;
                     }
                     decoder_state.frame_pos += to_write;
@@ -294,6 +368,44 @@ impl Input {
         Ok(done)
     }
 
+    /// Seeks using the container's own timing information, if it has any.
+    ///
+    /// The container moves to a frame boundary at or before `target`, after
+    /// which the remainder is consumed as usual.
+    fn try_seek_container(&mut self, target: usize) -> Option<IoResult<usize>> {
+        if matches!(self.reader, Reader::Restartable(_)) || !self.reader.is_seekable() {
+            return None;
+        }
+
+        let sample_size = if self.stereo { 2 } else { 1 } * mem::size_of::<f32>();
+        let pre_skip = match &self.kind {
+            Codec::Opus(state) => state.pre_skip,
+            _ => 0,
+        };
+
+        // Container timestamps count any samples to be skipped at the start of the stream.
+        let res = self.container.try_seek_sample(
+            &mut self.reader,
+            &mut self.demux,
+            (target / sample_size + pre_skip) as u64,
+        )?;
+
+        Some(res.and_then(|sample| {
+            let sample = sample as usize;
+
+            if let Codec::Opus(state) = &mut self.kind {
+                state.current_frame.truncate(0);
+                state.frame_pos = 0;
+                state.should_reset = true;
+                state.primer.clear();
+                state.skip = pre_skip.saturating_sub(sample);
+            }
+
+            self.pos = sample.saturating_sub(pre_skip) * sample_size;
+            self.cheap_consume(target.saturating_sub(self.pos))
+        }))
+    }
+
     pub(crate) fn supports_passthrough(&self) -> bool {
         match &self.kind {
             Codec::Opus(state) => state.allow_passthrough,
@@ -310,18 +422,25 @@ impl Input {
             state.frame_pos = 0;
             state.current_frame.truncate(0);
 
-            // step 2: read new header.
-            let frame = self
-                .container
-                .next_frame_length(&mut self.reader, CodecType::Opus)?;
-
-            // step 3: read in bytes.
-            self.reader
-                .read_exact(&mut buffer[..frame.frame_len])
-                .map(|_| {
-                    self.pos += STEREO_FRAME_BYTE_SIZE;
-                    frame.frame_len
-                })
+            // step 2: read in new frame.
+            let len = self.container.read_frame(
+                &mut self.reader,
+                CodecType::Opus,
+                &mut self.demux,
+                buffer,
+            )?;
+            self.pos += STEREO_FRAME_BYTE_SIZE;
+
+            // Frames sent as-is cannot have any samples skipped.
+            state.skip = 0;
+
+            // The decoder has not seen this frame, so must be reset (and primed)
+            // if the track is mixed again.
+            state.should_reset = true;
+            state.primer.clear();
+            state.primer.extend_from_slice(&buffer[..len]);
+
+            Ok(len)
         } else {
             Err(IoError::new(
                 IoErrorKind::InvalidInput,
@@ -365,12 +484,20 @@ impl Seek for Input {
                 self.pos = outer_dest;
                 outer_dest
             })
+        } else if let Some(res) = self.try_seek_container(target) {
+            res
         } else if target > self.pos {
             // seek in the next amount, disabling decoding if need be.
             let shift = target - self.pos;
             self.cheap_consume(shift)
         } else {
             // start from scratch, then seek in...
+            self.demux = Default::default();
+
+            if let Codec::Opus(state) = &mut self.kind {
+                state.skip = state.pre_skip;
+            }
+
             Seek::seek(
                 &mut self.reader,
                 SeekFrom::Start(self.container.input_start() as u64),
@@ -592,4 +719,68 @@ mod tests {
             assert!(diff.abs() < f32::EPSILON);
         }
     }
+
+    #[test]
+    fn decoding_resumes_cleanly_after_passthrough() {
+        let data = make_sine(50 * MONO_FRAME_SIZE, true);
+        let input = Input::new(true, data.into(), Codec::FloatPcm, Container::Raw, None);
+        let compressed =
+            cached::Compressed::new(input, audiopus::Bitrate::BitsPerSecond(128_000)).unwrap();
+
+        let mut reference = Input::from(compressed.new_handle());
+        let mut resumed = Input::from(compressed);
+
+        let mut opus_buf = [0u8; 4000];
+        for _ in 0..10 {
+            resumed.read_opus_frame(&mut opus_buf[..]).unwrap();
+            reference.mix(&mut [0f32; STEREO_FRAME_SIZE], 1.0);
+        }
+
+        let mut reference_frame = [0f32; STEREO_FRAME_SIZE];
+        let mut resumed_frame = [0f32; STEREO_FRAME_SIZE];
+        reference.mix(&mut reference_frame, 1.0);
+        resumed.mix(&mut resumed_frame, 1.0);
+
+        let max_diff = reference_frame
+            .iter()
+            .zip(&resumed_frame[..])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+
+        assert!(max_diff < 0.01);
+    }
+
+    #[test]
+    fn opus_pre_skip_is_discarded() {
+        const PRE_SKIP: usize = 312;
+
+        let data = make_sine(50 * MONO_FRAME_SIZE, true);
+        let input = Input::new(true, data.into(), Codec::FloatPcm, Container::Raw, None);
+        let compressed =
+            cached::Compressed::new(input, audiopus::Bitrate::BitsPerSecond(128_000)).unwrap();
+
+        let mut reference = Input::from(compressed.new_handle());
+        let mut skipped = Input::from(compressed);
+        if let Codec::Opus(state) = &mut skipped.kind {
+            state.pre_skip = PRE_SKIP;
+            state.skip = PRE_SKIP;
+        }
+
+        let read_all = |input: &mut Input| {
+            let mut out = vec![];
+            let mut buf = [0u8; 2048];
+            while let Ok(len @ 1..) = input.read(&mut buf[..]) {
+                out.extend_from_slice(&buf[..len]);
+            }
+            out
+        };
+
+        let expected = read_all(&mut reference);
+        let out = read_all(&mut skipped);
+        assert_eq!(out[..], expected[2 * PRE_SKIP * mem::size_of::<f32>()..]);
+
+        // Rewinding to the start skips these samples once more.
+        skipped.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(read_all(&mut skipped).len(), out.len());
+    }
 }
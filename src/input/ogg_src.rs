@@ -0,0 +1,172 @@
+use super::{
+    codec::{is_twenty_millis, OpusDecoderState},
+    container::ogg::{read_page_header, OggState},
+    error::ContainerError,
+    Codec,
+    Container,
+    Input,
+    Metadata,
+    Reader,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{Cursor, ErrorKind as IoErrorKind, Read, Seek, SeekFrom},
+    time::Duration,
+};
+use tokio::task;
+
+const OPUS_HEAD: &[u8] = b"OpusHead";
+const OPUS_TAGS: &[u8] = b"OpusTags";
+
+/// Distance from the end of a file to search for its final page.
+const LAST_PAGE_SEARCH_LEN: u64 = 64 * 1024;
+
+/// Creates a streamed audio source from an Ogg Opus file.
+///
+/// Opus packets are read directly from the file, so this source supports
+/// [frame passthrough] when its packets are 20ms long, and seeks using
+/// the file's granule positions.
+///
+/// [frame passthrough]: crate::input#opus-frame-passthrough
+pub async fn ogg<P: AsRef<OsStr>>(path: P) -> Result<Input, ContainerError> {
+    let path = path.as_ref().to_owned();
+
+    task::spawn_blocking(move || _ogg(File::open(path)?))
+        .await
+        .map_err(|e| ContainerError::IoError(e.into()))?
+}
+
+fn _ogg(mut file: File) -> Result<Input, ContainerError> {
+    // All streams' first pages precede any other data.
+    let (serial, head) = loop {
+        let header = read_page_header(&mut file).map_err(|_| ContainerError::InvalidHeader)?;
+
+        if !header.is_bos() {
+            return Err(ContainerError::MissingOpusStream);
+        }
+
+        let mut body = vec![0u8; header.body_len()];
+        file.read_exact(&mut body)?;
+
+        if body.starts_with(OPUS_HEAD) {
+            break (header.serial, body);
+        }
+    };
+
+    let mut head = Cursor::new(&head[OPUS_HEAD.len()..]);
+    let _version = head.read_u8()?;
+    let channels = head.read_u8()?;
+    let pre_skip = head.read_u16::<LittleEndian>()?;
+
+    let mut state = OggState::default();
+    let mut tags = vec![0u8; 64 * 1024];
+    let tags = match state.read_packet(&mut file, serial, &mut tags[..]) {
+        Ok(len) => &tags[..len],
+        // Oversized comment headers (i.e., album art) are not worth parsing.
+        Err(e) if e.kind() == IoErrorKind::InvalidData => &[][..],
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut metadata = parse_tags(tags);
+    metadata.channels = Some(channels);
+    metadata.sample_rate = Some(48_000);
+
+    let first_page = file.stream_position()?;
+
+    metadata.duration = last_granule(&mut file, serial)?
+        .map(|granule| samples_to_duration(granule.saturating_sub(u64::from(pre_skip))));
+
+    file.seek(SeekFrom::Start(first_page))?;
+
+    let mut first_frame = [0u8; 4000];
+    let allow_passthrough = state
+        .read_packet(&mut file, serial, &mut first_frame[..])
+        .map(|len| is_twenty_millis(&first_frame[..len]))
+        .unwrap_or(false);
+
+    file.seek(SeekFrom::Start(first_page))?;
+
+    let mut decoder_state = OpusDecoderState::new().map_err(ContainerError::Opus)?;
+    decoder_state.allow_passthrough = allow_passthrough;
+    decoder_state.pre_skip = pre_skip.into();
+    decoder_state.skip = pre_skip.into();
+
+    Ok(Input::new(
+        true,
+        Reader::from_file(file),
+        Codec::Opus(decoder_state),
+        Container::Ogg {
+            first_page: first_page as usize,
+            serial,
+        },
+        Some(metadata),
+    ))
+}
+
+/// Extracts metadata from the user comments of an `OpusTags` packet.
+fn parse_tags(packet: &[u8]) -> Metadata {
+    let mut metadata = Metadata::default();
+
+    if !packet.starts_with(OPUS_TAGS) {
+        return metadata;
+    }
+
+    let mut cursor = Cursor::new(&packet[OPUS_TAGS.len()..]);
+
+    let _vendor = read_string(&mut cursor);
+    let count = cursor.read_u32::<LittleEndian>().unwrap_or(0);
+
+    for _ in 0..count {
+        let comment = match read_string(&mut cursor) {
+            Some(c) => c,
+            None => break,
+        };
+
+        if let Some((key, value)) = comment.split_once('=') {
+            let value = Some(value.to_string());
+
+            match key.to_ascii_uppercase().as_str() {
+                "TITLE" => metadata.track = value,
+                "ARTIST" => metadata.artist = value,
+                "DATE" => metadata.date = value,
+                _ => {},
+            }
+        }
+    }
+
+    metadata
+}
+
+/// Finds the granule position of the final page of stream `serial`.
+fn last_granule(file: &mut File, serial: u32) -> Result<Option<u64>, ContainerError> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let start = len.saturating_sub(LAST_PAGE_SEARCH_LEN);
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)?;
+
+    Ok(tail
+        .windows(4)
+        .enumerate()
+        .rev()
+        .filter(|(_, window)| *window == b"OggS")
+        .filter_map(|(i, _)| read_page_header(&tail[i..]).ok())
+        .find(|header| header.serial == serial && header.granule != u64::MAX)
+        .map(|header| header.granule))
+}
+
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Option<String> {
+    let len = cursor.read_u32::<LittleEndian>().ok()? as usize;
+    let start = cursor.position() as usize;
+    let bytes = cursor.get_ref().get(start..start + len)?;
+    cursor.set_position((start + len) as u64);
+
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+pub(crate) fn samples_to_duration(samples: u64) -> Duration {
+    Duration::from_secs_f64(samples as f64 / 48_000.0)
+}
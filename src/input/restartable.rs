@@ -12,6 +12,8 @@
 use super::*;
 use async_trait::async_trait;
 use flume::{Receiver, TryRecvError};
+#[cfg(feature = "http")]
+use reqwest::Client;
 use std::{
     ffi::OsStr,
     fmt::{Debug, Error as FormatError, Formatter},
@@ -19,6 +21,8 @@ use std::{
     result::Result as StdResult,
     time::Duration,
 };
+#[cfg(feature = "http")]
+use tokio::task;
 
 type Recreator = Box<dyn Restart + Send + 'static>;
 type RecreateChannel = Receiver<Result<(Box<Input>, Recreator)>>;
@@ -125,7 +129,37 @@ impl Restartable {
         uri: P,
         lazy: bool,
     ) -> Result<Self> {
-        Self::new(YtdlRestarter { uri }, lazy).await
+        Self::new(
+            YtdlRestarter {
+                uri,
+                metadata: None,
+            },
+            lazy,
+        )
+        .await
+    }
+
+    /// Create a new restartable ytdl source from an entry listed by [`ytdl_playlist`]
+    /// or [`ytdl_search_results`].
+    ///
+    /// Lazy sources reuse the entry's metadata rather than running `youtube-dl`
+    /// again, so creating many of them (e.g., to enqueue a whole playlist) is cheap.
+    /// Fails if the entry has no [`source_url`].
+    ///
+    /// [`ytdl_playlist`]: super::ytdl_playlist
+    /// [`ytdl_search_results`]: super::ytdl_search_results
+    /// [`source_url`]: Metadata::source_url
+    pub async fn ytdl_entry(entry: Metadata, lazy: bool) -> Result<Self> {
+        let uri = entry.source_url.clone().ok_or(Error::Metadata)?;
+
+        Self::new(
+            YtdlRestarter {
+                uri,
+                metadata: Some(entry),
+            },
+            lazy,
+        )
+        .await
     }
 
     /// Create a new restartable ytdl source, using the first result of a youtube search.
@@ -136,6 +170,26 @@ impl Restartable {
         Self::ytdl(format!("ytsearch1:{}", name.as_ref()), lazy).await
     }
 
+    /// Create a new restartable source, streamed and decoded over HTTP(S) by [`http`].
+    ///
+    /// Unlike [`ytdl`], restarting only costs a new range request, after which the
+    /// decoder seeks to the requested position. Lazy sources still connect once at
+    /// creation, to read the source's metadata.
+    ///
+    /// [`http`]: super::http
+    /// [`ytdl`]: Self::ytdl
+    #[cfg(feature = "http")]
+    pub async fn http(url: impl Into<String>, lazy: bool) -> Result<Self> {
+        Self::new(
+            HttpRestarter {
+                client: Client::new(),
+                url: url.into(),
+            },
+            lazy,
+        )
+        .await
+    }
+
     pub(crate) fn prep_with_handle(&mut self, handle: Handle) {
         self.async_handle = Some(handle);
     }
@@ -221,6 +275,7 @@ where
     P: AsRef<str> + Send + Sync,
 {
     uri: P,
+    metadata: Option<Metadata>,
 }
 
 #[async_trait]
@@ -239,9 +294,44 @@ where
     }
 
     async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
-        _ytdl_metadata(self.uri.as_ref())
+        let metadata = match self.metadata.take() {
+            Some(m) => m,
+            None => _ytdl_metadata(self.uri.as_ref()).await?,
+        };
+
+        Ok((Some(metadata), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+#[cfg(feature = "http")]
+struct HttpRestarter {
+    client: Client,
+    url: String,
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl Restart for HttpRestarter {
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input> {
+        let mut input = http_with_client(self.client.clone(), &self.url).await?;
+
+        if let Some(time) = time {
+            // Seeking the decoder may block on network IO.
+            input = task::spawn_blocking(move || {
+                input.seek_time(time);
+                input
+            })
+            .await
+            .map_err(|e| Error::Io(e.into()))?;
+        }
+
+        Ok(input)
+    }
+
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
+        http_with_client(self.client.clone(), &self.url)
             .await
-            .map(|m| (Some(m), Codec::FloatPcm, Container::Raw))
+            .map(|mut input| (Some(input.metadata.take()), Codec::FloatPcm, Container::Raw))
     }
 }
 
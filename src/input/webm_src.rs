@@ -0,0 +1,328 @@
+use super::{
+    codec::{is_twenty_millis, OpusDecoderState},
+    container::webm::{self, Element},
+    error::ContainerError,
+    Codec,
+    Container,
+    Input,
+    Metadata,
+    Reader,
+};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{Cursor, Error as IoError, ErrorKind as IoErrorKind, Read, Seek, SeekFrom},
+    time::Duration,
+};
+use tokio::task;
+
+const OPUS_CODEC_ID: &[u8] = b"A_OPUS";
+
+/// Largest header element (i.e., `Info` or `Tracks`) which will be parsed.
+const MAX_HEADER_ELEMENT_LEN: u64 = 1024 * 1024;
+
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Creates a streamed audio source from a WebM or Matroska file containing Opus audio.
+///
+/// Opus frames are read directly from the file, so this source supports
+/// [frame passthrough] when its frames are 20ms long (as is typical for
+/// YouTube's audio streams), and seeks using the file's cluster timestamps.
+///
+/// [frame passthrough]: crate::input#opus-frame-passthrough
+pub async fn webm<P: AsRef<OsStr>>(path: P) -> Result<Input, ContainerError> {
+    let path = path.as_ref().to_owned();
+
+    task::spawn_blocking(move || _webm(File::open(path)?))
+        .await
+        .map_err(|e| ContainerError::IoError(e.into()))?
+}
+
+#[derive(Default)]
+struct OpusTrack {
+    number: Option<u64>,
+    is_opus: bool,
+    channels: Option<u8>,
+    codec_delay: Option<u64>,
+}
+
+fn _webm(mut file: File) -> Result<Input, ContainerError> {
+    let ebml = webm::read_element(&mut file).map_err(|_| ContainerError::InvalidHeader)?;
+    if ebml.id != webm::EBML_HEADER {
+        return Err(ContainerError::InvalidHeader);
+    }
+    webm::skip_element(&mut file, &ebml)?;
+
+    let segment = webm::read_element(&mut file)?;
+    if segment.id != webm::SEGMENT {
+        return Err(ContainerError::InvalidHeader);
+    }
+
+    let mut metadata = Metadata::default();
+    let mut timestamp_scale = DEFAULT_TIMESTAMP_SCALE;
+    let mut raw_duration = None;
+    let mut track = None;
+
+    // Parse top-level elements until the first cluster.
+    let first_cluster = loop {
+        let pos = file.stream_position()?;
+        let element = webm::read_element(&mut file)?;
+
+        match element.id {
+            webm::CLUSTER => break pos,
+            webm::INFO => {
+                let body = read_body(&mut file, &element)?;
+
+                for_each_child(&body, |child, data| {
+                    match child.id {
+                        webm::TIMESTAMP_SCALE =>
+                            timestamp_scale = webm::read_uint(data, child.size.unwrap_or(0))?,
+                        webm::DURATION =>
+                            raw_duration = Some(read_float(data, child.size.unwrap_or(0))?),
+                        webm::TITLE => metadata.title = Some(String::from_utf8_lossy(data).into()),
+                        _ => {},
+                    }
+
+                    Ok(())
+                })?;
+            },
+            webm::TRACKS => {
+                let body = read_body(&mut file, &element)?;
+
+                for_each_child(&body, |entry, data| {
+                    if entry.id == webm::TRACK_ENTRY && track.is_none() {
+                        let candidate = parse_track(data)?;
+
+                        if candidate.is_opus && candidate.number.is_some() {
+                            track = Some(candidate);
+                        }
+                    }
+
+                    Ok(())
+                })?;
+            },
+            _ => webm::skip_element(&mut file, &element)?,
+        }
+    };
+
+    let track = track.ok_or(ContainerError::MissingOpusStream)?;
+    let track_number = track.number.ok_or(ContainerError::MissingOpusStream)?;
+
+    metadata.channels = track.channels.or(Some(2));
+    metadata.sample_rate = Some(48_000);
+    metadata.duration =
+        raw_duration.map(|d| Duration::from_secs_f64(d * timestamp_scale as f64 / 1_000_000_000.0));
+
+    file.seek(SeekFrom::Start(first_cluster))?;
+
+    let allow_passthrough = webm::next_block(&mut file, track_number)
+        .and_then(|(_, len)| {
+            let mut frame = vec![0u8; len];
+            file.read_exact(&mut frame)
+                .map(|_| is_twenty_millis(&frame))
+        })
+        .unwrap_or(false);
+
+    file.seek(SeekFrom::Start(first_cluster))?;
+
+    // Block timestamps include the codec delay, which must be discarded as in Ogg's pre-skip.
+    let pre_skip = track
+        .codec_delay
+        .map(|delay| webm::timestamp_to_samples(delay, 1) as usize)
+        .unwrap_or(0);
+
+    let mut decoder_state = OpusDecoderState::new().map_err(ContainerError::Opus)?;
+    decoder_state.allow_passthrough = allow_passthrough;
+    decoder_state.pre_skip = pre_skip;
+    decoder_state.skip = pre_skip;
+
+    Ok(Input::new(
+        true,
+        Reader::from_file(file),
+        Codec::Opus(decoder_state),
+        Container::WebM {
+            first_cluster: first_cluster as usize,
+            track: track_number,
+            timestamp_scale,
+        },
+        Some(metadata),
+    ))
+}
+
+fn parse_track(body: &[u8]) -> Result<OpusTrack, ContainerError> {
+    let mut track = OpusTrack::default();
+
+    for_each_child(body, |child, data| {
+        let size = child.size.unwrap_or(0);
+
+        match child.id {
+            webm::TRACK_NUMBER => track.number = Some(webm::read_uint(data, size)?),
+            webm::CODEC_ID => track.is_opus = data == OPUS_CODEC_ID,
+            webm::CODEC_DELAY => track.codec_delay = Some(webm::read_uint(data, size)?),
+            webm::AUDIO => for_each_child(data, |audio, data| {
+                if audio.id == webm::CHANNELS {
+                    track.channels = Some(webm::read_uint(data, audio.size.unwrap_or(0))? as u8);
+                }
+
+                Ok(())
+            })?,
+            _ => {},
+        }
+
+        Ok(())
+    })?;
+
+    Ok(track)
+}
+
+fn read_body(file: &mut File, element: &Element) -> Result<Vec<u8>, ContainerError> {
+    match element.size {
+        Some(size) if size <= MAX_HEADER_ELEMENT_LEN => {
+            let mut body = vec![0u8; size as usize];
+            file.read_exact(&mut body)?;
+            Ok(body)
+        },
+        _ => Err(ContainerError::InvalidHeader),
+    }
+}
+
+/// Calls `f` with the header and body of each element contained in `body`.
+fn for_each_child(
+    body: &[u8],
+    mut f: impl FnMut(&Element, &[u8]) -> Result<(), ContainerError>,
+) -> Result<(), ContainerError> {
+    let mut cursor = Cursor::new(body);
+
+    while (cursor.position() as usize) < body.len() {
+        let child = webm::read_element(&mut cursor)?;
+        let start = cursor.position() as usize;
+        let end = child
+            .size
+            .map(|size| start + size as usize)
+            .filter(|end| *end <= body.len())
+            .ok_or(ContainerError::InvalidHeader)?;
+
+        f(&child, &body[start..end])?;
+        cursor.set_position(end as u64);
+    }
+
+    Ok(())
+}
+
+fn read_float(mut data: &[u8], size: u64) -> Result<f64, IoError> {
+    match size {
+        4 => data.read_f32::<BigEndian>().map(f64::from),
+        8 => data.read_f64::<BigEndian>(),
+        _ => Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "EBML float had invalid length.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::*,
+        input::{cached::Compressed, container::webm::tests::*},
+        test_utils::*,
+    };
+    use audiopus::Bitrate;
+    use std::{io::Write, mem};
+
+    const PRE_SKIP: usize = 312;
+
+    fn opus_frames(count: usize) -> Vec<Vec<u8>> {
+        let data = make_sine(count * STEREO_FRAME_SIZE, true);
+        let input = Input::new(true, data.into(), Codec::FloatPcm, Container::Raw, None);
+        let mut input =
+            Input::from(Compressed::new(input, Bitrate::BitsPerSecond(128_000)).unwrap());
+
+        let mut buf = [0u8; 4000];
+        (0..count)
+            .map(|_| {
+                let len = input.read_opus_frame(&mut buf[..]).unwrap();
+                buf[..len].to_vec()
+            })
+            .collect()
+    }
+
+    fn webm_file(clusters: &[Vec<u8>], codec_delay: Option<u64>) -> File {
+        let mut track = [
+            element(webm::TRACK_NUMBER, &[1]),
+            element(webm::CODEC_ID, OPUS_CODEC_ID),
+            element(webm::AUDIO, &element(webm::CHANNELS, &[2])),
+        ]
+        .concat();
+
+        if let Some(delay) = codec_delay {
+            track.extend(element(webm::CODEC_DELAY, &delay.to_be_bytes()));
+        }
+
+        let mut segment = [
+            element(
+                webm::INFO,
+                &element(webm::TIMESTAMP_SCALE, &1_000_000u32.to_be_bytes()),
+            ),
+            element(webm::TRACKS, &element(webm::TRACK_ENTRY, &track)),
+        ]
+        .concat();
+        clusters.iter().for_each(|c| segment.extend_from_slice(c));
+
+        let path = std::env::temp_dir().join(format!("songbird-{}.webm", uuid::Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&element(webm::EBML_HEADER, &[])).unwrap();
+        file.write_all(&element(webm::SEGMENT, &segment)).unwrap();
+        drop(file);
+
+        let file = File::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        file
+    }
+
+    fn read_all(input: &mut Input) -> Vec<u8> {
+        let mut out = vec![];
+        let mut buf = [0u8; 2048];
+        while let Ok(len @ 1..) = input.read(&mut buf[..]) {
+            out.extend_from_slice(&buf[..len]);
+        }
+        out
+    }
+
+    #[test]
+    fn header_describes_opus_track() {
+        let blocks: Vec<_> = opus_frames(2)
+            .iter()
+            .map(|frame| simple_block(1, 0x80, frame))
+            .collect();
+
+        let input = _webm(webm_file(&[cluster(0, &blocks)], None)).unwrap();
+        assert_eq!(input.metadata.channels, Some(2));
+        assert_eq!(input.metadata.sample_rate, Some(48_000));
+        assert!(input.supports_passthrough());
+        assert!(matches!(input.container, Container::WebM { track: 1, .. }));
+    }
+
+    #[test]
+    fn codec_delay_is_discarded() {
+        let blocks: Vec<_> = opus_frames(10)
+            .iter()
+            .map(|frame| simple_block(1, 0x80, frame))
+            .collect();
+        let clusters = [cluster(0, &blocks)];
+
+        let delay = (PRE_SKIP as u64 * 1_000_000_000) / 48_000;
+        let mut reference = _webm(webm_file(&clusters, None)).unwrap();
+        let mut delayed = _webm(webm_file(&clusters, Some(delay))).unwrap();
+        assert_eq!(delayed.metadata.start_time, None);
+
+        let expected = read_all(&mut reference);
+        let out = read_all(&mut delayed);
+        assert_eq!(out[..], expected[2 * PRE_SKIP * mem::size_of::<f32>()..]);
+
+        delayed.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(read_all(&mut delayed).len(), out.len());
+    }
+}
@@ -174,3 +174,121 @@ pub(crate) async fn _ytdl_metadata(uri: &str) -> Result<Metadata> {
 pub async fn ytdl_search(name: impl AsRef<str>) -> Result<Input> {
     ytdl(&format!("ytsearch1:{}", name.as_ref())).await
 }
+
+/// Lists the entries of a playlist (or other multi-video page) with `youtube-dl(c)`,
+/// without downloading or fully resolving any of them.
+///
+/// Only the lightweight details known to the playlist are filled in: typically
+/// each entry's [`source_url`] and [`title`], and sometimes its [`duration`].
+/// A single video URL yields one entry. Entries can later be played via
+/// [`Restartable::ytdl_entry`].
+///
+/// `youtube-dlc` and `yt-dlp` are also useable by enabling the `youtube-dlc`
+/// and `yt-dlp` features respectively.
+///
+/// [`source_url`]: Metadata::source_url
+/// [`title`]: Metadata::title
+/// [`duration`]: Metadata::duration
+/// [`Restartable::ytdl_entry`]: crate::input::restartable::Restartable::ytdl_entry
+pub async fn ytdl_playlist(uri: impl AsRef<str>) -> Result<Vec<Metadata>> {
+    _ytdl_flat(uri.as_ref()).await
+}
+
+/// Lists up to `n` YouTube search results with `youtube-dl(c)` and `ytsearch`,
+/// without downloading or fully resolving any of them.
+///
+/// As with [`ytdl_playlist`], each entry only holds lightweight metadata, and can
+/// later be played via [`Restartable::ytdl_entry`].
+///
+/// [`Restartable::ytdl_entry`]: crate::input::restartable::Restartable::ytdl_entry
+pub async fn ytdl_search_results(name: impl AsRef<str>, n: usize) -> Result<Vec<Metadata>> {
+    _ytdl_flat(&format!("ytsearch{}:{}", n, name.as_ref())).await
+}
+
+async fn _ytdl_flat(uri: &str) -> Result<Vec<Metadata>> {
+    let ytdl_args = [
+        "-j",
+        "--flat-playlist",
+        "--yes-playlist",
+        "--ignore-config",
+        "--no-warnings",
+        uri,
+    ];
+
+    let youtube_dl_output = TokioCommand::new(YOUTUBE_DL_COMMAND)
+        .args(ytdl_args)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !youtube_dl_output.status.success() && youtube_dl_output.stdout.is_empty() {
+        return Err(Error::YouTubeDlRun(youtube_dl_output));
+    }
+
+    flat_entries(&youtube_dl_output.stdout)
+}
+
+/// Parses the newline-delimited JSON printed by `--flat-playlist -j`.
+fn flat_entries(out: &[u8]) -> Result<Vec<Metadata>> {
+    out.split(|el| *el == 0xA)
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| {
+            let value: Value = serde_json::from_slice(line).map_err(|err| Error::Json {
+                error: err,
+                parsed_text: std::str::from_utf8(line).unwrap_or_default().to_string(),
+            })?;
+
+            let url = flat_entry_url(&value);
+            let mut metadata = Metadata::from_ytdl_output(value);
+            metadata.source_url = metadata.source_url.or(url);
+
+            Ok(metadata)
+        })
+        .collect()
+}
+
+/// Flat entries are often missing `webpage_url`, and older versions of `youtube-dl`
+/// give only the video ID in their `url` field.
+fn flat_entry_url(value: &Value) -> Option<String> {
+    let obj = value.as_object()?;
+    let url = obj.get("url").and_then(Value::as_str)?;
+
+    let is_bare_youtube_id =
+        !url.contains("://") && obj.get("ie_key").and_then(Value::as_str) == Some("Youtube");
+
+    Some(if is_bare_youtube_id {
+        format!("https://www.youtube.com/watch?v={}", url)
+    } else {
+        url.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_entries_fill_source_url() {
+        let out = br#"{"_type": "url", "ie_key": "Youtube", "id": "abc", "url": "abc", "title": "First"}
+{"_type": "url", "ie_key": "Youtube", "url": "https://www.youtube.com/watch?v=def", "title": "Second", "duration": 61.0}
+
+"#;
+
+        let entries = flat_entries(out).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].source_url.as_deref(),
+            Some("https://www.youtube.com/watch?v=abc")
+        );
+        assert_eq!(entries[0].title.as_deref(), Some("First"));
+        assert_eq!(
+            entries[1].source_url.as_deref(),
+            Some("https://www.youtube.com/watch?v=def")
+        );
+        assert_eq!(
+            entries[1].duration,
+            Some(std::time::Duration::from_secs(61))
+        );
+    }
+}
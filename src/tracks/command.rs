@@ -18,6 +18,8 @@ pub enum TrackCommand {
     Stop,
     /// Set the track's volume.
     Volume(f32),
+    /// Gradually change the track's volume.
+    Fade(Fade),
     /// Seek to the given duration.
     ///
     /// On unsupported input types, this can be fatal.
@@ -32,6 +34,8 @@ pub enum TrackCommand {
     Loop(LoopState),
     /// Prompts a track's input to become live and usable, if it is not already.
     MakePlayable,
+    /// Modify the chain of audio effects applied to this track.
+    Effects(EffectCommand),
 }
 
 impl std::fmt::Debug for TrackCommand {
@@ -45,12 +49,14 @@ impl std::fmt::Debug for TrackCommand {
                 Pause => "Pause".to_string(),
                 Stop => "Stop".to_string(),
                 Volume(vol) => format!("Volume({})", vol),
+                Fade(fade) => format!("Fade({:?})", fade),
                 Seek(d) => format!("Seek({:?})", d),
                 AddEvent(evt) => format!("AddEvent({:?})", evt),
                 Do(_f) => "Do([function])".to_string(),
                 Request(tx) => format!("Request({:?})", tx),
                 Loop(loops) => format!("Loop({:?})", loops),
                 MakePlayable => "MakePlayable".to_string(),
+                Effects(cmd) => format!("Effects({:?})", cmd),
             }
         )
     }
@@ -0,0 +1,190 @@
+use super::*;
+use std::f32::consts::PI;
+
+/// Frequency response of a [`Biquad`] filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BiquadKind {
+    /// Attenuates frequencies above the cutoff.
+    LowPass,
+    /// Attenuates frequencies below the cutoff.
+    HighPass,
+    /// Boosts or cuts a band around the centre frequency by the given gain (in dB).
+    Peak(f32),
+    /// Boosts or cuts frequencies below the corner frequency by the given gain (in dB).
+    LowShelf(f32),
+    /// Boosts or cuts frequencies above the corner frequency by the given gain (in dB).
+    HighShelf(f32),
+}
+
+/// A second-order IIR filter, suitable for building a parametric equaliser
+/// from several bands.
+///
+/// Coefficients follow the designs of the [Audio EQ Cookbook].
+///
+/// [Audio EQ Cookbook]: https://www.w3.org/TR/audio-eq-cookbook/
+#[derive(Clone, Debug)]
+pub struct Biquad {
+    kind: BiquadKind,
+    b: [f32; 3],
+    a: [f32; 2],
+    /// Transposed direct form II state, per channel.
+    state: [[f32; 2]; 2],
+}
+
+impl Biquad {
+    /// Creates a filter with the given response, centre/cutoff `frequency` (in Hz),
+    /// and quality factor `q`.
+    ///
+    /// A `q` of `0.707` gives a maximally flat pass band for low- and high-pass filters.
+    pub fn new(kind: BiquadKind, frequency: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * frequency / SAMPLE_RATE_RAW as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q.max(f32::EPSILON));
+
+        let (b, a) = match kind {
+            BiquadKind::LowPass => (
+                [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            BiquadKind::HighPass => (
+                [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            BiquadKind::Peak(gain) => {
+                let amp = 10.0f32.powf(gain / 40.0);
+                (
+                    [1.0 + alpha * amp, -2.0 * cos, 1.0 - alpha * amp],
+                    [1.0 + alpha / amp, -2.0 * cos, 1.0 - alpha / amp],
+                )
+            },
+            BiquadKind::LowShelf(gain) => {
+                let amp = 10.0f32.powf(gain / 40.0);
+                let sq = 2.0 * amp.sqrt() * alpha;
+                (
+                    [
+                        amp * ((amp + 1.0) - (amp - 1.0) * cos + sq),
+                        2.0 * amp * ((amp - 1.0) - (amp + 1.0) * cos),
+                        amp * ((amp + 1.0) - (amp - 1.0) * cos - sq),
+                    ],
+                    [
+                        (amp + 1.0) + (amp - 1.0) * cos + sq,
+                        -2.0 * ((amp - 1.0) + (amp + 1.0) * cos),
+                        (amp + 1.0) + (amp - 1.0) * cos - sq,
+                    ],
+                )
+            },
+            BiquadKind::HighShelf(gain) => {
+                let amp = 10.0f32.powf(gain / 40.0);
+                let sq = 2.0 * amp.sqrt() * alpha;
+                (
+                    [
+                        amp * ((amp + 1.0) + (amp - 1.0) * cos + sq),
+                        -2.0 * amp * ((amp - 1.0) + (amp + 1.0) * cos),
+                        amp * ((amp + 1.0) + (amp - 1.0) * cos - sq),
+                    ],
+                    [
+                        (amp + 1.0) - (amp - 1.0) * cos + sq,
+                        2.0 * ((amp - 1.0) - (amp + 1.0) * cos),
+                        (amp + 1.0) - (amp - 1.0) * cos - sq,
+                    ],
+                )
+            },
+        };
+
+        Self {
+            kind,
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            state: [[0.0; 2]; 2],
+        }
+    }
+
+    /// Returns this filter's response type.
+    pub fn kind(&self) -> BiquadKind {
+        self.kind
+    }
+}
+
+impl AudioEffect for Biquad {
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+
+        for samples in frame.chunks_exact_mut(2) {
+            for (x, z) in samples.iter_mut().zip(self.state.iter_mut()) {
+                let y = b0 * *x + z[0];
+                z[0] = b1 * *x - a1 * y + z[1];
+                z[1] = b2 * *x - a2 * y;
+                *x = y;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = [[0.0; 2]; 2];
+    }
+}
+
+/// Boosts low frequencies using a low-shelf filter.
+#[derive(Clone, Debug)]
+pub struct BassBoost {
+    filter: Biquad,
+}
+
+impl BassBoost {
+    /// Corner frequency of the shelf, in Hz.
+    pub const FREQUENCY: f32 = 120.0;
+
+    /// Creates a bass boost of `gain` dB.
+    pub fn new(gain: f32) -> Self {
+        Self {
+            filter: Biquad::new(BiquadKind::LowShelf(gain), Self::FREQUENCY, 0.707),
+        }
+    }
+}
+
+impl AudioEffect for BassBoost {
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        self.filter.process(frame);
+    }
+
+    fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steady_state_gain(filter: &mut Biquad, frequency: f32) -> f32 {
+        let mut peak = 0.0f32;
+
+        for i in 0..10 {
+            let mut frame = [0.0; STEREO_FRAME_SIZE];
+            for (j, pair) in frame.chunks_exact_mut(2).enumerate() {
+                let t = (i * MONO_FRAME_SIZE + j) as f32 / SAMPLE_RATE_RAW as f32;
+                pair[0] = (2.0 * PI * frequency * t).sin();
+                pair[1] = pair[0];
+            }
+
+            filter.process(&mut frame);
+
+            if i > 5 {
+                peak = frame.iter().fold(peak, |acc, s| acc.max(s.abs()));
+            }
+        }
+
+        peak
+    }
+
+    #[test]
+    fn low_pass_attenuates_only_above_cutoff() {
+        let mut filter = Biquad::new(BiquadKind::LowPass, 1000.0, 0.707);
+        assert!((steady_state_gain(&mut filter, 100.0) - 1.0).abs() < 0.02);
+
+        filter.reset();
+        assert!(steady_state_gain(&mut filter, 10_000.0) < 0.02);
+    }
+}
@@ -0,0 +1,132 @@
+use super::*;
+
+/// Converts a time constant into a one-pole smoothing coefficient.
+fn time_coefficient(time: f32) -> f32 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time * SAMPLE_RATE_RAW as f32)).exp()
+    }
+}
+
+/// Reduces the dynamic range of a track, attenuating audio which exceeds a threshold.
+///
+/// Both channels are compressed using a shared (linked) envelope, preserving
+/// the stereo image.
+#[derive(Clone, Debug)]
+pub struct Compressor {
+    threshold: f32,
+    ratio: f32,
+    makeup: f32,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+}
+
+impl Compressor {
+    /// Creates a compressor which begins to act above `threshold` dBFS,
+    /// reducing any excess level by `ratio` (i.e., `4.0` for 4:1 compression).
+    ///
+    /// `attack` and `release` are given in seconds, and `makeup` gain in dB
+    /// is applied to all output.
+    pub fn new(threshold: f32, ratio: f32, attack: f32, release: f32, makeup: f32) -> Self {
+        Self {
+            threshold,
+            ratio: ratio.max(1.0),
+            makeup,
+            attack: time_coefficient(attack),
+            release: time_coefficient(release),
+            envelope: 0.0,
+        }
+    }
+}
+
+impl AudioEffect for Compressor {
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        let slope = 1.0 - 1.0 / self.ratio;
+
+        for samples in frame.chunks_exact_mut(2) {
+            let level = samples[0].abs().max(samples[1].abs());
+            let coeff = if level > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope = coeff * self.envelope + (1.0 - coeff) * level;
+
+            let env_db = 20.0 * self.envelope.max(1e-6).log10();
+            let reduction = (env_db - self.threshold).max(0.0) * slope;
+            let gain = db_to_gain(self.makeup - reduction);
+
+            samples[0] *= gain;
+            samples[1] *= gain;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
+
+/// Prevents a track's peaks from exceeding a ceiling.
+///
+/// Gain reduction is applied instantly on any sample exceeding the ceiling,
+/// and recovers over the release time. This limiter has no lookahead, and
+/// so adds no latency.
+#[derive(Clone, Debug)]
+pub struct Limiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    /// Creates a limiter with the given `ceiling` in dBFS, and `release` time in seconds.
+    pub fn new(ceiling: f32, release: f32) -> Self {
+        Self {
+            ceiling: db_to_gain(ceiling),
+            release: time_coefficient(release),
+            gain: 1.0,
+        }
+    }
+}
+
+impl AudioEffect for Limiter {
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        for samples in frame.chunks_exact_mut(2) {
+            let peak = samples[0].abs().max(samples[1].abs());
+
+            // Recover towards unity, then clamp to whatever this sample requires.
+            self.gain = self.release * self.gain + (1.0 - self.release);
+            if peak * self.gain > self.ceiling {
+                self.gain = self.ceiling / peak;
+            }
+
+            samples[0] *= self.gain;
+            samples[1] *= self.gain;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_holds_ceiling() {
+        let mut limiter = Limiter::new(-6.0, 0.05);
+        let mut frame = [0.0; STEREO_FRAME_SIZE];
+        for (i, sample) in frame.iter_mut().enumerate() {
+            *sample = if i % 4 < 2 { 1.0 } else { -0.9 };
+        }
+
+        limiter.process(&mut frame);
+
+        let ceiling = db_to_gain(-6.0);
+        assert!(frame.iter().all(|s| s.abs() <= ceiling + 1e-6));
+    }
+}
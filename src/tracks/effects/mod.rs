@@ -0,0 +1,59 @@
+//! Audio effects which may be applied to individual tracks.
+//!
+//! Effects are run on each track's 20ms stereo frame, after its volume has been
+//! applied and before it is summed with all other live tracks. A track may hold
+//! any number of effects, which are applied in order. Each track reserves room for
+//! [`RESERVED_EFFECTS`] up front, so that chains of up to that length never force
+//! the mixer to reallocate.
+//!
+//! Effects run within the mixer thread, and so must be *real-time safe*:
+//! [`AudioEffect::process`] should not block, perform I/O, or allocate.
+//! Any required buffers should be created ahead of time, in the effect's constructor.
+//!
+//! Tracks which have any effects attached cannot use [Opus frame passthrough].
+//!
+//! [Opus frame passthrough]: crate::input#opus-frame-passthrough
+
+mod biquad;
+mod dynamics;
+mod pan;
+
+pub use self::{biquad::*, dynamics::*, pan::*};
+
+use crate::constants::*;
+use std::fmt::Debug;
+
+/// The number of effects each track has room for before its chain must be
+/// reallocated on the mixer thread.
+pub const RESERVED_EFFECTS: usize = 8;
+
+/// An effect which modifies a track's audio before it is mixed.
+///
+/// Audio is presented as interleaved stereo floating-point samples at 48kHz,
+/// in frames of 20ms.
+pub trait AudioEffect: Debug + Send {
+    /// Processes a single 20ms frame of interleaved stereo audio in place.
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]);
+
+    /// Clears any held state (e.g., filter history or envelope levels), such as
+    /// when a track seeks or loops.
+    fn reset(&mut self) {}
+}
+
+/// A change to a track's effects chain, sent via [`TrackCommand::Effects`].
+///
+/// [`TrackCommand::Effects`]: super::TrackCommand::Effects
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EffectCommand {
+    /// Replace all effects on a track.
+    Set(Vec<Box<dyn AudioEffect>>),
+    /// Append an effect to the end of a track's chain.
+    Add(Box<dyn AudioEffect>),
+}
+
+/// Converts a gain in decibels into a linear multiplier.
+#[inline]
+pub(crate) fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
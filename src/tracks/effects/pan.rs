@@ -0,0 +1,51 @@
+use super::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// Moves a track towards the left or right channel.
+///
+/// Panning left moves the right channel's audio into the left channel using a
+/// constant-power law (and vice versa), so that no audio is lost at the extremes.
+/// A centred pan leaves audio unchanged.
+#[derive(Clone, Debug)]
+pub struct Pan {
+    position: f32,
+    /// Share of the moved channel which remains in place, and which is moved.
+    gains: [f32; 2],
+}
+
+impl Pan {
+    /// Creates a pan to `position`, where `-1.0` is hard left, `0.0` is centre,
+    /// and `1.0` is hard right.
+    ///
+    /// Values outside this range are clamped.
+    pub fn new(position: f32) -> Self {
+        let position = position.clamp(-1.0, 1.0);
+        let (sin, cos) = (position.abs() * FRAC_PI_2).sin_cos();
+
+        Self {
+            position,
+            gains: [cos, sin],
+        }
+    }
+
+    /// Returns the pan position, from `-1.0` (left) to `1.0` (right).
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+}
+
+impl AudioEffect for Pan {
+    fn process(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        let [stay, moved] = self.gains;
+
+        // Index of the channel being moved, and of its destination.
+        let (from, to) = if self.position < 0.0 { (1, 0) } else { (0, 1) };
+
+        for samples in frame.chunks_exact_mut(2) {
+            let source = samples[from];
+
+            samples[from] = source * stay;
+            samples[to] += source * moved;
+        }
+    }
+}
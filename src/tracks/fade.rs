@@ -0,0 +1,213 @@
+use crate::constants::*;
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+/// Shape of a volume change made by a [`Fade`].
+///
+/// Curves describe a rising fade: falling fades use the mirror image, so that
+/// fading one track out while another fades in (using the same curve) produces
+/// a symmetric crossfade.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FadeCurve {
+    /// Volume changes at a constant rate.
+    Linear,
+    /// Volume follows a quarter sine wave, such that two overlapping tracks
+    /// retain constant combined power.
+    ///
+    /// This is the best choice for crossfading between unrelated tracks.
+    EqualPower,
+    /// Volume changes slowly at either end of the fade, and fastest in its middle.
+    SCurve,
+    /// Volume rises slowly at first, which sounds more natural for long fade-ins.
+    Exponential,
+}
+
+impl FadeCurve {
+    /// Maps progress through a rising fade (from `0.0` to `1.0`) onto a gain.
+    fn shape(self, t: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::EqualPower => (t * FRAC_PI_2).sin(),
+            FadeCurve::SCurve => t * t * (3.0 - 2.0 * t),
+            FadeCurve::Exponential => t * t,
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for FadeCurve {
+    fn default() -> Self {
+        FadeCurve::Linear
+    }
+}
+
+/// A gradual change in a track's volume, applied per-sample within the mixer.
+///
+/// Fades are started using [`TrackHandle::fade`] or [`TrackHandle::fade_to`], and
+/// fire a [`TrackEvent::FadeComplete`] once finished. Setting a track's volume
+/// directly cancels any ongoing fade.
+///
+/// [`TrackHandle::fade`]: super::TrackHandle::fade
+/// [`TrackHandle::fade_to`]: super::TrackHandle::fade_to
+/// [`TrackEvent::FadeComplete`]: crate::events::TrackEvent::FadeComplete
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fade {
+    pub(crate) from: Option<f32>,
+    pub(crate) to: Option<f32>,
+    pub(crate) duration: Duration,
+    pub(crate) curve: FadeCurve,
+    pub(crate) stop: bool,
+}
+
+impl Fade {
+    /// Fades from the track's current volume to `volume`.
+    pub fn to(volume: f32, duration: Duration, curve: FadeCurve) -> Self {
+        Self {
+            from: None,
+            to: Some(volume),
+            duration,
+            curve,
+            stop: false,
+        }
+    }
+
+    /// Fades from silence up to the track's current volume.
+    pub fn fade_in(duration: Duration, curve: FadeCurve) -> Self {
+        Self {
+            from: Some(0.0),
+            to: None,
+            duration,
+            curve,
+            stop: false,
+        }
+    }
+
+    /// Fades from the track's current volume down to silence, and then stops the track.
+    pub fn fade_out(duration: Duration, curve: FadeCurve) -> Self {
+        Self {
+            from: None,
+            to: Some(0.0),
+            duration,
+            curve,
+            stop: true,
+        }
+    }
+}
+
+/// A [`Fade`] which is in progress on a live track.
+#[derive(Clone, Debug)]
+pub(crate) struct FadeState {
+    pub(crate) from: f32,
+    pub(crate) to: f32,
+    pub(crate) curve: FadeCurve,
+    pub(crate) stop: bool,
+    /// Length of the fade, in samples per channel.
+    length: usize,
+    /// Samples (per channel) of the fade which have been applied.
+    elapsed: usize,
+}
+
+impl FadeState {
+    pub(crate) fn new(fade: Fade, volume: f32) -> Self {
+        let length = (fade.duration.as_secs_f64() * SAMPLE_RATE_RAW as f64).round() as usize;
+
+        Self {
+            from: fade.from.unwrap_or(volume),
+            to: fade.to.unwrap_or(volume),
+            curve: fade.curve,
+            stop: fade.stop,
+            length: length.max(1),
+            elapsed: 0,
+        }
+    }
+
+    fn gain(&self, sample: usize) -> f32 {
+        let t = (sample as f32 / self.length as f32).min(1.0);
+
+        if self.to >= self.from {
+            self.from + (self.to - self.from) * self.curve.shape(t)
+        } else {
+            self.to + (self.from - self.to) * self.curve.shape(1.0 - t)
+        }
+    }
+
+    /// Applies the next 20ms of this fade to an interleaved stereo frame,
+    /// returning whether the fade has completed.
+    pub(crate) fn apply(&mut self, frame: &mut [f32; STEREO_FRAME_SIZE]) -> bool {
+        for (i, samples) in frame.chunks_exact_mut(2).enumerate() {
+            let gain = self.gain(self.elapsed + i);
+
+            samples[0] *= gain;
+            samples[1] *= gain;
+        }
+
+        self.elapsed += MONO_FRAME_SIZE;
+
+        self.is_done()
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.elapsed >= self.length
+    }
+}
+
+/// A short ramp applied when a user pauses, resumes, or stops a track,
+/// preventing an audible click from the sudden change in level.
+///
+/// Ramps last for exactly one frame, so pausing a track does not skip any audio.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Declick {
+    /// Audio rises from silence over the next frame.
+    In,
+    /// Audio falls to silence over the next frame, after which the track
+    /// is no longer mixed.
+    Out,
+}
+
+impl Declick {
+    pub(crate) fn apply(self, frame: &mut [f32; STEREO_FRAME_SIZE]) {
+        for (i, samples) in frame.chunks_exact_mut(2).enumerate() {
+            let t = i as f32 / MONO_FRAME_SIZE as f32;
+            let gain = match self {
+                Declick::In => FadeCurve::SCurve.shape(t),
+                Declick::Out => FadeCurve::SCurve.shape(1.0 - t),
+            };
+
+            samples[0] *= gain;
+            samples[1] *= gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_power_crossfade_is_symmetric() {
+        let duration = Duration::from_millis(100);
+        let fade_in = FadeState::new(Fade::fade_in(duration, FadeCurve::EqualPower), 1.0);
+        let fade_out = FadeState::new(Fade::fade_out(duration, FadeCurve::EqualPower), 1.0);
+
+        for sample in (0..=fade_in.length).step_by(100) {
+            let (a, b) = (fade_in.gain(sample), fade_out.gain(sample));
+            assert!((a * a + b * b - 1.0).abs() < 1e-4);
+        }
+
+        assert_eq!(fade_in.gain(fade_in.length), 1.0);
+        assert_eq!(fade_out.gain(fade_out.length), 0.0);
+    }
+
+    #[test]
+    fn declick_ramps_cover_one_frame() {
+        let mut out = [1.0; STEREO_FRAME_SIZE];
+        let mut into = [1.0; STEREO_FRAME_SIZE];
+        Declick::Out.apply(&mut out);
+        Declick::In.apply(&mut into);
+
+        assert_eq!(out[0], 1.0);
+        assert_eq!(into[0], 0.0);
+        assert!(out[STEREO_FRAME_SIZE - 1] < 1e-4);
+        assert!(into[STEREO_FRAME_SIZE - 1] > 1.0 - 1e-4);
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     input::Metadata,
 };
 use flume::Sender;
+use parking_lot::Mutex;
 use std::{fmt, sync::Arc, time::Duration};
 use tokio::sync::RwLock;
 use typemap_rev::TypeMap;
@@ -30,6 +31,7 @@ struct InnerHandle {
     seekable: bool,
     uuid: Uuid,
     metadata: Box<Metadata>,
+    current_metadata: Mutex<Metadata>,
     typemap: RwLock<TypeMap>,
 }
 
@@ -40,6 +42,7 @@ impl fmt::Debug for InnerHandle {
             .field("seekable", &self.seekable)
             .field("uuid", &self.uuid)
             .field("metadata", &self.metadata)
+            .field("current_metadata", &self.current_metadata)
             .field("typemap", &"<LOCK>")
             .finish()
     }
@@ -60,6 +63,7 @@ impl TrackHandle {
             command_channel,
             seekable,
             uuid,
+            current_metadata: Mutex::new((*metadata).clone()),
             metadata,
             typemap: RwLock::new(TypeMap::new()),
         });
@@ -92,6 +96,43 @@ impl TrackHandle {
         self.send(TrackCommand::Volume(volume))
     }
 
+    /// Gradually changes the volume of an audio track.
+    ///
+    /// A [`TrackEvent::FadeComplete`] event fires once the fade has finished.
+    ///
+    /// [`TrackEvent::FadeComplete`]: crate::events::TrackEvent::FadeComplete
+    pub fn fade(&self, fade: Fade) -> TrackResult<()> {
+        self.send(TrackCommand::Fade(fade))
+    }
+
+    /// Fades the volume of an audio track to `volume` over `duration`.
+    ///
+    /// A [`TrackEvent::FadeComplete`] event fires once the fade has finished.
+    ///
+    /// [`TrackEvent::FadeComplete`]: crate::events::TrackEvent::FadeComplete
+    pub fn fade_to(&self, volume: f32, duration: Duration, curve: FadeCurve) -> TrackResult<()> {
+        self.fade(Fade::to(volume, duration, curve))
+    }
+
+    /// Replaces all audio effects applied to a track.
+    ///
+    /// Passing an empty `Vec` removes all effects.
+    pub fn set_effects(&self, mut effects: Vec<Box<dyn AudioEffect>>) -> TrackResult<()> {
+        super::reserve_effects(&mut effects);
+        self.send(TrackCommand::Effects(EffectCommand::Set(effects)))
+    }
+
+    /// Appends an audio effect to the end of a track's effects chain.
+    ///
+    /// Chains longer than [`RESERVED_EFFECTS`] will reallocate within the mixer
+    /// as they grow: prefer [`set_effects`] when building large chains.
+    ///
+    /// [`RESERVED_EFFECTS`]: super::effects::RESERVED_EFFECTS
+    /// [`set_effects`]: Self::set_effects
+    pub fn add_effect(&self, effect: impl AudioEffect + 'static) -> TrackResult<()> {
+        self.send(TrackCommand::Effects(EffectCommand::Add(Box::new(effect))))
+    }
+
     /// Ready a track for playing if it is lazily initialised.
     ///
     /// Currently, only [`Restartable`] sources support lazy setup.
@@ -220,13 +261,32 @@ impl TrackHandle {
     ///
     /// Metadata is cloned from the inner [`Input`] at
     /// the time a track/handle is created, and is effectively
-    /// read-only from then on.
+    /// read-only from then on. See [`current_metadata`] for
+    /// sources whose metadata changes during playback.
     ///
     /// [`Input`]: crate::input::Input
+    /// [`current_metadata`]: Self::current_metadata
     pub fn metadata(&self) -> &Metadata {
         &self.inner.metadata
     }
 
+    /// Returns the most recent metadata reported by this track's source.
+    ///
+    /// For most sources, this matches [`metadata`]. Live sources (such as
+    /// [`icy`] radio streams) update their metadata while playing, firing a
+    /// [`TrackEvent::MetadataUpdate`] event each time.
+    ///
+    /// [`metadata`]: Self::metadata
+    /// [`icy`]: crate::input::icy
+    /// [`TrackEvent::MetadataUpdate`]: crate::events::TrackEvent::MetadataUpdate
+    pub fn current_metadata(&self) -> Metadata {
+        self.inner.current_metadata.lock().clone()
+    }
+
+    pub(crate) fn set_current_metadata(&self, metadata: Metadata) {
+        *self.inner.current_metadata.lock() = metadata;
+    }
+
     /// Allows access to this track's attached TypeMap.
     ///
     /// TypeMaps allow additional, user-defined data shared by all handles
@@ -15,18 +15,33 @@
 //! [`create_player`]: fn.create_player.html
 
 mod command;
+pub mod effects;
 mod error;
+mod fade;
 mod handle;
 mod looping;
 mod mode;
 mod queue;
 mod state;
 
-pub use self::{command::*, error::*, handle::*, looping::*, mode::*, queue::*, state::*};
+pub use self::{
+    command::*,
+    error::*,
+    fade::{Fade, FadeCurve},
+    handle::*,
+    looping::*,
+    mode::*,
+    queue::*,
+    state::*,
+};
+
+pub(crate) use fade::Declick;
+use fade::FadeState;
 
 use crate::{constants::*, driver::tasks::message::*, events::EventStore, input::Input};
-use flume::{Receiver, TryRecvError};
-use std::time::Duration;
+use effects::{AudioEffect, EffectCommand, RESERVED_EFFECTS};
+use flume::{Receiver, Sender, TryRecvError};
+use std::{mem, time::Duration};
 use uuid::Uuid;
 
 /// Control object for audio playback.
@@ -80,6 +95,20 @@ pub struct Track {
     /// [`volume`]: Track::volume
     pub(crate) volume: f32,
 
+    /// Effects applied to this track's audio before mixing, in order.
+    ///
+    /// Can be controlled with [`set_effects`] or [`add_effect`] if chaining is desired.
+    ///
+    /// [`set_effects`]: Track::set_effects
+    /// [`add_effect`]: Track::add_effect
+    pub(crate) effects: Vec<Box<dyn AudioEffect>>,
+
+    /// Volume change currently being applied to this track, if any.
+    pub(crate) fade: Option<FadeState>,
+
+    /// Ramp to apply on the next frame, following a user-initiated change in play state.
+    pub(crate) declick: Option<Declick>,
+
     /// Underlying data access object.
     ///
     /// *Calling code is not expected to use this.*
@@ -115,6 +144,14 @@ pub struct Track {
 
     /// Unique identifier for this track.
     pub(crate) uuid: Uuid,
+
+    /// Track whose end should cause this (paused) track to begin playing
+    /// within the mixer, for gapless playback.
+    pub(crate) start_after: Option<Uuid>,
+
+    /// Length and shape of the overlap with the end of the `start_after` track,
+    /// if the two should crossfade.
+    pub(crate) crossfade: Option<(Duration, FadeCurve)>,
 }
 
 impl Track {
@@ -130,6 +167,9 @@ impl Track {
         Self {
             playing: Default::default(),
             volume: 1.0,
+            effects: Vec::with_capacity(RESERVED_EFFECTS),
+            fade: None,
+            declick: None,
             source,
             position: Default::default(),
             play_time: Default::default(),
@@ -138,11 +178,15 @@ impl Track {
             handle,
             loops: LoopState::Finite(0),
             uuid,
+            start_after: None,
+            crossfade: None,
         }
     }
 
     /// Sets a track to playing if it is paused.
     pub fn play(&mut self) -> &mut Self {
+        self.start_after = None;
+        self.crossfade = None;
         self.set_playing(PlayMode::Play)
     }
 
@@ -180,15 +224,60 @@ impl Track {
     /// [`volume`]: Track::volume
     pub fn set_volume(&mut self, volume: f32) -> &mut Self {
         self.volume = volume;
+        self.fade = None;
+
+        self
+    }
+
+    /// Gradually changes this track's volume in a manner that allows method chaining.
+    ///
+    /// This replaces any fade already in progress.
+    pub fn fade(&mut self, fade: Fade) -> &mut Self {
+        self.fade = Some(FadeState::new(fade, self.volume));
 
         self
     }
 
+    /// Schedules a de-click ramp following a user-initiated change in play state.
+    fn update_declick(&mut self, was_playing: bool) {
+        let ramping_out = self.declick == Some(Declick::Out);
+
+        self.declick = match (was_playing, self.playing == PlayMode::Play) {
+            (true, false) => Some(Declick::Out),
+            // Tracks resumed mid-ramp never fell silent, and fresh tracks begin silent.
+            (false, true) if !ramping_out && self.position > Duration::ZERO => Some(Declick::In),
+            (false, true) => None,
+            _ => self.declick,
+        };
+    }
+
     /// Returns the current volume.
     pub fn volume(&self) -> f32 {
         self.volume
     }
 
+    /// Replaces all [`effects`] on this track in a manner that allows method chaining.
+    ///
+    /// [`effects`]: effects
+    pub fn set_effects(&mut self, mut effects: Vec<Box<dyn AudioEffect>>) -> &mut Self {
+        reserve_effects(&mut effects);
+        self.effects = effects;
+
+        self
+    }
+
+    /// Appends an effect to this track's chain in a manner that allows method chaining.
+    pub fn add_effect(&mut self, effect: impl AudioEffect + 'static) -> &mut Self {
+        self.effects.push(Box::new(effect));
+
+        self
+    }
+
+    /// Returns the effects currently applied to this track, in order.
+    pub fn effects(&self) -> &[Box<dyn AudioEffect>] {
+        &self.effects
+    }
+
     /// Returns the current playback position.
     pub fn position(&self) -> Duration {
         self.position
@@ -235,7 +324,12 @@ impl Track {
     /// Receives and acts upon any commands forwarded by TrackHandles.
     ///
     /// *Used internally*, this should not be exposed to users.
-    pub(crate) fn process_commands(&mut self, index: usize, ic: &Interconnect) {
+    pub(crate) fn process_commands(
+        &mut self,
+        index: usize,
+        ic: &Interconnect,
+        disposer: &Sender<DisposalMessage>,
+    ) {
         // Note: disconnection and an empty channel are both valid,
         // and should allow the audio object to keep running as intended.
 
@@ -249,21 +343,27 @@ impl Track {
                     use TrackCommand::*;
                     match cmd {
                         Play => {
+                            let was_playing = self.playing == PlayMode::Play;
                             self.play();
+                            self.update_declick(was_playing);
                             let _ = ic.events.send(EventMessage::ChangeState(
                                 index,
                                 TrackStateChange::Mode(self.playing),
                             ));
                         },
                         Pause => {
+                            let was_playing = self.playing == PlayMode::Play;
                             self.pause();
+                            self.update_declick(was_playing);
                             let _ = ic.events.send(EventMessage::ChangeState(
                                 index,
                                 TrackStateChange::Mode(self.playing),
                             ));
                         },
                         Stop => {
+                            let was_playing = self.playing == PlayMode::Play;
                             self.stop();
+                            self.update_declick(was_playing);
                             let _ = ic.events.send(EventMessage::ChangeState(
                                 index,
                                 TrackStateChange::Mode(self.playing),
@@ -304,6 +404,16 @@ impl Track {
                                 ));
                             },
                         MakePlayable => self.make_playable(),
+                        Fade(fade) => {
+                            self.fade(fade);
+                        },
+                        Effects(EffectCommand::Set(effects)) => {
+                            // Handles reserve space before sending, and freeing the old
+                            // chain is left to the disposal thread.
+                            let old = mem::replace(&mut self.effects, effects);
+                            let _ = disposer.send(DisposalMessage::Effects(old));
+                        },
+                        Effects(EffectCommand::Add(effect)) => self.effects.push(effect),
                     }
                 },
                 Err(TryRecvError::Disconnected) => {
@@ -315,6 +425,13 @@ impl Track {
                 },
             }
         }
+
+        if let Some(metadata) = self.source.poll_metadata() {
+            let _ = ic.events.send(EventMessage::ChangeState(
+                index,
+                TrackStateChange::Metadata(Box::new(metadata)),
+            ));
+        }
     }
 
     /// Ready a track for playing if it is lazily initialised.
@@ -353,6 +470,11 @@ impl Track {
     pub fn seek_time(&mut self, pos: Duration) -> TrackResult<Duration> {
         if let Some(t) = self.source.seek_time(pos) {
             self.position = t;
+
+            for effect in self.effects.iter_mut() {
+                effect.reset();
+            }
+
             Ok(t)
         } else {
             Err(TrackError::SeekUnsupported)
@@ -394,3 +516,10 @@ pub fn create_player_with_uuid(source: Input, uuid: Uuid) -> (Track, TrackHandle
 
     (player, handle)
 }
+
+/// Ensures a new effects chain has room for at least [`RESERVED_EFFECTS`],
+/// so that later additions need not allocate within the mixer.
+#[inline]
+fn reserve_effects(effects: &mut Vec<Box<dyn AudioEffect>>) {
+    effects.reserve(RESERVED_EFFECTS.saturating_sub(effects.len()));
+}
@@ -2,7 +2,7 @@ use crate::{
     driver::Driver,
     events::{Event, EventContext, EventData, EventHandler, TrackEvent},
     input::Input,
-    tracks::{self, Track, TrackHandle, TrackResult},
+    tracks::{self, FadeCurve, Track, TrackHandle, TrackResult},
 };
 use async_trait::async_trait;
 use parking_lot::Mutex;
@@ -22,6 +22,9 @@ use tracing::{info, warn};
 /// track and use this to run a song queue in many guilds in parallel.
 /// This code is trivial to extend if extra functionality is needed.
 ///
+/// By default, each track begins only once the previous track's end event has been
+/// handled. Tighter transitions can be configured using [`set_transition`].
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -55,6 +58,7 @@ use tracing::{info, warn};
 ///
 /// [`TrackEvent`]: crate::events::TrackEvent
 /// [`Driver::queue`]: crate::driver::Driver
+/// [`set_transition`]: TrackQueue::set_transition
 #[derive(Clone, Debug, Default)]
 pub struct TrackQueue {
     // NOTE: the choice of a parking lot mutex is quite deliberate
@@ -82,6 +86,54 @@ impl Queued {
     }
 }
 
+/// How a [`TrackQueue`] moves from one track to the next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum QueueTransition {
+    /// The next track is started after the current track's [`TrackEvent::End`]
+    /// has been handled.
+    ///
+    /// This leaves a gap of at least one 20ms frame between tracks.
+    ///
+    /// [`TrackEvent::End`]: crate::events::TrackEvent::End
+    Sequential,
+    /// The next track is readied as soon as it is next in line, and is started by the
+    /// mixer in the same frame that the current track ends.
+    Gapless,
+    /// The end of each track overlaps with the start of the next for `duration`,
+    /// fading one out while the other fades in.
+    ///
+    /// The overlap begins once the current track's position is `duration` from its end,
+    /// and the outgoing track remains at the head of the queue until it has faded out.
+    ///
+    /// Crossfades require that each track's [`Metadata::duration`] is known:
+    /// tracks without one transition gaplessly.
+    ///
+    /// [`Metadata::duration`]: crate::input::Metadata::duration
+    Crossfade {
+        /// Length of the overlap between tracks.
+        duration: Duration,
+        /// Shape of the fade applied to each track.
+        curve: FadeCurve,
+    },
+}
+
+impl QueueTransition {
+    fn crossfade(self) -> Option<(Duration, FadeCurve)> {
+        match self {
+            QueueTransition::Crossfade { duration, curve } => Some((duration, curve)),
+            _ => None,
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for QueueTransition {
+    fn default() -> Self {
+        QueueTransition::Sequential
+    }
+}
+
 #[derive(Debug, Default)]
 /// Inner portion of a [`TrackQueue`].
 ///
@@ -91,6 +143,7 @@ impl Queued {
 /// [`TrackQueue`]: TrackQueue
 struct TrackQueueCore {
     tracks: VecDeque<Queued>,
+    transition: QueueTransition,
 }
 
 struct QueueHandler {
@@ -122,16 +175,7 @@ impl EventHandler for QueueHandler {
         info!("Queued track ended: {:?}.", ctx);
         info!("{} tracks remain.", inner.tracks.len());
 
-        // Keep going until we find one track which works, or we run out.
-        while let Some(new) = inner.tracks.front() {
-            if new.play().is_err() {
-                // Discard files which cannot be used for whatever reason.
-                warn!("Track in Queue couldn't be played...");
-                inner.tracks.pop_front();
-            } else {
-                break;
-            }
-        }
+        inner.start_front();
 
         None
     }
@@ -160,6 +204,7 @@ impl TrackQueue {
         Self {
             inner: Arc::new(Mutex::new(TrackQueueCore {
                 tracks: VecDeque::new(),
+                transition: QueueTransition::Sequential,
             })),
         }
     }
@@ -192,8 +237,17 @@ impl TrackQueue {
 
         let track_handle = track.handle.clone();
 
-        if !inner.tracks.is_empty() {
+        if let Some(tail) = inner.tracks.back() {
             track.pause();
+
+            if inner.transition != QueueTransition::Sequential {
+                track.start_after = Some(tail.uuid());
+                track.crossfade = inner.transition.crossfade();
+
+                if inner.tracks.len() == 1 {
+                    let _ = track.handle.make_playable();
+                }
+            }
         }
 
         track
@@ -225,6 +279,21 @@ impl TrackQueue {
         inner.tracks.push_back(Queued(track_handle));
     }
 
+    /// Sets how this queue moves between tracks.
+    ///
+    /// This applies to every queued track which has not yet started.
+    pub fn set_transition(&self, transition: QueueTransition) {
+        let mut inner = self.inner.lock();
+
+        inner.transition = transition;
+        inner.link_tracks();
+    }
+
+    /// Returns how this queue moves between tracks.
+    pub fn transition(&self) -> QueueTransition {
+        self.inner.lock().transition
+    }
+
     /// Returns a handle to the currently playing track.
     pub fn current(&self) -> Option<TrackHandle> {
         let inner = self.inner.lock();
@@ -264,7 +333,12 @@ impl TrackQueue {
         F: FnOnce(&mut VecDeque<Queued>) -> O,
     {
         let mut inner = self.inner.lock();
-        func(&mut inner.tracks)
+        let out = func(&mut inner.tracks);
+
+        // Tracks may have been reordered, so gapless successors must be updated.
+        inner.link_tracks();
+
+        out
     }
 
     /// Pause the track at the head of the queue.
@@ -322,6 +396,43 @@ impl TrackQueue {
 }
 
 impl TrackQueueCore {
+    /// Starts the track at the head of the queue, discarding any which cannot be played.
+    fn start_front(&mut self) {
+        // Keep going until we find one track which works, or we run out.
+        while let Some(new) = self.tracks.front() {
+            if new.play().is_err() {
+                // Discard files which cannot be used for whatever reason.
+                warn!("Track in Queue couldn't be played...");
+                self.tracks.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.transition != QueueTransition::Sequential {
+            if let Some(next) = self.tracks.get(1) {
+                let _ = next.make_playable();
+            }
+        }
+    }
+
+    /// Points each waiting track at its predecessor, so that the mixer starts it gaplessly.
+    fn link_tracks(&self) {
+        let gapless = self.transition != QueueTransition::Sequential;
+        let crossfade = self.transition.crossfade();
+
+        for (prev, track) in self.tracks.iter().zip(self.tracks.iter().skip(1)) {
+            let prev = if gapless { Some(prev.uuid()) } else { None };
+
+            let _ = track.action(move |t| {
+                if !t.playing().is_done() {
+                    t.start_after = prev;
+                    t.crossfade = crossfade;
+                }
+            });
+        }
+    }
+
     /// Skip to the next track in the queue, if it exists.
     fn stop_current(&self) -> TrackResult<()> {
         if let Some(handle) = self.tracks.front() {
@@ -1,18 +1,39 @@
-use crate::model::Event;
+use crate::model::{Event, OpCode};
 
 use async_trait::async_trait;
 use async_tungstenite::{
     self as tungstenite,
-    tokio::ConnectStream,
     tungstenite::{error::Error as TungsteniteError, protocol::CloseFrame, Message},
-    WebSocketStream,
 };
-use futures::{SinkExt, StreamExt, TryStreamExt};
-use serde_json::Error as JsonError;
+use futures::{Sink, SinkExt, Stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde_json::{json, Error as JsonError, Value};
 use tokio::time::{timeout, Duration};
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, trace};
 
-pub type WsStream = WebSocketStream<ConnectStream>;
+/// A websocket connection to a voice gateway, after its handshake has completed.
+///
+/// This is implemented for all `async_tungstenite` websocket streams.
+pub trait WsConnection:
+    Stream<Item = std::result::Result<Message, TungsteniteError>>
+    + Sink<Message, Error = TungsteniteError>
+    + Send
+    + Unpin
+{
+}
+
+impl<T> WsConnection for T where
+    T: Stream<Item = std::result::Result<Message, TungsteniteError>>
+        + Sink<Message, Error = TungsteniteError>
+        + Send
+        + Unpin
+{
+}
+
+/// A websocket connection opened by a [`Transport`].
+///
+/// [`Transport`]: crate::driver::transport::Transport
+pub type WsStream = Box<dyn WsConnection>;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -59,20 +80,26 @@ use std::{
 };
 use url::Url;
 
+/// A gateway event, along with its sequence number (if the voice server sent one).
+///
+/// The event is `None` for any opcodes which songbird does not understand, which
+/// must still be acknowledged via their sequence number.
+pub type SeqEvent = (Option<Event>, Option<u64>);
+
 #[async_trait]
 pub trait ReceiverExt {
-    async fn recv_json(&mut self) -> Result<Option<Event>>;
-    async fn recv_json_no_timeout(&mut self) -> Result<Option<Event>>;
+    async fn recv_json(&mut self) -> Result<Option<SeqEvent>>;
+    async fn recv_json_no_timeout(&mut self) -> Result<Option<SeqEvent>>;
 }
 
 #[async_trait]
 pub trait SenderExt {
-    async fn send_json(&mut self, value: &Event) -> Result<()>;
+    async fn send_json<T: Serialize + Sync>(&mut self, value: &T) -> Result<()>;
 }
 
 #[async_trait]
 impl ReceiverExt for WsStream {
-    async fn recv_json(&mut self) -> Result<Option<Event>> {
+    async fn recv_json(&mut self) -> Result<Option<SeqEvent>> {
         const TIMEOUT: Duration = Duration::from_millis(500);
 
         let ws_message = match timeout(TIMEOUT, self.next()).await {
@@ -84,14 +111,14 @@ impl ReceiverExt for WsStream {
         convert_ws_message(ws_message)
     }
 
-    async fn recv_json_no_timeout(&mut self) -> Result<Option<Event>> {
+    async fn recv_json_no_timeout(&mut self) -> Result<Option<SeqEvent>> {
         convert_ws_message(self.try_next().await?)
     }
 }
 
 #[async_trait]
 impl SenderExt for SplitSink<WsStream, Message> {
-    async fn send_json(&mut self, value: &Event) -> Result<()> {
+    async fn send_json<T: Serialize + Sync>(&mut self, value: &T) -> Result<()> {
         Ok(serde_json::to_string(value)
             .map(Message::Text)
             .map_err(Error::from)
@@ -102,7 +129,7 @@ impl SenderExt for SplitSink<WsStream, Message> {
 
 #[async_trait]
 impl SenderExt for WsStream {
-    async fn send_json(&mut self, value: &Event) -> Result<()> {
+    async fn send_json<T: Serialize + Sync>(&mut self, value: &T) -> Result<()> {
         Ok(serde_json::to_string(value)
             .map(Message::Text)
             .map_err(Error::from)
@@ -112,9 +139,9 @@ impl SenderExt for WsStream {
 }
 
 #[inline]
-pub(crate) fn convert_ws_message(message: Option<Message>) -> Result<Option<Event>> {
+pub(crate) fn convert_ws_message(message: Option<Message>) -> Result<Option<SeqEvent>> {
     Ok(match message {
-        Some(Message::Text(payload)) => serde_json::from_str(&payload)
+        Some(Message::Text(payload)) => parse_event(&payload)
             .map_err(|e| {
                 debug!("Unexpected JSON {payload:?}.");
                 e
@@ -131,6 +158,61 @@ pub(crate) fn convert_ws_message(message: Option<Message>) -> Result<Option<Even
     })
 }
 
+/// Parses a voice gateway message, separating out its sequence number.
+///
+/// `serenity-voice-model` describes voice gateway v4, and so rejects the additional
+/// fields and payload shapes of later versions: these are normalised here.
+fn parse_event(payload: &str) -> std::result::Result<SeqEvent, JsonError> {
+    let mut value: Value = serde_json::from_str(payload)?;
+
+    let seq = value
+        .as_object_mut()
+        .and_then(|map| map.remove("seq"))
+        .and_then(|seq| seq.as_u64());
+
+    // Later versions add opcodes (e.g., 11, 18 and 20, describing other clients) which
+    // have no typed model: these are skipped, while still counting towards `seq_ack`.
+    if serde_json::from_value::<OpCode>(value["op"].clone()).is_err() {
+        trace!("Skipping unknown voice gateway opcode {}.", value["op"]);
+        return Ok((None, seq));
+    }
+
+    // v8 heartbeat ACKs echo the nonce as `{"t": nonce}`, rather than alone.
+    if value["op"] == OpCode::HeartbeatAck as u8 {
+        if let Some(nonce) = value["d"].get("t").cloned() {
+            value["d"] = nonce;
+        }
+    }
+
+    Ok((Some(serde_json::from_str(&value.to_string())?), seq))
+}
+
+/// Creates a v8 heartbeat, acknowledging all messages up to `seq_ack`.
+pub(crate) fn heartbeat(nonce: u64, seq_ack: Option<u64>) -> Value {
+    json!({
+        "op": OpCode::Heartbeat as u8,
+        "d": {
+            "t": nonce,
+            "seq_ack": seq_ack,
+        },
+    })
+}
+
+/// Adds the last acknowledged sequence number to a resume request, so that
+/// the voice server can replay any messages which were missed.
+pub(crate) fn with_seq_ack(
+    resume: &Event,
+    seq_ack: Option<u64>,
+) -> std::result::Result<Value, JsonError> {
+    let mut value = serde_json::to_value(resume)?;
+
+    if let Some(seq_ack) = seq_ack {
+        value["d"]["seq_ack"] = seq_ack.into();
+    }
+
+    Ok(value)
+}
+
 /// An error that occured while connecting over rustls
 #[derive(Debug)]
 #[non_exhaustive]
@@ -185,7 +267,7 @@ pub(crate) async fn create_rustls_client(url: Url) -> Result<WsStream> {
     .await
     .map_err(|_| RustlsError::HandshakeError)?;
 
-    Ok(stream)
+    Ok(Box::new(stream))
 }
 
 #[cfg(feature = "native-marker")]
@@ -202,5 +284,36 @@ pub(crate) async fn create_native_tls_client(url: Url) -> Result<WsStream> {
     )
     .await?;
 
-    Ok(stream)
+    Ok(Box::new(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v8_messages_parse() {
+        let (ack, seq) = parse_event(r#"{"op":6,"d":{"t":1501184119561}}"#).unwrap();
+        assert!(matches!(ack, Some(Event::HeartbeatAck(a)) if a.nonce == 1501184119561));
+        assert_eq!(seq, None);
+
+        let (disconnect, seq) =
+            parse_event(r#"{"op":13,"seq":10,"d":{"user_id":"1234"}}"#).unwrap();
+        assert!(matches!(disconnect, Some(Event::ClientDisconnect(_))));
+        assert_eq!(seq, Some(10));
+    }
+
+    #[test]
+    fn unknown_opcodes_are_skipped_but_sequenced() {
+        let (event, seq) =
+            parse_event(r#"{"op":11,"seq":12,"d":{"user_ids":["1234","5678"]}}"#).unwrap();
+        assert!(event.is_none());
+        assert_eq!(seq, Some(12));
+
+        let msg = Message::Text(r#"{"op":18,"seq":13,"d":{"any":"thing"}}"#.into());
+        assert!(matches!(
+            convert_ws_message(Some(msg)),
+            Ok(Some((None, Some(13))))
+        ));
+    }
 }